@@ -0,0 +1,47 @@
+//! Spreads the messages of a schedule over its minor frames, see
+//! [`Schedule::balance`](milisse::schedule::Schedule::balance).
+//!
+//! Usage: `milisse-balance <schedule>`
+//!
+//! Prints the balanced schedule, which `milisse-sim` and `milisse-check`
+//! read back, and the peak frame load before and after on stderr.
+use std::process::ExitCode;
+
+use milisse::schedule::Schedule;
+
+fn main() -> ExitCode {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: milisse-balance <schedule>");
+        return ExitCode::FAILURE;
+    };
+    let mut schedule = match std::fs::read_to_string(&path).map(|text| text.parse::<Schedule>()) {
+        Ok(Ok(schedule)) => schedule,
+        Ok(Err(err)) => {
+            eprintln!(
+                "milisse-balance: {}: line {}: {:?}",
+                path, err.line, err.kind
+            );
+            return ExitCode::FAILURE;
+        }
+        Err(err) => {
+            eprintln!("milisse-balance: {}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let before = schedule.peak_load_ns();
+    schedule.balance();
+    print!("{}", schedule);
+
+    let jitter = (0..schedule.entries().len())
+        .map(|entry| schedule.jitter_ns(entry))
+        .max()
+        .unwrap_or(0);
+    eprintln!(
+        "peak load {} ns, was {} ns, of {} ns minor frames; worst jitter {} ns",
+        schedule.peak_load_ns(),
+        before,
+        schedule.minor_frame_ns(),
+        jitter
+    );
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,133 @@
+//! Checks the BC schedule of a scenario or schedule file, see
+//! [`Schedule::check`](milisse::schedule::Schedule::check).
+//!
+//! Usage: `milisse-check [--icd FILE] <scenario>`
+//!
+//! Prints one line per problem and fails if there is any. RT legality is
+//! only checked for the RTs the scenario declares.
+use std::process::ExitCode;
+
+use milisse::{icd::Icd, scenario::Scenario, schedule::Finding};
+
+const USAGE: &str = "usage: milisse-check [--icd FILE] <scenario>";
+
+#[derive(Debug, Default)]
+struct Options {
+    icd: Option<String>,
+    path: String,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--icd" => options.icd = Some(args.next().ok_or("--icd needs a value")?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option: {}", arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(String::from("more than one scenario")),
+        }
+    }
+    options.path = path.ok_or("no scenario")?;
+    Ok(options)
+}
+
+fn load_icd(path: &str) -> Result<Icd, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    text.parse()
+        .map_err(|err: milisse::icd::IcdError| format!("line {}: {:?}", err.line, err.kind))
+}
+
+fn main() -> ExitCode {
+    let Options {
+        icd: icd_path,
+        path,
+    } = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("milisse-check: {}\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    let icd = match icd_path.as_deref().map(load_icd).transpose() {
+        Ok(icd) => icd,
+        Err(err) => {
+            eprintln!("milisse-check: {}: {}", icd_path.unwrap_or_default(), err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let scenario = match std::fs::read_to_string(&path).map(|text| text.parse::<Scenario>()) {
+        Ok(Ok(scenario)) => scenario,
+        Ok(Err(err)) => {
+            eprintln!("milisse-check: {}: line {}: {:?}", path, err.line, err.kind);
+            return ExitCode::FAILURE;
+        }
+        Err(err) => {
+            eprintln!("milisse-check: {}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let schedule = scenario.schedule();
+    let findings = schedule.check(icd.as_ref(), scenario.rts());
+    let name = |entry: usize| &schedule.entries()[entry].name;
+    for finding in &findings {
+        match *finding {
+            Finding::FrameOverflow { frame, load_ns } => println!(
+                "minor frame {}: {} ns of messages in {} ns",
+                frame,
+                load_ns,
+                schedule.minor_frame_ns()
+            ),
+            Finding::RateTooLow {
+                entry,
+                rate_hz,
+                required_hz,
+            } => println!(
+                "{}: sent at {} Hz, the ICD wants {} Hz",
+                name(entry),
+                rate_hz,
+                required_hz
+            ),
+            Finding::BroadcastModeCode { entry, code } => {
+                println!("{}: {:?} can't be broadcast", name(entry), code)
+            }
+            Finding::WordCount {
+                entry,
+                command,
+                icd,
+            } => println!("{}: {} but the ICD has {} words", name(entry), command, icd),
+            Finding::IllegalCommand { entry, command, rt } => {
+                println!("{}: {} is illegal at RT {}", name(entry), command, rt)
+            }
+        }
+    }
+    if findings.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Result<Options, String> {
+        parse_args(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parses_the_options() {
+        let options = args("--icd x.icd in").unwrap();
+        assert_eq!(options.icd.as_deref(), Some("x.icd"));
+        assert_eq!(options.path, "in");
+        let options = args("in").unwrap();
+        assert_eq!(options.icd, None);
+
+        assert_eq!(args("in --icd").unwrap_err(), "--icd needs a value");
+        assert_eq!(args("--all in").unwrap_err(), "unknown option: --all");
+        assert_eq!(args("a b").unwrap_err(), "more than one scenario");
+        assert_eq!(args("--icd x.icd").unwrap_err(), "no scenario");
+    }
+}
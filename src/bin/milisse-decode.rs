@@ -0,0 +1,383 @@
+//! Prints the messages of a capture, disassembled.
+//!
+//! Usage: `milisse-decode [options] <file>`
+//!
+//! Reads hex word dumps, IRIG 106 Chapter 10 recordings (`.ch10`, `.c10`)
+//! and the crate's pcapng, CSV and JSON Lines files, by extension unless
+//! `--format` says otherwise. A hex dump has one message per line, its
+//! words in bus order, optionally after `@` and a time in ns, and the bus:
+//!
+//! ```text
+//! @1000 A 2822 FFF6 3000 2800
+//! ```
+//!
+//! A receive command followed by more words than its word count and status
+//! allow is taken for an RT to RT transfer.
+//!
+//! Options:
+//!
+//! - `--format hex|ch10|pcapng|csv|jsonl`
+//! - `--rt N`: only messages to or from RT N.
+//! - `--sa N`: only data transfers to or from subaddress N.
+//! - `--mode-code N`: only mode commands with code N.
+//! - `--errors`: only messages with errors, or whose words don't add up.
+//! - `--icd FILE`: decode the signals of the messages it lists.
+use std::io::{self, BufWriter, Write};
+use std::process::ExitCode;
+
+use milisse::{
+    bus::BusId,
+    ch10::Ch10Reader,
+    disasm::Disassembly,
+    icd::Icd,
+    manchester::WORD_NS,
+    message::{Message, MessageFormat},
+    pcapng::PcapngReader,
+    tabular,
+    words::{CommandWord, Word},
+};
+
+const USAGE: &str = "usage: milisse-decode [--format hex|ch10|pcapng|csv|jsonl] [--rt N] [--sa N] \
+                     [--mode-code N] [--errors] [--icd FILE] <file>";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Hex,
+    Ch10,
+    Pcapng,
+    Csv,
+    Jsonl,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "hex" => Some(Format::Hex),
+            "ch10" | "c10" => Some(Format::Ch10),
+            "pcapng" => Some(Format::Pcapng),
+            "csv" => Some(Format::Csv),
+            "jsonl" => Some(Format::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    format: Option<Format>,
+    rt: Option<u16>,
+    subaddress: Option<u16>,
+    mode_code: Option<u16>,
+    errors_only: bool,
+    icd: Option<String>,
+    path: String,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        let number = |name: &str, text: String| {
+            text.parse::<u16>()
+                .ok()
+                .filter(|n| *n <= 31)
+                .ok_or(format!("bad {}: {}", name, text))
+        };
+        match arg.as_str() {
+            "--format" => {
+                let name = value("--format")?;
+                options.format =
+                    Some(Format::from_name(&name).ok_or(format!("unknown format: {}", name))?);
+            }
+            "--rt" => options.rt = Some(number("RT", value("--rt")?)?),
+            "--sa" => options.subaddress = Some(number("subaddress", value("--sa")?)?),
+            "--mode-code" => options.mode_code = Some(number("mode code", value("--mode-code")?)?),
+            "--errors" => options.errors_only = true,
+            "--icd" => options.icd = Some(value("--icd")?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option: {}", arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(String::from("more than one file")),
+        }
+    }
+    options.path = path.ok_or(String::from("no file"))?;
+    Ok(options)
+}
+
+/// A hex dump, one message per line.
+fn read_hex(text: &str) -> Result<Vec<Message>, String> {
+    let mut messages = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let error = |what: &str| format!("line {}: {}", idx + 1, what);
+        let mut tokens = line.split_whitespace().peekable();
+        let time_ns: u64 = match tokens.next_if(|token| token.starts_with('@')) {
+            Some(time) => time[1..].parse().map_err(|_| error("bad time"))?,
+            None => 0,
+        };
+        let bus = match tokens.next_if(|token| *token == "A" || *token == "B") {
+            Some("B") => BusId::B,
+            _ => BusId::A,
+        };
+        let words = tokens
+            .map(|token| u16::from_str_radix(token.trim_start_matches("0x"), 16))
+            .collect::<Result<Vec<u16>, _>>()
+            .map_err(|_| error("bad word"))?;
+        let Some(&first) = words.first() else {
+            continue;
+        };
+        let first = CommandWord::from_u16(first);
+        let format = MessageFormat::of(&first);
+        let expected = Message::new(bus, first).expected_data_words();
+        let rt_to_rt = format.with_transmit_command().is_some()
+            && words.len() > 1 + expected + format.status_words();
+        let mut message = Message::from_raw_words(bus, rt_to_rt, &words).unwrap();
+        let end_ns = time_ns
+            .checked_add(words.len() as u64 * WORD_NS as u64)
+            .ok_or_else(|| error("bad time"))?;
+        message.set_time(time_ns, end_ns);
+        messages.push(message);
+    }
+    Ok(messages)
+}
+
+fn load(format: Format, bytes: &[u8]) -> Result<Vec<Message>, String> {
+    let text = || std::str::from_utf8(bytes).map_err(|_| String::from("not a text file"));
+    match format {
+        Format::Hex => read_hex(text()?),
+        Format::Ch10 => Ch10Reader::new(bytes)
+            .collect::<Result<_, _>>()
+            .map_err(|err| format!("{:?}", err)),
+        Format::Pcapng => PcapngReader::new(bytes)
+            .collect::<io::Result<_>>()
+            .map_err(|err| err.to_string()),
+        Format::Csv => tabular::read_csv(text()?).map_err(|err| format!("{:?}", err)),
+        Format::Jsonl => tabular::read_jsonl(text()?).map_err(|err| format!("{:?}", err)),
+    }
+}
+
+fn wanted(options: &Options, message: &Message) -> bool {
+    let fields = |cmd: &CommandWord| {
+        let value = cmd.value();
+        (value >> 11, (value >> 5) & 0x1F, value & 0x1F)
+    };
+    let mode = message.format().is_mode_command();
+    let commands = message.commands();
+    let any = |pred: &dyn Fn((u16, u16, u16)) -> bool| commands.iter().any(|c| pred(fields(c)));
+    options.rt.is_none_or(|rt| any(&|(addr, _, _)| addr == rt))
+        && options
+            .subaddress
+            .is_none_or(|sa| !mode && any(&|(_, subaddress, _)| subaddress == sa))
+        && options
+            .mode_code
+            .is_none_or(|code| mode && any(&|(_, _, field)| field == code))
+        && (!options.errors_only || message.errors().any() || message.validate().is_err())
+}
+
+fn print(out: &mut impl Write, message: &Message, icd: Option<&Icd>) -> io::Result<()> {
+    let words: Vec<Word> = message.words().collect();
+    let bus = match message.bus() {
+        BusId::A => 'A',
+        BusId::B => 'B',
+    };
+    write!(
+        out,
+        "{:>12} {}  {}",
+        message.start_ns(),
+        bus,
+        Disassembly(&words)
+    )?;
+    let errors = message.errors();
+    let flags = [
+        (errors.no_response, "no_response"),
+        (errors.word_error, "word_error"),
+        (errors.wrong_address, "wrong_address"),
+        (errors.word_count, "word_count"),
+        (errors.sequence, "sequence"),
+    ];
+    for (_, name) in flags.iter().filter(|(set, _)| *set) {
+        write!(out, "  !{}", name)?;
+    }
+    if let Err(err) = message.validate() {
+        write!(out, "  !inconsistent: {:?}", err)?;
+    }
+    writeln!(out)?;
+
+    let Some(icd) = icd else {
+        return Ok(());
+    };
+    let found = icd.find_command(&message.command()).or_else(|| {
+        message
+            .transmit_command()
+            .and_then(|cmd| icd.find_command(&cmd))
+    });
+    let Some(idx) = found else {
+        return Ok(());
+    };
+    let name = &icd.messages()[idx].name;
+    for signal in icd.signals_of(idx) {
+        if let Some(value) = signal.value(message.data()) {
+            writeln!(
+                out,
+                "{:>16}{}.{} = {} {}",
+                "", name, signal.name, value, signal.units
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("milisse-decode: {}\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    let icd = match &options.icd {
+        None => None,
+        Some(path) => match std::fs::read_to_string(path).map(|text| text.parse::<Icd>()) {
+            Ok(Ok(icd)) => Some(icd),
+            Ok(Err(err)) => {
+                eprintln!(
+                    "milisse-decode: {}: line {}: {:?}",
+                    path, err.line, err.kind
+                );
+                return ExitCode::FAILURE;
+            }
+            Err(err) => {
+                eprintln!("milisse-decode: {}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    let format = options.format.unwrap_or_else(|| {
+        let extension = options.path.rsplit_once('.').map(|(_, ext)| ext);
+        extension.and_then(Format::from_name).unwrap_or(Format::Hex)
+    });
+    let messages = match std::fs::read(&options.path)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| load(format, &bytes))
+    {
+        Ok(messages) => messages,
+        Err(err) => {
+            eprintln!("milisse-decode: {}: {}", options.path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut shown = 0;
+    for message in messages.iter().filter(|m| wanted(&options, m)) {
+        if print(&mut out, message, icd.as_ref()).is_err() {
+            // Output closed, e.g. piped into head.
+            return ExitCode::SUCCESS;
+        }
+        shown += 1;
+    }
+    let _ = out.flush();
+    eprintln!("{} of {} messages", shown, messages.len());
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Result<Options, String> {
+        parse_args(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parses_the_options() {
+        let options =
+            args("--format csv --rt 5 --sa 1 --mode-code 2 --errors --icd x.icd in").unwrap();
+        assert_eq!(options.format, Some(Format::Csv));
+        assert_eq!(
+            (options.rt, options.subaddress, options.mode_code),
+            (Some(5), Some(1), Some(2))
+        );
+        assert!(options.errors_only);
+        assert_eq!(options.icd.as_deref(), Some("x.icd"));
+        assert_eq!(options.path, "in");
+
+        assert_eq!(args("--rt").unwrap_err(), "--rt needs a value");
+        assert_eq!(args("--rt 32 in").unwrap_err(), "bad RT: 32");
+        assert_eq!(args("--format txt in").unwrap_err(), "unknown format: txt");
+        assert_eq!(args("--all in").unwrap_err(), "unknown option: --all");
+        assert_eq!(args("a b").unwrap_err(), "more than one file");
+        assert_eq!(args("--errors").unwrap_err(), "no file");
+    }
+
+    #[test]
+    fn reads_hex_dumps() {
+        let messages = read_hex(
+            "@1000 B 2822 0xFFF6 3000 2800\n\
+             \n\
+             2821 3441 3000 1234 2800\n\
+             2821 0001 2800\n",
+        )
+        .unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].bus(), BusId::B);
+        assert_eq!(messages[0].start_ns(), 1000);
+        assert_eq!(messages[0].data().len(), 2);
+        // More words than a BC to RT transfer holds: RT 6 sends to RT 5.
+        assert_eq!(messages[1].format(), MessageFormat::RtToRt);
+        assert_eq!(messages[1].data()[0].value(), 0x1234);
+        assert_eq!(messages[2].format(), MessageFormat::BcToRt);
+        assert!(messages.iter().all(|m| m.validate().is_ok()));
+
+        assert_eq!(read_hex("2821\n@x 2821").unwrap_err(), "line 2: bad time");
+        assert_eq!(
+            read_hex("@18446744073709551615 2822").unwrap_err(),
+            "line 1: bad time"
+        );
+        let last = read_hex("@18446744073709531615 2822").unwrap();
+        assert_eq!(last[0].end_ns(), u64::MAX);
+        assert_eq!(read_hex("2821 xyz").unwrap_err(), "line 1: bad word");
+    }
+
+    #[test]
+    fn filters_messages() {
+        let messages = read_hex(
+            "2822 0001 0002 2800\n\
+             2821 3441 3000 1234 2800\n\
+             2FE2 2800\n\
+             2822 0001 0002\n",
+        )
+        .unwrap();
+        let shown = |options: Options| {
+            let shown = messages.iter().enumerate();
+            let shown = shown.filter(|(_, message)| wanted(&options, message));
+            shown.map(|(idx, _)| idx).collect::<Vec<_>>()
+        };
+        assert_eq!(shown(Options::default()), [0, 1, 2, 3]);
+        // RT 6 only transmits in the RT to RT transfer.
+        let rt = |rt| Options {
+            rt: Some(rt),
+            ..Options::default()
+        };
+        assert_eq!(shown(rt(6)), [1]);
+        assert_eq!(shown(rt(5)), [0, 1, 2, 3]);
+        assert_eq!(shown(rt(7)), []);
+        // Mode codes go through subaddress 31, which is not a data transfer.
+        let sa = |sa| Options {
+            subaddress: Some(sa),
+            ..Options::default()
+        };
+        assert_eq!(shown(sa(2)), [1]);
+        assert_eq!(shown(sa(31)), []);
+        let mode_code = |code| Options {
+            mode_code: Some(code),
+            ..Options::default()
+        };
+        assert_eq!(shown(mode_code(2)), [2]);
+        assert_eq!(shown(mode_code(1)), []);
+        let errors = Options {
+            errors_only: true,
+            ..Options::default()
+        };
+        assert_eq!(shown(errors), [3]);
+    }
+}
@@ -0,0 +1,31 @@
+//! Runs a virtual 1553 bus that processes join over a Unix domain socket.
+//!
+//! Usage: `milisse-hub <socket path>`
+use std::os::unix::fs::FileTypeExt;
+use std::process::ExitCode;
+
+use milisse::unix::UnixHub;
+
+fn main() -> ExitCode {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: milisse-hub <socket path>");
+        return ExitCode::FAILURE;
+    };
+    // A socket left behind by an earlier hub, but nothing else.
+    if std::fs::metadata(&path).is_ok_and(|meta| meta.file_type().is_socket()) {
+        let _ = std::fs::remove_file(&path);
+    }
+    let hub = match UnixHub::bind(&path) {
+        Ok(hub) => hub,
+        Err(err) => {
+            eprintln!("milisse-hub: can't listen on {}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+    eprintln!("milisse-hub: listening on {}", path);
+    if let Err(err) = hub.run() {
+        eprintln!("milisse-hub: {}", err);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,155 @@
+//! Runs a scenario file on the in-process simulator, see
+//! [`milisse::scenario`].
+//!
+//! Usage: `milisse-sim <scenario> [trace]`
+//!
+//! Prints a summary of the run, per scheduled message and per busy RT
+//! subaddress. The trace, if asked for, gets every message as it went, in
+//! pcapng, CSV or JSON Lines after its extension.
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process::ExitCode;
+
+use milisse::{
+    message::Message,
+    pcapng::PcapngWriter,
+    scenario::{Outcome, Scenario},
+    tabular,
+};
+
+const USAGE: &str = "usage: milisse-sim <scenario> [trace]";
+
+#[derive(Debug, Default)]
+struct Options {
+    path: String,
+    trace: Option<String>,
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut paths = Vec::new();
+    for arg in args {
+        if arg.starts_with("--") {
+            return Err(format!("unknown option: {}", arg));
+        }
+        paths.push(arg);
+    }
+    if paths.len() > 2 {
+        return Err(String::from("more than one trace"));
+    }
+    let mut paths = paths.into_iter();
+    Ok(Options {
+        path: paths.next().ok_or("no scenario")?,
+        trace: paths.next(),
+    })
+}
+
+fn write_trace(path: &str, messages: &[Message]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let extension = path.rsplit_once('.').map(|(_, ext)| ext);
+    if !matches!(extension, Some("csv" | "jsonl")) {
+        let mut writer = PcapngWriter::new(&mut out)?;
+        for message in messages {
+            writer.write(message)?;
+        }
+        return out.flush();
+    }
+    let mut text = String::new();
+    if extension == Some("csv") {
+        let _ = tabular::write_csv_header(&mut text);
+    }
+    for message in messages {
+        let _ = match extension {
+            Some("csv") => tabular::write_csv(&mut text, message, None),
+            _ => tabular::write_jsonl(&mut text, message, None),
+        };
+    }
+    out.write_all(text.as_bytes())?;
+    out.flush()
+}
+
+fn report(out: &mut impl Write, scenario: &Scenario, outcome: &Outcome) -> io::Result<()> {
+    writeln!(
+        out,
+        "{} messages in {} minor frames, {} overran, {} faults injected",
+        outcome.messages.len(),
+        outcome.minor_frames,
+        outcome.overruns,
+        outcome.faults_injected
+    )?;
+    writeln!(
+        out,
+        "{:<20} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8}",
+        "message", "sent", "errors", "no_resp", "msg_err", "busy", "resched", "gave_up"
+    )?;
+    let entries = scenario.schedule().entries();
+    for (entry, stats) in entries.iter().zip(&outcome.stats) {
+        writeln!(
+            out,
+            "{:<20} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8}",
+            entry.name,
+            stats.sent,
+            stats.errors,
+            stats.no_response,
+            stats.message_error,
+            stats.busy,
+            stats.rescheduled,
+            stats.gave_up
+        )?;
+    }
+    for (rt, subaddress, count) in outcome.busy.iter() {
+        writeln!(out, "RT {} SA {}: busy {} times", rt, subaddress, count)?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let Options { path, trace } = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("milisse-sim: {}\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    let scenario = match std::fs::read_to_string(&path).map(|text| text.parse::<Scenario>()) {
+        Ok(Ok(scenario)) => scenario,
+        Ok(Err(err)) => {
+            eprintln!("milisse-sim: {}: line {}: {:?}", path, err.line, err.kind);
+            return ExitCode::FAILURE;
+        }
+        Err(err) => {
+            eprintln!("milisse-sim: {}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let outcome = scenario.run();
+    if let Some(trace) = trace {
+        if let Err(err) = write_trace(&trace, &outcome.messages) {
+            eprintln!("milisse-sim: {}: {}", trace, err);
+            return ExitCode::FAILURE;
+        }
+    }
+    let _ = report(&mut io::stdout().lock(), &scenario, &outcome);
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Result<Options, String> {
+        parse_args(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parses_the_options() {
+        let options = args("in out.pcapng").unwrap();
+        assert_eq!(options.path, "in");
+        assert_eq!(options.trace.as_deref(), Some("out.pcapng"));
+        assert_eq!(args("in").unwrap().trace, None);
+
+        assert_eq!(args("--trace in").unwrap_err(), "unknown option: --trace");
+        assert_eq!(args("a b c").unwrap_err(), "more than one trace");
+        assert_eq!(args("").unwrap_err(), "no scenario");
+    }
+}
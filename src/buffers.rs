@@ -0,0 +1,287 @@
+use crate::words::*;
+
+/// Most data words a single message can carry.
+pub const MAX_DATA_WORDS: usize = 32;
+/// Message slots available to each subaddress buffer.
+pub const BUFFER_SLOTS: usize = 4;
+/// Slots of a circular buffer: one more than it holds, so that a message
+/// can arrive into a full ring without overwriting the oldest one before
+/// it is known to be valid.
+const RING: usize = BUFFER_SLOTS + 1;
+
+/// How a subaddress buffer hands messages between the bus and the
+/// application, following what commercial RT chips offer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferMode {
+    /// One message buffer shared by the bus and the application. Nothing
+    /// can be read while a message is arriving, and an invalid message
+    /// leaves the buffer empty.
+    Single,
+    /// Ping-pong: the bus fills one buffer while the application owns the
+    /// other. They swap only when a complete, valid message has arrived.
+    Double,
+    /// Ring of `BUFFER_SLOTS` messages, consumed in arrival order. When the
+    /// ring is full, a new valid message replaces the oldest unread one.
+    Circular,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferError {
+    /// More than 32 data words in one message.
+    TooManyWords,
+    /// Not a data subaddress (1 to 30).
+    BadSubaddress,
+    /// A circular transmit buffer has no free slot left.
+    Full,
+}
+
+/// Message storage for one direction (receive or transmit) of one data
+/// subaddress.
+///
+/// Receive buffers are filled by the bus with `begin_receive`,
+/// `push_received` and `finish_receive` and drained by the application with
+/// `read`. Transmit buffers are filled by the application with `write` and
+/// drained by the bus with `transmit`.
+#[derive(Debug, Clone)]
+pub struct SubaddressBuffer {
+    mode: BufferMode,
+    slots: [[DataWord; MAX_DATA_WORDS]; RING],
+    lens: [u8; RING],
+    // Slot of the message the application sees next.
+    head: usize,
+    // Complete messages available to the consumer.
+    pending: usize,
+    // Slot currently being filled by the bus, if any.
+    filling: Option<usize>,
+    overflows: u32,
+}
+
+impl SubaddressBuffer {
+    pub const fn new(mode: BufferMode) -> Self {
+        Self {
+            mode,
+            slots: [[DataWord::from_u16(0); MAX_DATA_WORDS]; RING],
+            lens: [0; RING],
+            head: 0,
+            pending: 0,
+            filling: None,
+            overflows: 0,
+        }
+    }
+
+    pub fn mode(&self) -> BufferMode {
+        self.mode
+    }
+
+    /// Change the buffering mode. Any buffered message is dropped.
+    pub fn set_mode(&mut self, mode: BufferMode) {
+        *self = Self::new(mode);
+    }
+
+    /// Number of complete messages the consumer has not taken yet.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Messages lost because a circular buffer was full.
+    pub fn overflows(&self) -> u32 {
+        self.overflows
+    }
+
+    /// True while the bus is writing a message into this buffer.
+    pub fn is_receiving(&self) -> bool {
+        self.filling.is_some()
+    }
+
+    /// Bus side: a receive command for this subaddress has started.
+    pub fn begin_receive(&mut self) {
+        let slot = match self.mode {
+            BufferMode::Single => {
+                self.pending = 0;
+                0
+            }
+            BufferMode::Double => 1 - self.head,
+            BufferMode::Circular => (self.head + self.pending) % RING,
+        };
+        self.lens[slot] = 0;
+        self.filling = Some(slot);
+    }
+
+    /// Bus side: store the next data word of the message being received.
+    /// Extra words past the 32nd are dropped.
+    pub fn push_received(&mut self, word: DataWord) {
+        if let Some(slot) = self.filling {
+            let len = self.lens[slot] as usize;
+            if len < MAX_DATA_WORDS {
+                self.slots[slot][len] = word;
+                self.lens[slot] += 1;
+            }
+        }
+    }
+
+    /// Bus side: the message is over. Only a `valid` message is handed to
+    /// the application.
+    pub fn finish_receive(&mut self, valid: bool) {
+        let Some(slot) = self.filling.take() else {
+            return;
+        };
+        if !valid {
+            return;
+        }
+        match self.mode {
+            BufferMode::Single | BufferMode::Double => {
+                self.head = slot;
+                self.pending = 1;
+            }
+            BufferMode::Circular => {
+                if self.pending == BUFFER_SLOTS {
+                    self.head = (self.head + 1) % RING;
+                    self.pending -= 1;
+                    self.overflows = self.overflows.wrapping_add(1);
+                }
+                self.pending += 1;
+            }
+        }
+    }
+
+    /// Application side: copy the next complete message into `out` and
+    /// return its length. Single and double buffers keep returning the
+    /// latest message; circular buffers return each message once.
+    pub fn read(&mut self, out: &mut [DataWord]) -> Option<usize> {
+        if self.pending == 0 || (self.mode == BufferMode::Single && self.is_receiving()) {
+            return None;
+        }
+        let len = (self.lens[self.head] as usize).min(out.len());
+        out[..len].copy_from_slice(&self.slots[self.head][..len]);
+        if self.mode == BufferMode::Circular {
+            self.head = (self.head + 1) % RING;
+            self.pending -= 1;
+        }
+        Some(len)
+    }
+
+    /// Application side: queue `data` for the next transmit command.
+    pub fn write(&mut self, data: &[DataWord]) -> Result<(), BufferError> {
+        if data.len() > MAX_DATA_WORDS {
+            return Err(BufferError::TooManyWords);
+        }
+        let slot = match self.mode {
+            BufferMode::Single => 0,
+            BufferMode::Double => 1 - self.head,
+            BufferMode::Circular => {
+                if self.pending == BUFFER_SLOTS {
+                    return Err(BufferError::Full);
+                }
+                (self.head + self.pending) % RING
+            }
+        };
+        self.slots[slot][..data.len()].copy_from_slice(data);
+        self.lens[slot] = data.len() as u8;
+        match self.mode {
+            BufferMode::Single | BufferMode::Double => {
+                self.head = slot;
+                self.pending = 1;
+            }
+            BufferMode::Circular => self.pending += 1,
+        }
+        Ok(())
+    }
+
+    /// Bus side: the message to send for a transmit command. The caller
+    /// sends as many words as the command's word count asks for. A drained
+    /// circular buffer repeats its last message.
+    pub fn transmit(&mut self) -> &[DataWord; MAX_DATA_WORDS] {
+        let slot = self.head;
+        if self.mode == BufferMode::Circular && self.pending > 0 {
+            self.pending -= 1;
+            if self.pending > 0 {
+                self.head = (self.head + 1) % RING;
+            }
+        }
+        &self.slots[slot]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffers::*;
+
+    fn words(values: &[u16]) -> [DataWord; 4] {
+        let mut out = [DataWord::from_u16(0); 4];
+        for (w, v) in out.iter_mut().zip(values) {
+            *w = DataWord::from_u16(*v);
+        }
+        out
+    }
+
+    fn receive(buf: &mut SubaddressBuffer, values: &[u16], valid: bool) {
+        buf.begin_receive();
+        for v in values {
+            buf.push_received(DataWord::from_u16(*v));
+        }
+        buf.finish_receive(valid);
+    }
+
+    #[test]
+    fn single_buffer_hides_partial_messages() {
+        let mut buf = SubaddressBuffer::new(BufferMode::Single);
+        let mut out = [DataWord::from_u16(0); 4];
+        receive(&mut buf, &[1, 2], true);
+        assert_eq!(buf.read(&mut out), Some(2));
+
+        buf.begin_receive();
+        buf.push_received(DataWord::from_u16(9));
+        assert_eq!(buf.read(&mut out), None);
+        buf.finish_receive(false);
+        assert_eq!(buf.read(&mut out), None);
+    }
+
+    #[test]
+    fn double_buffer_keeps_last_good_message() {
+        let mut buf = SubaddressBuffer::new(BufferMode::Double);
+        let mut out = [DataWord::from_u16(0); 4];
+        receive(&mut buf, &[1, 2], true);
+
+        buf.begin_receive();
+        buf.push_received(DataWord::from_u16(9));
+        assert_eq!(buf.read(&mut out), Some(2));
+        assert_eq!(out, words(&[1, 2]));
+        buf.finish_receive(false);
+        assert_eq!(buf.read(&mut out), Some(2));
+        assert_eq!(out, words(&[1, 2]));
+
+        receive(&mut buf, &[3, 4, 5], true);
+        assert_eq!(buf.read(&mut out), Some(3));
+        assert_eq!(out, words(&[3, 4, 5]));
+    }
+
+    #[test]
+    fn circular_buffer_queues_and_overflows() {
+        let mut buf = SubaddressBuffer::new(BufferMode::Circular);
+        let mut out = [DataWord::from_u16(0); 4];
+        for v in 0..BUFFER_SLOTS as u16 {
+            receive(&mut buf, &[v], true);
+        }
+        // A message that turns out invalid costs nothing.
+        receive(&mut buf, &[9], false);
+        assert_eq!(buf.overflows(), 0);
+        receive(&mut buf, &[BUFFER_SLOTS as u16], true);
+        assert_eq!(buf.overflows(), 1);
+        assert_eq!(buf.pending(), BUFFER_SLOTS);
+        for v in 1..(BUFFER_SLOTS as u16 + 1) {
+            assert_eq!(buf.read(&mut out), Some(1));
+            assert_eq!(out[0].value(), v);
+        }
+        assert_eq!(buf.read(&mut out), None);
+    }
+
+    #[test]
+    fn circular_transmit_repeats_last_message() {
+        let mut buf = SubaddressBuffer::new(BufferMode::Circular);
+        buf.write(&words(&[1])).unwrap();
+        buf.write(&words(&[2])).unwrap();
+        assert_eq!(buf.transmit()[0].value(), 1);
+        assert_eq!(buf.transmit()[0].value(), 2);
+        assert_eq!(buf.transmit()[0].value(), 2);
+    }
+}
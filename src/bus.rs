@@ -1,61 +1,381 @@
-use crate::{primitives::BitField, words::*};
+use crate::{
+    message::{Fields, Message, MessageErrors, Part},
+    primitives::BitField,
+    words::*,
+};
+
+/// One of the two redundant buses of a dual-standby 1553 system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusId {
+    A,
+    B,
+}
+
+impl BusId {
+    /// The redundant bus of `self`.
+    pub fn other(self) -> Self {
+        match self {
+            BusId::A => BusId::B,
+            BusId::B => BusId::A,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        match self {
+            BusId::A => 0,
+            BusId::B => 1,
+        }
+    }
+}
+
+/// Why a word could not be read from the bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusError {
+    /// Nothing arrived within the response timeout.
+    NoResponse,
+    /// A word arrived with a parity (or Manchester) error.
+    Parity,
+}
 
 // Should the timing be implemented here?
 pub trait Bus {
     fn write_word(&mut self, value: Word);
     // TODO: this should probably not be blocking
-    fn read_next(&self) -> Word;
+    fn read_next(&mut self) -> Result<Word, BusError>;
+
+    /// Put the following words on bus `id` of the pair. Buses with a
+    /// single path ignore it.
+    fn select_bus(&mut self, _id: BusId) {}
+
+    /// Leave the bus quiet for `ns`. Buses without a clock of their own
+    /// return at once.
+    fn idle(&mut self, _ns: u64) {}
+}
+
+impl<B: Bus + ?Sized> Bus for &mut B {
+    fn write_word(&mut self, value: Word) {
+        (**self).write_word(value)
+    }
+
+    fn read_next(&mut self) -> Result<Word, BusError> {
+        (**self).read_next()
+    }
+
+    fn select_bus(&mut self, id: BusId) {
+        (**self).select_bus(id)
+    }
+
+    fn idle(&mut self, ns: u64) {
+        (**self).idle(ns)
+    }
+}
+
+/// Reasons a BusController refuses to put a message on the bus, or could
+/// not complete it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferError {
+    /// More data words than a single message can carry.
+    TooManyDataWords,
+    /// The selected mode code does not allow a Broadcast address.
+    BroadcastNotAllowed,
+    /// The selected mode code requires a data word but none was provided.
+    MissingDataWord,
+    /// The RT did not answer in time.
+    NoResponse,
+    /// A word of the reply arrived with a parity error.
+    Parity,
+    /// A word of the reply had the wrong sync type.
+    UnexpectedWord(Word),
+    /// The status word came from another RT.
+    WrongAddress(StatusWord),
+}
+
+impl From<BusError> for TransferError {
+    fn from(err: BusError) -> Self {
+        match err {
+            BusError::NoResponse => TransferError::NoResponse,
+            BusError::Parity => TransferError::Parity,
+        }
+    }
+}
+
+/// Word count field for a message of `len` data words (1 to 32).
+fn word_count_field(len: usize) -> Result<BitField<5>, TransferError> {
+    match len {
+        0 => Err(TransferError::MissingDataWord),
+        1..=32 => Ok(((len % 32) as u8).into()),
+        _ => Err(TransferError::TooManyDataWords),
+    }
+}
+
+fn record(errors: &mut MessageErrors, err: TransferError) {
+    match err {
+        TransferError::NoResponse => errors.no_response = true,
+        TransferError::Parity => errors.word_error = true,
+        TransferError::WrongAddress(_) => errors.wrong_address = true,
+        _ => errors.sequence = true,
+    }
+}
+
+/// Most retries a [`RetryPolicy`] can ask for.
+pub const MAX_RETRIES: usize = 3;
+
+/// What makes the BC send a message again.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RetryOn {
+    pub no_response: bool,
+    /// Any other [`MessageErrors`]: bad words, wrong address or count.
+    pub format_error: bool,
+    /// A status word with Message Error set.
+    pub message_error: bool,
+    /// A status word with Busy set.
+    pub busy: bool,
+}
+
+/// How the BC retries a failed message, as BC chips do in hardware. The
+/// default sends every message once.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts after the first, up to [`MAX_RETRIES`].
+    pub retries: u8,
+    /// Retry on the other bus of the pair rather than the same one.
+    pub alternate_bus: bool,
+    pub on: RetryOn,
+    /// Retry broadcast messages too. They are only answered in a
+    /// broadcast RT to RT transfer, by the transmitter.
+    pub broadcasts: bool,
+}
+
+impl RetryPolicy {
+    /// Does `message`, as it went, call for another attempt?
+    pub fn wants_retry(&self, message: &Message) -> bool {
+        if message.command().get_rt_addr() == RTAddr::Broadcast && !self.broadcasts {
+            return false;
+        }
+        let errors = message.errors();
+        let flags = message
+            .status_words()
+            .iter()
+            .fold(StatusFlags::default(), |acc, sw| {
+                let flags = sw.flags();
+                StatusFlags {
+                    message_error: acc.message_error || flags.message_error,
+                    busy: acc.busy || flags.busy,
+                    ..acc
+                }
+            });
+        (self.on.no_response && errors.no_response)
+            || (self.on.format_error && !errors.no_response && errors.any())
+            || (self.on.message_error && flags.message_error)
+            || (self.on.busy && flags.busy)
+    }
+}
+
+/// Every try at one message, from [`BusController::transact_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attempts {
+    messages: [Message; MAX_RETRIES + 1],
+    len: usize,
+}
+
+impl Attempts {
+    /// The attempts in the order they went, the first one on the bus of
+    /// the request.
+    pub fn messages(&self) -> &[Message] {
+        &self.messages[..self.len]
+    }
+
+    /// The last attempt, which stands for the message.
+    pub fn last(&self) -> &Message {
+        &self.messages[self.len - 1]
+    }
+
+    pub fn retries(&self) -> usize {
+        self.len - 1
+    }
+}
+
+/// What the BC does when an RT answers with Busy set, having taken or
+/// given no data.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BusyPolicy {
+    /// Nothing: the caller sees it in the status word.
+    #[default]
+    Report,
+    /// Wait `delay_ns` and ask the RT for its status with Transmit Status
+    /// Word, up to `polls` times, then send the message again once Busy
+    /// has cleared.
+    Repoll { delay_ns: u64, polls: u8 },
+    /// Send the message again `frames` minor frames later, which is up to
+    /// the caller's schedule, at most `times` times over.
+    Reschedule { frames: u32, times: u8 },
+}
+
+/// A message run under a [`BusyPolicy`], from
+/// [`BusController::transact_busy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusyOutcome {
+    /// The message as it first went.
+    pub message: Message,
+    /// Transmit Status Word commands sent to the busy RT.
+    pub polls: u8,
+    /// The message sent again after Busy cleared.
+    pub resent: Option<Message>,
+    /// Minor frames from now to send the message again in.
+    pub reschedule: Option<u32>,
+    /// Still busy, with every poll or reschedule the policy allows used
+    /// up: the message failed.
+    pub gave_up: bool,
+}
+
+impl BusyOutcome {
+    /// The message as it went last.
+    pub fn last(&self) -> &Message {
+        self.resent.as_ref().unwrap_or(&self.message)
+    }
+}
+
+/// How often each RT answered busy, per subaddress of the command. Mode
+/// commands count under subaddress 0 or 31, whichever they used.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BusyStats {
+    counts: [[u32; 32]; 32],
+}
+
+impl BusyStats {
+    /// Count the status words of `message` with Busy set.
+    pub fn record(&mut self, message: &Message) {
+        for (idx, sw) in message.status_words().iter().enumerate() {
+            if sw.flags().busy {
+                let fields = Fields::of(&message.responder(idx));
+                let count = &mut self.counts[fields.addr as usize][fields.subaddress as usize];
+                *count = count.saturating_add(1);
+            }
+        }
+    }
+
+    pub fn count(&self, rt: u8, subaddress: u8) -> u32 {
+        self.counts[rt as usize & 0x1F][subaddress as usize & 0x1F]
+    }
+
+    pub fn total(&self) -> u32 {
+        self.counts
+            .iter()
+            .flatten()
+            .fold(0, |total, count| total.saturating_add(*count))
+    }
+
+    /// `(rt, subaddress, count)` for every pair that was busy at all.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, u8, u32)> + '_ {
+        self.counts.iter().enumerate().flat_map(|(rt, counts)| {
+            counts
+                .iter()
+                .enumerate()
+                .filter(|(_, count)| **count > 0)
+                .map(move |(sa, count)| (rt as u8, sa as u8, *count))
+        })
+    }
 }
 
 pub struct BusController<'a> {
     bus: &'a mut dyn Bus,
+    retry: RetryPolicy,
+    busy: BusyPolicy,
+    busy_stats: BusyStats,
 }
 
-impl BusController<'_> {
-    pub fn send_broadcast_transfer(&mut self, data: &[DataWord]) -> Result<(), ()> {
-        if data.len() > 31 {
-            return Err(());
+impl<'a> BusController<'a> {
+    pub fn new(bus: &'a mut dyn Bus) -> Self {
+        Self {
+            bus,
+            retry: RetryPolicy::default(),
+            busy: BusyPolicy::default(),
+            busy_stats: BusyStats::default(),
         }
+    }
+
+    pub fn busy_policy(&self) -> BusyPolicy {
+        self.busy
+    }
+
+    /// The policy of [`BusController::transact_busy`].
+    pub fn set_busy_policy(&mut self, policy: BusyPolicy) {
+        self.busy = policy;
+    }
+
+    /// Busy answers to the messages of [`BusController::transact`] so far.
+    pub fn busy_stats(&self) -> &BusyStats {
+        &self.busy_stats
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry
+    }
+
+    /// The policy of [`BusController::transact_retrying`], for messages
+    /// without one of their own.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry = policy;
+    }
+
+    pub fn send_broadcast_transfer(&mut self, data: &[DataWord]) -> Result<(), TransferError> {
+        self.send_transfer(RTAddr::Broadcast, 1.into(), data)
+            .map(|_| ())
+    }
+
+    /// Send `data` to an RT subaddress. Returns the RT's status word, or
+    /// `None` for a broadcast, which is not answered.
+    pub fn send_transfer(
+        &mut self,
+        addr: RTAddr,
+        subaddr: BitField<5>,
+        data: &[DataWord],
+    ) -> Result<Option<StatusWord>, TransferError> {
         let rcv_cmd = CommandWord::new_data_transfer(
-            RTAddr::Broadcast,
+            addr,
             RTAction::Receive,
-            1.into(),
-            (data.len() as u8).into(),
+            subaddr,
+            word_count_field(data.len())?,
         );
 
         self.bus.write_word(Word::Command(rcv_cmd));
         for w in data {
-            self.bus.write_word(Word::Data((*w).clone()));
+            self.bus.write_word(Word::Data(*w));
         }
-        Ok(())
+        if rcv_cmd.get_rt_addr() == RTAddr::Broadcast {
+            return Ok(None);
+        }
+        self.read_status(addr).map(Some)
     }
 
-    pub fn send_transfer(
+    /// Ask an RT subaddress for `out.len()` data words. When the status word
+    /// reports a message error or busy the RT sends no data and `out` is
+    /// left untouched.
+    pub fn receive_transfer(
         &mut self,
         addr: RTAddr,
         subaddr: BitField<5>,
-        data: &[DataWord],
-    ) -> Result<(), ()> {
-        // Broadcast transfer alias
-        if addr == RTAddr::Single(BROADCAST_ADDR.into()) || addr == RTAddr::Broadcast {
-            self.send_broadcast_transfer(data)?
-        }
-
-        if data.len() > 31 {
-            return Err(());
+        out: &mut [DataWord],
+    ) -> Result<StatusWord, TransferError> {
+        if addr == RTAddr::Broadcast {
+            return Err(TransferError::BroadcastNotAllowed);
         }
-        let rcv_cmd = CommandWord::new_data_transfer(
+        let tx_cmd = CommandWord::new_data_transfer(
             addr,
-            RTAction::Receive,
+            RTAction::Transmit,
             subaddr,
-            (data.len() as u8).into(),
+            word_count_field(out.len())?,
         );
 
-        self.bus.write_word(Word::Command(rcv_cmd));
-        for w in data {
-            self.bus.write_word(Word::Data((*w).clone()));
+        self.bus.write_word(Word::Command(tx_cmd));
+        let sw = self.read_status(addr)?;
+        if sw.flags().message_error || sw.flags().busy {
+            return Ok(sw);
         }
-        Ok(())
+        for w in out.iter_mut() {
+            *w = self.read_data()?;
+        }
+        Ok(sw)
     }
 
     pub fn send_mode_command(
@@ -63,18 +383,17 @@ impl BusController<'_> {
         addr: RTAddr,
         code: ModeCode,
         /*IN/OUT*/ data: Option<&mut DataWord>,
-    ) -> Result<Option<StatusWord>, ()> {
+    ) -> Result<Option<StatusWord>, TransferError> {
         let mode_command = CommandWord::new_mode_command(addr, code);
         let options = code.associated_options();
         if !options.broadcast_allowed && addr == RTAddr::Broadcast {
-            // The selected mode code does not allow a Broadcast address.
-            return Err(());
+            return Err(TransferError::BroadcastNotAllowed);
         }
 
-        if let (true, true, RTAction::Receive) = (data.is_none(), options.requires_data_word, options.tr) {
-            // The selected mode requires the Bus Controller to transmit a data word
-            // but none was provided.
-            return Err(());
+        if let (true, true, RTAction::Receive) =
+            (data.is_none(), options.requires_data_word, options.tr)
+        {
+            return Err(TransferError::MissingDataWord);
         }
 
         self.bus.write_word(Word::Command(mode_command));
@@ -82,27 +401,162 @@ impl BusController<'_> {
         if options.requires_data_word {
             match options.tr {
                 RTAction::Transmit => {
-                    let sw = match self.bus.read_next() {
-                        Word::Command(_) => todo!(), // should not
-                        Word::Data(_) => todo!(),    // should not
-                        Word::Status(sw) => sw,
-                    };
-                    let dw = match self.bus.read_next() {
-                        Word::Command(_) => todo!(), // should not
-                        Word::Data(dw) => dw,
-                        Word::Status(_) => todo!(), // should not
-                    };
-                    data.unwrap().set_value(dw.value()); // set DataWord value
+                    let sw = self.read_status(addr)?;
+                    if sw.flags().message_error || sw.flags().busy {
+                        return Ok(Some(sw));
+                    }
+                    let dw = self.read_data()?;
+                    if let Some(data) = data {
+                        data.set_value(dw.value()); // set DataWord value
+                    }
                     return Ok(Some(sw));
                 }
-                RTAction::Receive => self.bus.write_word(Word::Data(data.unwrap().clone())),
+                RTAction::Receive => self.bus.write_word(Word::Data(*data.unwrap())),
             }
         }
-        let sw = match self.bus.read_next() {
-            Word::Command(_) => todo!(), // should not
-            Word::Data(_) => todo!(),    // should not
-            Word::Status(sw) => sw,
+        if addr == RTAddr::Broadcast {
+            return Ok(None);
+        }
+        self.read_status(addr).map(Some)
+    }
+
+    /// [`BusController::transact_with`] the controller's retry policy.
+    pub fn transact_retrying(&mut self, request: &Message) -> Attempts {
+        let policy = self.retry;
+        self.transact_with(request, &policy)
+    }
+
+    /// Run `request` as [`BusController::transact`] does, then again as
+    /// long as `policy` asks for it.
+    pub fn transact_with(&mut self, request: &Message, policy: &RetryPolicy) -> Attempts {
+        let first = self.transact(request);
+        let mut attempts = Attempts {
+            messages: [first; MAX_RETRIES + 1],
+            len: 1,
         };
-        Ok(Some(sw))
+        let retries = (policy.retries as usize).min(MAX_RETRIES);
+        let mut retry = *request;
+        if policy.alternate_bus {
+            retry.set_bus(request.bus().other());
+        }
+        while attempts.retries() < retries && policy.wants_retry(attempts.last()) {
+            attempts.messages[attempts.len] = self.transact(&retry);
+            attempts.len += 1;
+        }
+        attempts
+    }
+
+    /// Run `request` as [`BusController::transact`] does, then deal with
+    /// a busy RT as the controller's [`BusyPolicy`] says. `rescheduled` is
+    /// how many times the message was put off already.
+    pub fn transact_busy(&mut self, request: &Message, rescheduled: u8) -> BusyOutcome {
+        let mut outcome = BusyOutcome {
+            message: self.transact(request),
+            polls: 0,
+            resent: None,
+            reschedule: None,
+            gave_up: false,
+        };
+        let status = outcome.message.status_words();
+        let Some(idx) = status.iter().position(|sw| sw.flags().busy) else {
+            return outcome;
+        };
+        match self.busy {
+            BusyPolicy::Report => {}
+            BusyPolicy::Reschedule { times, .. } if rescheduled >= times => outcome.gave_up = true,
+            BusyPolicy::Reschedule { frames, .. } => outcome.reschedule = Some(frames),
+            BusyPolicy::Repoll { delay_ns, polls } => {
+                let addr = outcome.message.responder(idx).get_rt_addr();
+                while outcome.polls < polls {
+                    self.bus.idle(delay_ns);
+                    outcome.polls += 1;
+                    match self.send_mode_command(addr, ModeCode::TransmitStatusWord, None) {
+                        Ok(Some(sw)) if sw.flags().busy => {}
+                        Ok(Some(_)) => {
+                            outcome.resent = Some(self.transact(request));
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+                let status = outcome.last().status_words();
+                outcome.gave_up =
+                    status.iter().any(|sw| sw.flags().busy) || outcome.resent.is_none();
+            }
+        }
+        outcome
+    }
+
+    /// Run `request` on the bus: its commands, and its data if the BC
+    /// sends it, then collect the replies. Returns the message as it went,
+    /// with [`MessageErrors`] for anything that went wrong.
+    pub fn transact(&mut self, request: &Message) -> Message {
+        let message = self.run(request);
+        self.busy_stats.record(&message);
+        message
+    }
+
+    fn run(&mut self, request: &Message) -> Message {
+        let format = request.format();
+        let mut message = request.request();
+        self.bus.select_bus(request.bus());
+        for part in format.parts() {
+            match *part {
+                Part::Command(idx) => self.bus.write_word(Word::Command(request.commands()[idx])),
+                Part::Data if !format.rt_sends_data() => {
+                    for dw in request.data() {
+                        self.bus.write_word(Word::Data(*dw));
+                    }
+                }
+                Part::Data => {
+                    if message.transmitter_declined() {
+                        break;
+                    }
+                    for _ in 0..message.expected_data_words() {
+                        match self.read_data() {
+                            Ok(dw) => {
+                                let _ = message.push_data(dw);
+                            }
+                            Err(err) => {
+                                record(message.errors_mut(), err);
+                                return message;
+                            }
+                        }
+                    }
+                }
+                Part::Status(idx) => {
+                    let addr = message.responder(idx).get_rt_addr();
+                    match self.read_status(addr) {
+                        Ok(sw) => {
+                            let _ = message.push_status(sw);
+                        }
+                        Err(err) => {
+                            if let TransferError::WrongAddress(sw) = err {
+                                let _ = message.push_status(sw);
+                            }
+                            record(message.errors_mut(), err);
+                            return message;
+                        }
+                    }
+                }
+            }
+        }
+        message
+    }
+
+    /// Read the status word answering a command sent to `addr`.
+    fn read_status(&mut self, addr: RTAddr) -> Result<StatusWord, TransferError> {
+        match self.bus.read_next()? {
+            Word::Status(sw) if sw.get_rt_addr() == addr => Ok(sw),
+            Word::Status(sw) => Err(TransferError::WrongAddress(sw)),
+            word => Err(TransferError::UnexpectedWord(word)),
+        }
+    }
+
+    fn read_data(&mut self) -> Result<DataWord, TransferError> {
+        match self.bus.read_next()? {
+            Word::Data(dw) => Ok(dw),
+            word => Err(TransferError::UnexpectedWord(word)),
+        }
     }
 }
@@ -0,0 +1,286 @@
+//! Reading IRIG 106 Chapter 10 recordings.
+//!
+//! [`Ch10Reader`] walks the packets of a recording and turns each message
+//! of the MIL-STD-1553 format 1 packets (data type 0x19) into a
+//! [`Message`]; other packets are skipped. Message times are those of the
+//! intra-packet time stamps, taken as the start of the message: the 10 MHz
+//! relative time counter in ns, or IEEE 1588 time when the secondary
+//! header carries it. Data checksums are not checked; header checksums
+//! are.
+use crate::{bus::BusId, manchester::WORD_NS, message::Message};
+
+pub const SYNC: u16 = 0xEB25;
+pub const HEADER_LEN: usize = 24;
+pub const SECONDARY_HEADER_LEN: usize = 12;
+pub const DATA_TYPE_1553_F1: u8 = 0x19;
+
+/// Packet flags.
+const FLAG_SECONDARY_HEADER: u8 = 0x80;
+const FLAG_SECONDARY_TIME: u8 = 0x40;
+const TIME_FORMAT_MASK: u8 = 0x0C;
+const TIME_FORMAT_1588: u8 = 0x04;
+
+/// Block status word bits.
+const BSW_BUS_B: u16 = 1 << 13;
+const BSW_RT_TO_RT: u16 = 1 << 11;
+const BSW_FORMAT_ERROR: u16 = 1 << 10;
+const BSW_TIMEOUT: u16 = 1 << 9;
+const BSW_WORD_COUNT: u16 = 1 << 5;
+const BSW_SYNC_ERROR: u16 = 1 << 4;
+const BSW_WORD_ERROR: u16 = 1 << 3;
+
+/// Intra-packet header of a 1553 message: time stamp, block status, gap
+/// times and length.
+const MESSAGE_HEADER_LEN: usize = 14;
+
+/// Why a recording could not be read, with the byte offset of the packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ch10Error {
+    BadSync(usize),
+    BadChecksum(usize),
+    /// The file ends inside the packet.
+    Truncated(usize),
+    /// Lengths that do not add up.
+    BadPacket(usize),
+    /// Time stamps in the IRIG 106 Chapter 4 format.
+    UnsupportedTime(usize),
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+/// Low 48 bits of the little-endian word at `at`.
+fn rtc_at(bytes: &[u8], at: usize) -> u64 {
+    let mut rtc = [0; 8];
+    rtc[..6].copy_from_slice(&bytes[at..at + 6]);
+    u64::from_le_bytes(rtc)
+}
+
+/// The messages of a Chapter 10 recording held in memory. Reading stops at
+/// the first error.
+pub struct Ch10Reader<'a> {
+    file: &'a [u8],
+    /// Offset of the next packet.
+    next: usize,
+    /// Offset of the packet being read.
+    packet: usize,
+    /// Messages of that packet not yet read.
+    body: &'a [u8],
+    flags: u8,
+    skipped: u32,
+    failed: bool,
+}
+
+impl<'a> Ch10Reader<'a> {
+    pub fn new(file: &'a [u8]) -> Self {
+        Self {
+            file,
+            next: 0,
+            packet: 0,
+            body: &[],
+            flags: 0,
+            skipped: 0,
+            failed: false,
+        }
+    }
+
+    /// Packets of other data types passed over.
+    pub fn skipped_packets(&self) -> u32 {
+        self.skipped
+    }
+
+    /// Move on to the next 1553 packet. `Ok(false)` at the end of the file.
+    fn next_packet(&mut self) -> Result<bool, Ch10Error> {
+        loop {
+            let at = self.next;
+            let rest = &self.file[at..];
+            if rest.is_empty() {
+                return Ok(false);
+            }
+            if rest.len() < HEADER_LEN {
+                return Err(Ch10Error::Truncated(at));
+            }
+            if u16_at(rest, 0) != SYNC {
+                return Err(Ch10Error::BadSync(at));
+            }
+            let checksum = (0..11).fold(0u16, |sum, idx| sum.wrapping_add(u16_at(rest, idx * 2)));
+            if checksum != u16_at(rest, 22) {
+                return Err(Ch10Error::BadChecksum(at));
+            }
+            let packet_len = u32_at(rest, 4) as usize;
+            let data_len = u32_at(rest, 8) as usize;
+            let flags = rest[14];
+            let data_type = rest[15];
+            let data_start = match flags & FLAG_SECONDARY_HEADER {
+                0 => HEADER_LEN,
+                _ => HEADER_LEN + SECONDARY_HEADER_LEN,
+            };
+            if packet_len < data_start + data_len || !packet_len.is_multiple_of(4) {
+                return Err(Ch10Error::BadPacket(at));
+            }
+            if rest.len() < packet_len {
+                return Err(Ch10Error::Truncated(at));
+            }
+            self.next = at + packet_len;
+            if data_type != DATA_TYPE_1553_F1 {
+                self.skipped += 1;
+                continue;
+            }
+            // Channel specific data word: message count in the low 24 bits,
+            // which the lengths already tell.
+            if data_len < 4 {
+                return Err(Ch10Error::BadPacket(at));
+            }
+            if flags & FLAG_SECONDARY_TIME != 0 && flags & TIME_FORMAT_MASK != TIME_FORMAT_1588 {
+                return Err(Ch10Error::UnsupportedTime(at));
+            }
+            self.packet = at;
+            self.flags = flags;
+            self.body = &rest[data_start + 4..data_start + data_len];
+            return Ok(true);
+        }
+    }
+
+    fn message(&mut self) -> Result<Message, Ch10Error> {
+        let body = self.body;
+        if body.len() < MESSAGE_HEADER_LEN {
+            return Err(Ch10Error::BadPacket(self.packet));
+        }
+        let time_ns = match self.flags & FLAG_SECONDARY_TIME {
+            0 => rtc_at(body, 0) * 100,
+            _ => u32_at(body, 4) as u64 * 1_000_000_000 + u32_at(body, 0) as u64,
+        };
+        let status = u16_at(body, 8);
+        let gaps = u16_at(body, 10);
+        let len = u16_at(body, 12) as usize;
+        let end = MESSAGE_HEADER_LEN + len;
+        if body.len() < end || !len.is_multiple_of(2) || len > 2 * 36 {
+            return Err(Ch10Error::BadPacket(self.packet));
+        }
+        self.body = &body[end..];
+
+        let mut words = [0; 36];
+        let words = &mut words[..len / 2];
+        for (idx, word) in words.iter_mut().enumerate() {
+            *word = u16_at(body, MESSAGE_HEADER_LEN + 2 * idx);
+        }
+        let bus = match status & BSW_BUS_B {
+            0 => BusId::A,
+            _ => BusId::B,
+        };
+        let mut message = Message::from_raw_words(bus, status & BSW_RT_TO_RT != 0, words)
+            .ok_or(Ch10Error::BadPacket(self.packet))?;
+        let errors = message.errors_mut();
+        errors.no_response |= status & BSW_TIMEOUT != 0;
+        errors.word_count |= status & BSW_WORD_COUNT != 0;
+        errors.word_error |= status & (BSW_SYNC_ERROR | BSW_WORD_ERROR) != 0;
+        errors.sequence |= status & BSW_FORMAT_ERROR != 0;
+        // Gap times count in 0.1 µs.
+        let gaps_ns = ((gaps & 0xFF) + (gaps >> 8)) as u64 * 100;
+        let words_ns = words.len() as u64 * WORD_NS as u64;
+        message.set_time(time_ns, time_ns + words_ns + gaps_ns);
+        Ok(message)
+    }
+}
+
+impl Iterator for Ch10Reader<'_> {
+    type Item = Result<Message, Ch10Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        while self.body.is_empty() {
+            match self.next_packet() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        let result = self.message();
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+
+    use crate::{bus::BusId, ch10::*, message::*, words::*};
+
+    fn packet(out: &mut Vec<u8>, data_type: u8, body: &[u8]) {
+        let data_len = body.len();
+        let packet_len = (HEADER_LEN + data_len).next_multiple_of(4);
+        let mut header = [0u8; HEADER_LEN];
+        header[0..2].copy_from_slice(&SYNC.to_le_bytes());
+        header[2..4].copy_from_slice(&3u16.to_le_bytes());
+        header[4..8].copy_from_slice(&(packet_len as u32).to_le_bytes());
+        header[8..12].copy_from_slice(&(data_len as u32).to_le_bytes());
+        header[15] = data_type;
+        let checksum = header[..22].chunks(2).fold(0u16, |sum, pair| {
+            sum.wrapping_add(u16::from_le_bytes([pair[0], pair[1]]))
+        });
+        header[22..24].copy_from_slice(&checksum.to_le_bytes());
+        out.extend_from_slice(&header);
+        out.extend_from_slice(body);
+        out.resize(out.len().next_multiple_of(4), 0);
+    }
+
+    fn message(out: &mut Vec<u8>, rtc: u64, status: u16, gaps: u16, words: &[u16]) {
+        out.extend_from_slice(&rtc.to_le_bytes());
+        out.extend_from_slice(&status.to_le_bytes());
+        out.extend_from_slice(&gaps.to_le_bytes());
+        out.extend_from_slice(&(2 * words.len() as u16).to_le_bytes());
+        for word in words {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn reads_1553_messages() {
+        let mut body = std::vec![2, 0, 0, 0];
+        // BC to RT 5, two words, on bus B, 4 µs response time.
+        message(&mut body, 10_000, 1 << 13, 40, &[0x2822, 1, 2, 0x2800]);
+        // RT 5 to RT 6, one word, RT 6 never answers.
+        message(
+            &mut body,
+            20_000,
+            1 << 11 | 1 << 9,
+            0,
+            &[0x3021, 0x2C21, 0x2800, 7],
+        );
+        let mut file = Vec::new();
+        packet(&mut file, 0x11, &[0; 12]);
+        packet(&mut file, DATA_TYPE_1553_F1, &body);
+
+        let mut reader = Ch10Reader::new(&file);
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(first.bus(), BusId::B);
+        assert_eq!(first.format(), MessageFormat::BcToRt);
+        assert_eq!((first.start_ns(), first.end_ns()), (1_000_000, 1_084_000));
+        assert_eq!(first.validate(), Ok(()));
+
+        let second = reader.next().unwrap().unwrap();
+        assert_eq!(second.format(), MessageFormat::RtToRt);
+        assert_eq!(second.data(), &[DataWord::from_u16(7)]);
+        assert!(second.errors().no_response);
+        assert_eq!(second.validate(), Ok(()));
+        assert!(reader.next().is_none());
+        assert_eq!(reader.skipped_packets(), 1);
+
+        file[22] ^= 1;
+        let mut reader = Ch10Reader::new(&file);
+        assert_eq!(reader.next(), Some(Err(Ch10Error::BadChecksum(0))));
+        assert_eq!(reader.next(), None);
+    }
+}
@@ -0,0 +1,170 @@
+//! Threaded bus over `std` channels.
+//!
+//! A [`ChannelHub`] stands for one bus. Every participant (BC, RT or
+//! monitor) joins it and gets its own [`ChannelBus`] endpoint, which can be
+//! moved to another thread. A word written on one endpoint is delivered to
+//! every other endpoint, like on the wire.
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+use std::vec::Vec;
+
+use crate::{
+    bus::{Bus, BusError},
+    words::Word,
+};
+
+/// How long `read_next` waits before reporting `NoResponse`. Threads are
+/// scheduled far slower than the 14 µs a real RT has to answer.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Default)]
+pub struct ChannelHub {
+    // Indexed by endpoint id, `None` once the endpoint is dropped.
+    members: Arc<Mutex<Vec<Option<mpsc::Sender<Word>>>>>,
+}
+
+impl ChannelHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a participant to the bus. It sees every word written after it
+    /// joined.
+    pub fn join(&self) -> ChannelBus {
+        let (tx, rx) = mpsc::channel();
+        let mut members = self.members.lock().unwrap();
+        members.push(Some(tx));
+        ChannelBus {
+            id: members.len() - 1,
+            hub: self.clone(),
+            rx,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+/// One participant's connection to a [`ChannelHub`].
+pub struct ChannelBus {
+    id: usize,
+    hub: ChannelHub,
+    rx: mpsc::Receiver<Word>,
+    timeout: Duration,
+}
+
+impl ChannelBus {
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Next word if one already arrived, without blocking.
+    pub fn try_read(&mut self) -> Option<Word> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl Bus for ChannelBus {
+    fn write_word(&mut self, value: Word) {
+        let members = self.hub.members.lock().unwrap();
+        for (id, member) in members.iter().enumerate() {
+            if let (true, Some(tx)) = (id != self.id, member) {
+                // A member that went away has nothing to receive.
+                let _ = tx.send(value);
+            }
+        }
+    }
+
+    fn read_next(&mut self) -> Result<Word, BusError> {
+        self.rx
+            .recv_timeout(self.timeout)
+            .map_err(|_| BusError::NoResponse)
+    }
+}
+
+impl Drop for ChannelBus {
+    fn drop(&mut self) {
+        if let Ok(mut members) = self.hub.members.lock() {
+            members[self.id] = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::Instant;
+
+    use crate::{bus::*, channel::*, rt::RemoteTerminal, words::*};
+
+    fn spawn_rt(
+        hub: &ChannelHub,
+        mut rt: RemoteTerminal,
+        stop: Arc<AtomicBool>,
+    ) -> thread::JoinHandle<RemoteTerminal> {
+        let mut bus = hub.join();
+        bus.set_timeout(Duration::from_millis(5));
+        thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                match rt.poll(BusId::A, &mut bus) {
+                    Ok(()) | Err(BusError::NoResponse) => {}
+                    Err(err) => panic!("{:?}", err),
+                }
+            }
+            rt
+        })
+    }
+
+    #[test]
+    fn bc_rts_and_monitor_on_threads() {
+        let hub = ChannelHub::new();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let mut monitor = hub.join();
+        monitor.set_timeout(Duration::from_millis(5));
+        // Both messages make 7 words; wait for them however slow the
+        // machine is, but not forever.
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let monitor = thread::spawn(move || {
+            let mut seen = Vec::new();
+            while seen.len() < 7 && Instant::now() < deadline {
+                if let Ok(word) = monitor.read_next() {
+                    seen.push(word);
+                }
+            }
+            seen
+        });
+
+        let mut sender = RemoteTerminal::new(4.into());
+        sender.write(2.into(), &[DataWord::from_u16(0xAB)]).unwrap();
+        let rts = [
+            spawn_rt(&hub, RemoteTerminal::new(3.into()), stop.clone()),
+            spawn_rt(&hub, sender, stop.clone()),
+        ];
+
+        let mut bus = hub.join();
+        bus.set_timeout(Duration::from_secs(2));
+        let mut bc = BusController::new(&mut bus);
+        let data = [DataWord::from_u16(1), DataWord::from_u16(2)];
+        bc.send_transfer(RTAddr::Single(3.into()), 1.into(), &data)
+            .unwrap();
+        let mut out = [DataWord::from_u16(0)];
+        bc.receive_transfer(RTAddr::Single(4.into()), 2.into(), &mut out)
+            .unwrap();
+        assert_eq!(out[0].value(), 0xAB);
+
+        stop.store(true, Ordering::Relaxed);
+        let [mut first, _] = rts.map(|handle| handle.join().unwrap());
+        let mut received = [DataWord::from_u16(0); 2];
+        assert_eq!(first.read(1.into(), &mut received), Some(2));
+        assert_eq!(received, data);
+
+        let seen = monitor.join().unwrap();
+        assert_eq!(seen.len(), 7);
+        assert!(matches!(seen[3], Word::Status(_)));
+        assert!(matches!(seen[6], Word::Data(_)));
+    }
+}
@@ -0,0 +1,402 @@
+//! Telling command, status and data words apart in raw captures.
+//!
+//! Command and status words share their sync, so a monitor working from
+//! the wire has to use the protocol: a status word only comes back from
+//! the commanded RT, shortly after the command (or its data), while a new
+//! command follows an intermessage gap. [`Classifier`] follows each message
+//! word by word and labels what it sees, and [`Monitor`] groups the words
+//! it labelled into [`Message`]s.
+use crate::{
+    bus::{BusError, BusId},
+    manchester::{DecodedWord, Sync, WORD_NS},
+    message::{Assembler, Fields, Message, MessageFormat, Part},
+    words::{CommandWord, DataWord, StatusWord, Word},
+};
+
+/// Longest gap, from the end of the command (or last data word) to the
+/// status word, taken for an RT response: 12 µs of response time, less the
+/// 2 µs between the middle of the parity bit and the middle of the sync.
+pub const RESPONSE_GAP_MAX_NS: u64 = 10_000;
+/// Gap under which a word is taken as sent back to back with the previous
+/// one, as the two commands of an RT to RT transfer are.
+pub const CONTIGUOUS_GAP_MAX_NS: u64 = 1_000;
+/// Shortest gap between messages.
+pub const INTERMESSAGE_GAP_MIN_NS: u64 = 4_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    /// Both the sync and what came before fit the same protocol
+    /// reading.
+    Certain,
+    /// Only the timing or the addresses settle it.
+    Likely,
+    /// Nothing fits; the sync alone decided.
+    Guess,
+}
+
+/// Why a word does not fit where it came.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceError {
+    /// A data word where none was due.
+    UnexpectedData,
+    /// A command/status sync while data words were due.
+    MissingData,
+    /// No status word came back for the previous message.
+    NoResponse,
+    /// The status word carries another address than the commanded RT's.
+    WrongAddress,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Classified {
+    pub word: Word,
+    pub confidence: Confidence,
+    pub error: Option<SequenceError>,
+}
+
+/// What a message still has to carry, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Data(u8),
+    Status(u8),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Plan {
+    steps: [Step; 3],
+    len: usize,
+    /// The receive command, while its transmit command may still follow.
+    rt_to_rt: Option<CommandWord>,
+}
+
+impl Plan {
+    const IDLE: Plan = Plan {
+        steps: [Step::Data(0); 3],
+        len: 0,
+        rt_to_rt: None,
+    };
+
+    /// The step due now; steps are stored last first.
+    fn current(&mut self) -> Option<&mut Step> {
+        match self.len {
+            0 => None,
+            len => Some(&mut self.steps[len - 1]),
+        }
+    }
+}
+
+/// Steps of a message of `format` after its commands.
+fn plan(format: MessageFormat, commands: &[CommandWord]) -> Plan {
+    let words = Fields::of(&commands[commands.len() - 1]).data_words();
+    let mut plan = Plan::IDLE;
+    for part in format.parts().iter().rev() {
+        plan.steps[plan.len] = match *part {
+            Part::Command(_) => continue,
+            Part::Data => Step::Data(words),
+            Part::Status(idx) => Step::Status(Fields::of(&commands[format.responder(idx)]).addr),
+        };
+        plan.len += 1;
+    }
+    plan
+}
+
+/// Streaming classifier for the words of one bus.
+#[derive(Debug, Clone, Copy)]
+pub struct Classifier {
+    plan: Plan,
+    started: bool,
+}
+
+impl Classifier {
+    pub const fn new() -> Self {
+        Self {
+            plan: Plan::IDLE,
+            started: false,
+        }
+    }
+
+    /// Label the next word, given its sync, its bits and the time between
+    /// the end of the previous word and its start.
+    pub fn push(&mut self, sync: Sync, value: u16, gap_ns: u64) -> Classified {
+        let first = !self.started;
+        self.started = true;
+        match sync {
+            Sync::Data => self.data(value),
+            Sync::CommandStatus => self.command_or_status(value, gap_ns, first),
+        }
+    }
+
+    fn data(&mut self, value: u16) -> Classified {
+        let word = Word::Data(DataWord::from_u16(value));
+        self.plan.rt_to_rt = None;
+        let error = match self.plan.current() {
+            Some(Step::Data(count)) => {
+                *count -= 1;
+                if *count == 0 {
+                    self.plan.len -= 1;
+                }
+                None
+            }
+            _ => Some(SequenceError::UnexpectedData),
+        };
+        Classified {
+            word,
+            confidence: Confidence::Certain,
+            error,
+        }
+    }
+
+    fn command_or_status(&mut self, value: u16, gap_ns: u64, first: bool) -> Classified {
+        if let Some(receive) = self.plan.rt_to_rt.take() {
+            if gap_ns <= CONTIGUOUS_GAP_MAX_NS {
+                return self.second_command(receive, value);
+            }
+        }
+        match self.plan.current().copied() {
+            Some(Step::Status(addr)) if gap_ns <= RESPONSE_GAP_MAX_NS => {
+                self.plan.len -= 1;
+                let matches = (value >> 11) as u8 == addr;
+                Classified {
+                    word: Word::Status(StatusWord::from_u16(value)),
+                    confidence: if matches {
+                        Confidence::Certain
+                    } else {
+                        Confidence::Likely
+                    },
+                    error: (!matches).then_some(SequenceError::WrongAddress),
+                }
+            }
+            Some(Step::Status(_)) => self.command(value, Some(SequenceError::NoResponse)),
+            Some(Step::Data(_)) => self.command(value, Some(SequenceError::MissingData)),
+            None => {
+                let mut classified = self.command(value, None);
+                if !first && gap_ns < INTERMESSAGE_GAP_MIN_NS {
+                    classified.confidence = Confidence::Likely;
+                }
+                classified
+            }
+        }
+    }
+
+    /// A new message starting with the command `value`.
+    fn command(&mut self, value: u16, error: Option<SequenceError>) -> Classified {
+        let cmd = CommandWord::from_u16(value);
+        let format = MessageFormat::of(&cmd);
+        self.plan = plan(format, &[cmd]);
+        if format.with_transmit_command().is_some() {
+            self.plan.rt_to_rt = Some(cmd);
+        }
+        Classified {
+            word: Word::Command(cmd),
+            confidence: match error {
+                None => Confidence::Certain,
+                Some(_) => Confidence::Likely,
+            },
+            error,
+        }
+    }
+
+    /// The transmit command right after the receive command of an RT to RT
+    /// transfer.
+    fn second_command(&mut self, receive: CommandWord, value: u16) -> Classified {
+        let cmd = CommandWord::from_u16(value);
+        let rx = Fields::of(&receive);
+        let tx = Fields::of(&cmd);
+        if !tx.transmit || tx.mode || tx.broadcast() {
+            // Nothing else can come back to back, but this isn't a transfer
+            // either.
+            self.plan = plan(MessageFormat::of(&cmd), &[cmd]);
+            return Classified {
+                word: Word::Command(cmd),
+                confidence: Confidence::Guess,
+                error: Some(SequenceError::MissingData),
+            };
+        }
+        let format = match rx.broadcast() {
+            true => MessageFormat::BroadcastRtToRt,
+            false => MessageFormat::RtToRt,
+        };
+        self.plan = plan(format, &[receive, cmd]);
+        Classified {
+            word: Word::Command(cmd),
+            confidence: if tx.count == rx.count {
+                Confidence::Certain
+            } else {
+                Confidence::Likely
+            },
+            error: None,
+        }
+    }
+}
+
+impl Default for Classifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Messages from the words decoded off one bus: a [`Classifier`] feeding
+/// an [`Assembler`].
+#[derive(Debug, Clone, Copy)]
+pub struct Monitor {
+    classifier: Classifier,
+    assembler: Assembler,
+    /// End of the last word, `None` before the first.
+    end_ns: Option<u64>,
+}
+
+impl Monitor {
+    pub const fn new(bus: BusId) -> Self {
+        Self {
+            classifier: Classifier::new(),
+            assembler: Assembler::new(bus),
+            end_ns: None,
+        }
+    }
+
+    pub fn assembler(&self) -> &Assembler {
+        &self.assembler
+    }
+
+    /// Take the next word, in time order. Returns the message it completes
+    /// or ends, if any. A word decoded with an error still counts in the
+    /// message, as a word error.
+    pub fn push(&mut self, word: DecodedWord) -> Option<Message> {
+        let gap_ns = self
+            .end_ns
+            .map_or(u64::MAX, |end_ns| word.time_ns.saturating_sub(end_ns));
+        self.end_ns = Some(word.time_ns.saturating_add(WORD_NS as u64));
+        let classified = self
+            .classifier
+            .push(word.word.sync, word.word.value, gap_ns);
+        let word_or_error = match word.word.error {
+            None => Ok(classified.word),
+            Some(_) => Err(BusError::Parity),
+        };
+        self.assembler.push(word.time_ns, word_or_error)
+    }
+
+    /// End the message in progress, e.g. at the end of a capture.
+    pub fn finish(&mut self) -> Option<Message> {
+        self.assembler.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{classify::*, manchester::Sync, words::*};
+
+    fn push_all(words: &[(Sync, u16, u64)]) -> [Option<Classified>; 16] {
+        let mut classifier = Classifier::new();
+        let mut out = [None; 16];
+        for (slot, (sync, value, gap)) in out.iter_mut().zip(words) {
+            *slot = Some(classifier.push(*sync, *value, *gap));
+        }
+        out
+    }
+
+    fn kinds(out: &[Option<Classified>]) -> [u8; 16] {
+        let mut kinds = [0; 16];
+        for (kind, classified) in kinds.iter_mut().zip(out) {
+            *kind = match classified.map(|c| c.word) {
+                Some(Word::Command(_)) => b'C',
+                Some(Word::Status(_)) => b'S',
+                Some(Word::Data(_)) => b'D',
+                None => b'.',
+            }
+        }
+        kinds
+    }
+
+    const CS: Sync = Sync::CommandStatus;
+    const D: Sync = Sync::Data;
+
+    #[test]
+    fn labels_the_message_formats() {
+        let out = push_all(&[
+            // BC to RT 5, two words.
+            (CS, 0x2822, 0),
+            (D, 1, 0),
+            (D, 2, 0),
+            (CS, 0x2800, 4_000),
+            // RT 5 to BC, one word.
+            (CS, 0x2C21, 30_000),
+            (CS, 0x2800, 6_000),
+            (D, 3, 0),
+            // RT 5 to RT 6, one word.
+            (CS, 0x2821, 30_000),
+            (CS, 0x3421, 0),
+            (CS, 0x3000, 5_000),
+            (D, 4, 0),
+            (CS, 0x2800, 5_000),
+            // Transmit vector word, mode code 16.
+            (CS, 0x2C10, 30_000),
+            (CS, 0x2800, 5_000),
+            (D, 5, 0),
+        ]);
+        assert_eq!(&kinds(&out), b"CDDSCSDCCSDSCSD.");
+        assert!(out[..15].iter().all(|c| {
+            let c = c.unwrap();
+            c.confidence == Confidence::Certain && c.error.is_none()
+        }));
+    }
+
+    #[test]
+    fn broadcast_and_inconsistent_sequences() {
+        let out = push_all(&[
+            // Broadcast receive: nothing comes back.
+            (CS, 0xF821, 0),
+            (D, 1, 0),
+            // Next command, even after a short gap.
+            (CS, 0x2C21, 2_000),
+            // RT 5 never answers.
+            (CS, 0x3421, 20_000),
+            // RT 6 answers with RT 7's address.
+            (CS, 0x3800, 5_000),
+            (D, 2, 0),
+            (D, 3, 0),
+        ]);
+        assert_eq!(&kinds(&out), b"CDCCSDD.........");
+        let at = |idx: usize| out[idx].unwrap();
+        assert_eq!(at(2).confidence, Confidence::Likely);
+        assert_eq!(at(3).error, Some(SequenceError::NoResponse));
+        assert_eq!(at(4).error, Some(SequenceError::WrongAddress));
+        assert_eq!(at(4).confidence, Confidence::Likely);
+        assert_eq!(at(6).error, Some(SequenceError::UnexpectedData));
+    }
+
+    #[test]
+    fn monitor_groups_messages() {
+        use crate::manchester::{RawWord, WordError};
+
+        let mut monitor = Monitor::new(BusId::B);
+        let mut time_ns = 0;
+        let mut push = |sync: Sync, value: u16, gap_ns: u64, error: Option<WordError>| {
+            time_ns += gap_ns;
+            let word = RawWord { sync, value, error };
+            let decoded = DecodedWord { time_ns, word };
+            time_ns += WORD_NS as u64;
+            monitor.push(decoded)
+        };
+        // BC to RT 5, then RT 5 to BC with a garbled data word.
+        assert_eq!(push(CS, 0x2822, 0, None), None);
+        assert_eq!(push(D, 1, 0, None), None);
+        assert_eq!(push(D, 2, 0, None), None);
+        let sent = push(CS, 0x2800, 4_000, None).unwrap();
+        assert_eq!(sent.format(), MessageFormat::BcToRt);
+        assert_eq!(sent.bus(), BusId::B);
+        assert_eq!(sent.data().len(), 2);
+        assert_eq!(sent.start_ns(), 0);
+        assert_eq!(sent.validate(), Ok(()));
+
+        assert_eq!(push(CS, 0x2C22, 30_000, None), None);
+        assert_eq!(push(CS, 0x2800, 6_000, None), None);
+        assert_eq!(push(D, 3, 0, Some(WordError::Parity)), None);
+        assert_eq!(push(D, 4, 0, None), None);
+        let received = monitor.finish().unwrap();
+        assert_eq!(received.format(), MessageFormat::RtToBc);
+        assert!(received.errors().word_error);
+        assert_eq!(received.validate(), Ok(()));
+        assert_eq!(monitor.assembler().stray_words(), 0);
+    }
+}
@@ -0,0 +1,257 @@
+//! 1553-over-UDP datagram format.
+//!
+//! A datagram carries one or more words seen on one bus, e.g. a single word
+//! or a whole message, with their timing and any receive error. All fields
+//! are big endian.
+//!
+//! | Bytes  | Field                                              |
+//! |--------|----------------------------------------------------|
+//! | 0..4   | magic, `"1553"`                                    |
+//! | 4      | format version, [`VERSION`]                        |
+//! | 5      | bus: 0 for A, 1 for B                              |
+//! | 6..8   | sequence number, wrapping, per sender              |
+//! | 8..16  | time of the datagram, in ns, sender's clock        |
+//! | 16..   | [`RECORD_LEN`]-byte records, one per word          |
+//!
+//! Each record is:
+//!
+//! | Bytes | Field                                                    |
+//! |-------|----------------------------------------------------------|
+//! | 0     | word type: `C`, `S` or `D`, or 0 when it is unknown      |
+//! | 1     | error flags: [`FLAG_PARITY`], [`FLAG_NO_RESPONSE`]       |
+//! | 2..4  | the word, meaningless when an error flag is set          |
+//! | 4..8  | time of the word, in ns after the datagram time          |
+use crate::{
+    bus::{BusError, BusId},
+    frame,
+    words::Word,
+};
+
+pub const MAGIC: [u8; 4] = *b"1553";
+pub const VERSION: u8 = 1;
+pub const HEADER_LEN: usize = 16;
+pub const RECORD_LEN: usize = 8;
+/// Most records in one datagram: a whole RT to RT transfer is 36 words.
+pub const MAX_RECORDS: usize = 64;
+pub const MAX_DATAGRAM_LEN: usize = HEADER_LEN + MAX_RECORDS * RECORD_LEN;
+
+/// The word was received with a parity (or Manchester) error.
+pub const FLAG_PARITY: u8 = 0x01;
+/// Nothing arrived where a word was expected.
+pub const FLAG_NO_RESPONSE: u8 = 0x02;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatagramError {
+    /// Shorter than a header, or not a whole number of records.
+    BadLength,
+    BadMagic,
+    UnsupportedVersion(u8),
+    BadBus(u8),
+    /// The record at this index has an unknown word type.
+    BadRecord(usize),
+    TooManyRecords,
+    /// The output buffer can't hold the datagram.
+    BufferTooSmall,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub bus: BusId,
+    pub sequence: u16,
+    pub time_ns: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record {
+    /// Time of the word after the datagram time.
+    pub offset_ns: u32,
+    pub word: Result<Word, BusError>,
+}
+
+impl Record {
+    pub fn new(offset_ns: u32, word: Result<Word, BusError>) -> Self {
+        Self { offset_ns, word }
+    }
+
+    fn encode(&self) -> [u8; RECORD_LEN] {
+        let [kind, hi, lo] = match self.word {
+            Ok(word) => frame::encode(word),
+            Err(_) => [0; frame::FRAME_LEN],
+        };
+        let flags = match self.word {
+            Ok(_) => 0,
+            Err(BusError::Parity) => FLAG_PARITY,
+            Err(BusError::NoResponse) => FLAG_NO_RESPONSE,
+        };
+        let [o0, o1, o2, o3] = self.offset_ns.to_be_bytes();
+        [kind, flags, hi, lo, o0, o1, o2, o3]
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let offset_ns = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        let word = if bytes[1] & FLAG_PARITY != 0 {
+            Err(BusError::Parity)
+        } else if bytes[1] & FLAG_NO_RESPONSE != 0 {
+            Err(BusError::NoResponse)
+        } else {
+            Ok(frame::decode([bytes[0], bytes[2], bytes[3]])?)
+        };
+        Some(Self { offset_ns, word })
+    }
+}
+
+/// Write a datagram into `out` and return its length.
+pub fn encode(header: &Header, records: &[Record], out: &mut [u8]) -> Result<usize, DatagramError> {
+    if records.len() > MAX_RECORDS {
+        return Err(DatagramError::TooManyRecords);
+    }
+    let len = HEADER_LEN + records.len() * RECORD_LEN;
+    if out.len() < len {
+        return Err(DatagramError::BufferTooSmall);
+    }
+    out[..4].copy_from_slice(&MAGIC);
+    out[4] = VERSION;
+    out[5] = header.bus.index() as u8;
+    out[6..8].copy_from_slice(&header.sequence.to_be_bytes());
+    out[8..16].copy_from_slice(&header.time_ns.to_be_bytes());
+    for (record, chunk) in records
+        .iter()
+        .zip(out[HEADER_LEN..len].chunks_exact_mut(RECORD_LEN))
+    {
+        chunk.copy_from_slice(&record.encode());
+    }
+    Ok(len)
+}
+
+/// Check a whole datagram and return its header and records.
+pub fn decode(datagram: &[u8]) -> Result<(Header, Records<'_>), DatagramError> {
+    if datagram.len() < HEADER_LEN || !(datagram.len() - HEADER_LEN).is_multiple_of(RECORD_LEN) {
+        return Err(DatagramError::BadLength);
+    }
+    if datagram[..4] != MAGIC {
+        return Err(DatagramError::BadMagic);
+    }
+    if datagram[4] != VERSION {
+        return Err(DatagramError::UnsupportedVersion(datagram[4]));
+    }
+    let bus = match datagram[5] {
+        0 => BusId::A,
+        1 => BusId::B,
+        other => return Err(DatagramError::BadBus(other)),
+    };
+    let records = Records {
+        bytes: &datagram[HEADER_LEN..],
+    };
+    if records.len() > MAX_RECORDS {
+        return Err(DatagramError::TooManyRecords);
+    }
+    if let Some(idx) = records
+        .bytes
+        .chunks_exact(RECORD_LEN)
+        .position(|bytes| Record::decode(bytes).is_none())
+    {
+        return Err(DatagramError::BadRecord(idx));
+    }
+    let header = Header {
+        bus,
+        sequence: u16::from_be_bytes([datagram[6], datagram[7]]),
+        time_ns: u64::from_be_bytes(datagram[8..16].try_into().unwrap()),
+    };
+    Ok((header, records))
+}
+
+/// The records of a datagram checked by [`decode`].
+#[derive(Debug, Clone)]
+pub struct Records<'a> {
+    bytes: &'a [u8],
+}
+
+impl Records<'_> {
+    pub fn len(&self) -> usize {
+        self.bytes.len() / RECORD_LEN
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+impl Iterator for Records<'_> {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Record> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+        let (record, rest) = self.bytes.split_at(RECORD_LEN);
+        self.bytes = rest;
+        Record::decode(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{datagram::*, words::*};
+
+    #[test]
+    fn datagram_roundtrip() {
+        let header = Header {
+            bus: BusId::B,
+            sequence: 0xFFFF,
+            time_ns: 123_456_789,
+        };
+        let records = [
+            Record::new(0, Ok(Word::Command(CommandWord::from_u16(0x2043)))),
+            Record::new(20_000, Ok(Word::Data(DataWord::from_u16(0xBEEF)))),
+            Record::new(40_000, Err(BusError::Parity)),
+            Record::new(54_000, Err(BusError::NoResponse)),
+        ];
+        let mut buf = [0; MAX_DATAGRAM_LEN];
+        let len = encode(&header, &records, &mut buf).unwrap();
+        assert_eq!(len, HEADER_LEN + 4 * RECORD_LEN);
+        assert_eq!(&buf[..6], b"1553\x01\x01");
+        assert_eq!(&buf[24..28], &[b'D', 0, 0xBE, 0xEF]);
+
+        let (decoded, mut rest) = decode(&buf[..len]).unwrap();
+        assert_eq!(decoded, header);
+        assert_eq!(rest.len(), 4);
+        for record in records {
+            assert_eq!(rest.next(), Some(record));
+        }
+        assert_eq!(rest.next(), None);
+    }
+
+    #[test]
+    fn bad_datagrams_are_rejected() {
+        let header = Header {
+            bus: BusId::A,
+            sequence: 0,
+            time_ns: 0,
+        };
+        let record = Record::new(0, Ok(Word::Data(DataWord::from_u16(1))));
+        let mut buf = [0; MAX_DATAGRAM_LEN];
+        let len = encode(&header, &[record], &mut buf).unwrap();
+
+        assert_eq!(
+            decode(&buf[..len - 1]).err(),
+            Some(DatagramError::BadLength)
+        );
+        let mut bad = buf;
+        bad[0] = b'X';
+        assert_eq!(decode(&bad[..len]).err(), Some(DatagramError::BadMagic));
+        let mut bad = buf;
+        bad[5] = 2;
+        assert_eq!(decode(&bad[..len]).err(), Some(DatagramError::BadBus(2)));
+        let mut bad = buf;
+        bad[HEADER_LEN] = b'?';
+        assert_eq!(decode(&bad[..len]).err(), Some(DatagramError::BadRecord(0)));
+        assert_eq!(
+            encode(&header, &[record; MAX_RECORDS + 1], &mut buf),
+            Err(DatagramError::TooManyRecords)
+        );
+        assert_eq!(
+            encode(&header, &[record], &mut buf[..HEADER_LEN]),
+            Err(DatagramError::BufferTooSmall)
+        );
+    }
+}
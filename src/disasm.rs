@@ -0,0 +1,404 @@
+//! Human-readable shorthand for bus traffic.
+//!
+//! Command words print as `RT05 R SA03 WC04` (or `RT05 T MC02 TSW` for mode
+//! commands), status words as the address followed by the mnemonics of the
+//! flags that are set (`RT05 ME BUSY TF`) and data words as four hex digits.
+//! Every format can be read back with `FromStr`.
+use core::fmt;
+use core::str::FromStr;
+
+use crate::primitives::*;
+use crate::words::*;
+
+/// Status flag mnemonics, MSB first, with the bit each one lives in.
+const STATUS_FLAGS: [(&str, u16); 8] = [
+    ("ME", 10),
+    ("INS", 9),
+    ("SR", 8),
+    ("BCR", 4),
+    ("BUSY", 3),
+    ("SSF", 2),
+    ("DBCA", 1),
+    ("TF", 0),
+];
+const STATUS_RESERVED_MASK: u16 = 0b111 << 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseWordError {
+    /// The input had no tokens.
+    Empty,
+    /// A token was not recognised at its position.
+    UnexpectedToken,
+    /// A numeric field does not fit in its bits.
+    OutOfRange,
+    /// The input had more tokens than the word needs.
+    TrailingInput,
+    /// The output buffer is too small for the parsed message.
+    BufferFull,
+}
+
+impl ModeCode {
+    /// Conventional mnemonic, e.g. `TSW` for Transmit Status Word.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            ModeCode::DynamicBusControl => "DBC",
+            ModeCode::Synchronize => "SYNC",
+            ModeCode::TransmitStatusWord => "TSW",
+            ModeCode::InitiateSelfTest => "IST",
+            ModeCode::TransmitterShutdown => "TS",
+            ModeCode::OverrideTransmitter => "OTS",
+            ModeCode::InhibitTerminalFlagBit => "ITF",
+            ModeCode::OverrideInhibitTerminalFlagBit => "OITF",
+            ModeCode::ResetRT => "RST",
+            ModeCode::TransmitVectorWord => "TVW",
+            ModeCode::SynchronizeWithDataWord => "SYNCD",
+            ModeCode::TransmitLastCommand => "TLC",
+            ModeCode::TransmitBITWord => "TBW",
+            ModeCode::SelectedTransmitter => "STS",
+            ModeCode::OverrideSelectedTransmitter => "OSTS",
+            ModeCode::Invalid => "RSVD",
+        }
+    }
+}
+
+impl fmt::Display for ModeCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+impl fmt::Display for RTAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RT{:02}", BitField::<5>::from(*self).value())
+    }
+}
+
+impl fmt::Display for RTAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RTAction::Transmit => f.write_str("T"),
+            RTAction::Receive => f.write_str("R"),
+        }
+    }
+}
+
+impl fmt::Display for CommandWord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.get_rt_addr(), self.get_tr_bit())?;
+        let subaddr = (self.value() >> 5) & 0b11111;
+        let field = self.value() & 0b11111;
+        match self.get_command_data() {
+            CommandWordData::DataTransfer { .. } => {
+                // A word count field of 0 means 32 words.
+                let count = if field == 0 { 32 } else { field };
+                write!(f, " SA{:02} WC{:02}", subaddr, count)
+            }
+            CommandWordData::ModeCode(code) => {
+                // Subaddress 31 is the default, only spell out subaddress 0.
+                if subaddr == 0 {
+                    f.write_str(" SA00")?;
+                }
+                write!(f, " MC{:02} {}", field, code)
+            }
+        }
+    }
+}
+
+impl fmt::Display for StatusWord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.get_rt_addr())?;
+        for (name, bit) in STATUS_FLAGS {
+            if self.value() & (1 << bit) != 0 {
+                write!(f, " {}", name)?;
+            }
+        }
+        let reserved = (self.value() & STATUS_RESERVED_MASK) >> 5;
+        if reserved != 0 {
+            write!(f, " RSV{:03b}", reserved)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DataWord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04X}", self.value())
+    }
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Word::Command(cmd) => cmd.fmt(f),
+            Word::Data(dw) => dw.fmt(f),
+            Word::Status(sw) => sw.fmt(f),
+        }
+    }
+}
+
+fn parse_prefixed(token: &str, prefix: &str, max: u16) -> Result<u16, ParseWordError> {
+    let digits = token
+        .strip_prefix(prefix)
+        .ok_or(ParseWordError::UnexpectedToken)?;
+    let value: u16 = digits
+        .parse()
+        .map_err(|_| ParseWordError::UnexpectedToken)?;
+    if value > max {
+        return Err(ParseWordError::OutOfRange);
+    }
+    Ok(value)
+}
+
+fn parse_rt_addr(token: Option<&str>) -> Result<u16, ParseWordError> {
+    parse_prefixed(token.ok_or(ParseWordError::Empty)?, "RT", 31)
+}
+
+fn parse_tr(token: &str) -> Option<RTAction> {
+    match token {
+        "T" => Some(RTAction::Transmit),
+        "R" => Some(RTAction::Receive),
+        _ => None,
+    }
+}
+
+impl FromStr for CommandWord {
+    type Err = ParseWordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace();
+        let addr = parse_rt_addr(tokens.next())?;
+        let tr = tokens
+            .next()
+            .and_then(parse_tr)
+            .ok_or(ParseWordError::UnexpectedToken)?;
+        let mut token = tokens.next().ok_or(ParseWordError::UnexpectedToken)?;
+
+        let (subaddr, field) = if token.starts_with("SA") {
+            let subaddr = parse_prefixed(token, "SA", 31)?;
+            token = tokens.next().ok_or(ParseWordError::UnexpectedToken)?;
+            if token.starts_with("MC") {
+                if subaddr != 0 && subaddr != 31 {
+                    return Err(ParseWordError::UnexpectedToken);
+                }
+                (subaddr, parse_prefixed(token, "MC", 31)?)
+            } else {
+                let count = parse_prefixed(token, "WC", 32)?;
+                if count == 0 {
+                    return Err(ParseWordError::OutOfRange);
+                }
+                (subaddr, count % 32)
+            }
+        } else {
+            (31, parse_prefixed(token, "MC", 31)?)
+        };
+
+        // The mode code mnemonic is optional, but must match if present.
+        if subaddr == 0 || subaddr == 31 {
+            if let Some(name) = tokens.next() {
+                if name != ModeCode::from(field as u8).mnemonic() {
+                    return Err(ParseWordError::UnexpectedToken);
+                }
+            }
+        }
+        if tokens.next().is_some() {
+            return Err(ParseWordError::TrailingInput);
+        }
+
+        let mut value = addr << 11;
+        value = tr.set_in(value);
+        Ok(CommandWord::from_u16(value | subaddr << 5 | field))
+    }
+}
+
+impl FromStr for StatusWord {
+    type Err = ParseWordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace();
+        let mut value = parse_rt_addr(tokens.next())? << 11;
+        for token in tokens {
+            if let Some(bits) = token.strip_prefix("RSV") {
+                let reserved =
+                    u16::from_str_radix(bits, 2).map_err(|_| ParseWordError::UnexpectedToken)?;
+                if reserved > 0b111 {
+                    return Err(ParseWordError::OutOfRange);
+                }
+                value |= reserved << 5;
+                continue;
+            }
+            let (_, bit) = STATUS_FLAGS
+                .iter()
+                .find(|(name, _)| *name == token)
+                .ok_or(ParseWordError::UnexpectedToken)?;
+            value |= 1 << bit;
+        }
+        Ok(StatusWord::from_u16(value))
+    }
+}
+
+impl FromStr for DataWord {
+    type Err = ParseWordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseWordError::Empty);
+        }
+        let digits = s.strip_prefix("0x").unwrap_or(s);
+        if digits.len() > 4 {
+            return Err(ParseWordError::OutOfRange);
+        }
+        u16::from_str_radix(digits, 16)
+            .map(DataWord::from_u16)
+            .map_err(|_| ParseWordError::UnexpectedToken)
+    }
+}
+
+impl FromStr for Word {
+    type Err = ParseWordError;
+
+    /// Anything starting with an `RT` address is a command word if a T/R
+    /// letter follows, and a status word otherwise. Everything else must be
+    /// a hex data word.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace();
+        match tokens.next() {
+            None => Err(ParseWordError::Empty),
+            Some(first) if first.starts_with("RT") => {
+                if tokens.next().and_then(parse_tr).is_some() {
+                    s.parse().map(Word::Command)
+                } else {
+                    s.parse().map(Word::Status)
+                }
+            }
+            Some(_) => s.parse().map(Word::Data),
+        }
+    }
+}
+
+/// Display wrapper for a reconstructed message (or any word sequence).
+/// Command and status words are separated by ` / `, data words in between
+/// are grouped on one line: `RT05 R SA03 WC02 / 0001 0002 / RT05`.
+pub struct Disassembly<'a>(pub &'a [Word]);
+
+impl fmt::Display for Disassembly<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut prev_data = false;
+        for (idx, word) in self.0.iter().enumerate() {
+            let is_data = matches!(word, Word::Data(_));
+            if idx > 0 {
+                f.write_str(if is_data && prev_data { " " } else { " / " })?;
+            }
+            word.fmt(f)?;
+            prev_data = is_data;
+        }
+        Ok(())
+    }
+}
+
+impl Disassembly<'_> {
+    /// Parse the output of `Disassembly`'s Display back into `buf`,
+    /// returning the number of words written.
+    pub fn parse_into(s: &str, buf: &mut [Word]) -> Result<usize, ParseWordError> {
+        let mut count = 0;
+        for group in s.split('/') {
+            let group = group.trim();
+            if group.starts_with("RT") {
+                *buf.get_mut(count).ok_or(ParseWordError::BufferFull)? = group.parse()?;
+                count += 1;
+                continue;
+            }
+            for token in group.split_whitespace() {
+                *buf.get_mut(count).ok_or(ParseWordError::BufferFull)? = Word::Data(token.parse()?);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::string::ToString;
+
+    use crate::disasm::*;
+
+    #[test]
+    fn command_word_shorthand() {
+        let cmd = CommandWord::new_data_transfer(
+            RTAddr::Single(5.into()),
+            RTAction::Receive,
+            3.into(),
+            4.into(),
+        );
+        assert_eq!(cmd.to_string(), "RT05 R SA03 WC04");
+        assert_eq!("RT05 R SA03 WC04".parse::<CommandWord>(), Ok(cmd));
+
+        let full = CommandWord::new_data_transfer(
+            RTAddr::Single(5.into()),
+            RTAction::Transmit,
+            3.into(),
+            0.into(),
+        );
+        assert_eq!(full.to_string(), "RT05 T SA03 WC32");
+        assert_eq!("RT05 T SA03 WC32".parse::<CommandWord>(), Ok(full));
+    }
+
+    #[test]
+    fn mode_command_shorthand() {
+        let cmd =
+            CommandWord::new_mode_command(RTAddr::Single(12.into()), ModeCode::TransmitStatusWord);
+        assert_eq!(cmd.to_string(), "RT12 T MC02 TSW");
+        assert_eq!("RT12 T MC02 TSW".parse::<CommandWord>(), Ok(cmd));
+        assert_eq!("RT12 T MC02".parse::<CommandWord>(), Ok(cmd));
+        assert_eq!(
+            "RT12 T MC02 RST".parse::<CommandWord>(),
+            Err(ParseWordError::UnexpectedToken)
+        );
+
+        let sa0 = CommandWord::from_u16(0b0110010000000010);
+        assert_eq!(sa0.to_string(), "RT12 T SA00 MC02 TSW");
+        assert_eq!(sa0.to_string().parse::<CommandWord>(), Ok(sa0));
+    }
+
+    #[test]
+    fn status_word_shorthand() {
+        let sw = StatusWord::builder(RTAddr::Single(5.into()))
+            .message_error(true)
+            .busy(true)
+            .terminal_flag(true)
+            .build();
+        assert_eq!(sw.to_string(), "RT05 ME BUSY TF");
+        assert_eq!("RT05 ME BUSY TF".parse::<StatusWord>(), Ok(sw));
+        assert_eq!(
+            "RT05 BUZY".parse::<StatusWord>(),
+            Err(ParseWordError::UnexpectedToken)
+        );
+    }
+
+    #[test]
+    fn message_roundtrip() {
+        let words = [
+            Word::Command(CommandWord::new_data_transfer(
+                RTAddr::Single(5.into()),
+                RTAction::Receive,
+                3.into(),
+                2.into(),
+            )),
+            Word::Data(DataWord::from_u16(0x0001)),
+            Word::Data(DataWord::from_u16(0xBEEF)),
+            Word::Status(StatusWord::builder(RTAddr::Single(5.into())).build()),
+        ];
+        let text = Disassembly(&words).to_string();
+        assert_eq!(text, "RT05 R SA03 WC02 / 0001 BEEF / RT05");
+
+        let mut buf = [Word::Data(DataWord::from_u16(0)); 8];
+        let len = Disassembly::parse_into(&text, &mut buf).unwrap();
+        assert_eq!(&buf[..len], &words);
+        assert_eq!(
+            Disassembly::parse_into(&text, &mut buf[..2]),
+            Err(ParseWordError::BufferFull)
+        );
+    }
+}
@@ -0,0 +1,304 @@
+//! Manchester decoding from edge timestamps, for RTs built around a plain
+//! transceiver and a timer input-capture peripheral.
+//!
+//! Feed every captured [`Edge`] to an [`EdgeDecoder`], and call
+//! [`EdgeDecoder::flush`] when the line has been quiet for a while, since
+//! the last half-bit of a word has no edge of its own to end it. Each word
+//! is timed from the transition in the middle of its sync, and its other
+//! edges may stray up to 249 ns from their nominal place relative to it,
+//! more than the ±150 ns zero-crossing deviation a MIL-STD-1553B receiver
+//! has to accept.
+use crate::manchester::{self, DecodedWord, WordError, HALF_BITS, HALF_BIT_NS};
+
+/// Shortest run taken for half a sync while looking for a word: 1.5 µs
+/// with both its edges 150 ns off.
+const SYNC_MIN_NS: u64 = 1_200;
+/// Longest a level may be held inside a word, at the sync, before the word
+/// is taken as cut short.
+const MAX_HOLD_HALVES: usize = 4;
+
+/// A transition on the bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub time_ns: u64,
+    /// Level after the edge.
+    pub positive: bool,
+}
+
+impl Edge {
+    pub fn new(time_ns: u64, positive: bool) -> Self {
+        Self { time_ns, positive }
+    }
+}
+
+/// A word being received.
+#[derive(Debug, Clone, Copy)]
+struct Partial {
+    /// The transition in the middle of the sync.
+    mid_ns: u64,
+    levels: [bool; HALF_BITS],
+    count: usize,
+    /// An edge arrived where none can be.
+    glitch: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EdgeDecoder {
+    level: bool,
+    /// When `level` started, `None` before the first edge.
+    since_ns: Option<u64>,
+    word: Option<Partial>,
+}
+
+impl EdgeDecoder {
+    pub const fn new() -> Self {
+        Self {
+            level: false,
+            since_ns: None,
+            word: None,
+        }
+    }
+
+    /// Take the next edge, in time order. Returns the word this edge
+    /// completes, if any. An edge from before the last one, as when the
+    /// capture counter wraps, cuts the word in progress short and starts
+    /// over as the first edge would.
+    pub fn push(&mut self, edge: Edge) -> Option<DecodedWord> {
+        let Some(since_ns) = self.since_ns.filter(|since_ns| *since_ns <= edge.time_ns) else {
+            let cut = self.word.take().map(|word| word.finish(self.level, true));
+            self.level = edge.positive;
+            self.since_ns = Some(edge.time_ns);
+            return cut;
+        };
+        if edge.positive == self.level {
+            // The capture missed the edge in between.
+            if let Some(word) = &mut self.word {
+                word.glitch = true;
+            }
+            return None;
+        }
+        let held = self.level;
+        self.level = edge.positive;
+        self.since_ns = Some(edge.time_ns);
+
+        let Some(mut word) = self.word.take() else {
+            if edge.time_ns - since_ns >= SYNC_MIN_NS {
+                self.word = Some(Partial::start(edge.time_ns, held));
+            }
+            return None;
+        };
+        // Half-bits from the start of the word to this edge.
+        let pos = word.position(edge.time_ns);
+        if pos <= word.count {
+            word.glitch = true;
+            self.word = Some(word);
+            return None;
+        }
+        if pos < HALF_BITS {
+            if pos - word.count > MAX_HOLD_HALVES {
+                // The line went quiet mid-word; this edge starts afresh.
+                return Some(word.finish(held, true));
+            }
+            word.levels[word.count..pos].fill(held);
+            word.count = pos;
+            self.word = Some(word);
+            return None;
+        }
+        let truncated = HALF_BITS - word.count > MAX_HOLD_HALVES;
+        // What is left is the first half of the next sync, or a gap before
+        // a sync.
+        if pos - HALF_BITS >= 3 {
+            self.word = Some(Partial::start(edge.time_ns, held));
+        }
+        Some(word.finish(held, truncated))
+    }
+
+    /// Complete the word in progress if the line has held its level long
+    /// enough by `now_ns` to end it.
+    pub fn flush(&mut self, now_ns: u64) -> Option<DecodedWord> {
+        let word = self.word?;
+        if word.position(now_ns) < HALF_BITS {
+            return None;
+        }
+        self.word = None;
+        Some(word.finish(self.level, HALF_BITS - word.count > MAX_HOLD_HALVES))
+    }
+}
+
+impl Default for EdgeDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Partial {
+    fn start(mid_ns: u64, first: bool) -> Self {
+        let mut levels = [false; HALF_BITS];
+        levels[..3].fill(first);
+        Self {
+            mid_ns,
+            levels,
+            count: 3,
+            glitch: false,
+        }
+    }
+
+    /// Half-bits from the start of the word to `time_ns`, rounded.
+    fn position(&self, time_ns: u64) -> usize {
+        let half = HALF_BIT_NS as u64;
+        ((time_ns.saturating_sub(self.mid_ns) + half / 2) / half) as usize + 3
+    }
+
+    /// Fill the rest of the word with `level` and decode it.
+    fn finish(mut self, level: bool, truncated: bool) -> DecodedWord {
+        self.levels[self.count..].fill(level);
+        let time_ns = self.mid_ns.saturating_sub(3 * HALF_BIT_NS as u64);
+        // A broken sync still gets reported, as a Manchester error.
+        let mut word = manchester::decode(&self.levels).unwrap_or(manchester::RawWord {
+            sync: manchester::Sync::from_first_level(self.levels[0]),
+            value: 0,
+            error: Some(WordError::Manchester),
+        });
+        if truncated {
+            word.error = Some(WordError::Truncated);
+        } else if self.glitch && word.error.is_none() {
+            word.error = Some(WordError::Manchester);
+        }
+        DecodedWord { time_ns, word }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{edge::*, manchester::*, words::*};
+
+    /// Edges of `words` back to back from `start_ns`, each but the mid-sync
+    /// ones moved by the next value of `jitter`.
+    fn edges(
+        start_ns: u64,
+        levels: &[[bool; HALF_BITS]],
+        jitter: &mut impl Iterator<Item = i64>,
+        out: &mut [Edge; 256],
+    ) -> usize {
+        let mut len = 0;
+        let mut last = None;
+        for (idx, half) in levels.iter().flatten().enumerate() {
+            if last != Some(*half) {
+                let mut time = start_ns + idx as u64 * HALF_BIT_NS as u64;
+                if idx % HALF_BITS != 3 {
+                    time = time.saturating_add_signed(jitter.next().unwrap());
+                }
+                out[len] = Edge::new(time, *half);
+                len += 1;
+                last = Some(*half);
+            }
+        }
+        len
+    }
+
+    #[test]
+    fn decodes_back_to_back_words_with_jitter() {
+        let words = [
+            Word::Command(CommandWord::from_u16(0x1C22)),
+            Word::Data(DataWord::from_u16(0x0000)),
+            Word::Data(DataWord::from_u16(0xFFFF)),
+        ];
+        let levels = words.map(encode_word);
+        let mut jitter = [150, -150, 0, 120, -90].into_iter().cycle();
+        let mut buf = [Edge::new(0, false); 256];
+        let len = edges(10_000, &levels, &mut jitter, &mut buf);
+
+        let mut decoder = EdgeDecoder::new();
+        let mut decoded = buf[..len].iter().filter_map(|edge| decoder.push(*edge));
+        for (idx, word) in words.iter().take(2).enumerate() {
+            let got = decoded.next().unwrap();
+            assert_eq!(got.word.to_word(false), Ok(*word));
+            assert!(got.time_ns.abs_diff(10_000 + idx as u64 * 20_000) <= 150);
+        }
+        assert_eq!(decoded.next(), None);
+        // The last word ends without an edge.
+        assert_eq!(decoder.flush(69_000), None);
+        let last = decoder.flush(71_000).unwrap();
+        assert_eq!(last.word.to_word(false), Ok(words[2]));
+    }
+
+    #[test]
+    fn flags_parity_manchester_and_truncated_words() {
+        let mut bad_parity = encode(Sync::Data, 0x0F0F);
+        bad_parity[38] = !bad_parity[38];
+        bad_parity[39] = !bad_parity[39];
+        let mut decoder = EdgeDecoder::new();
+        let mut buf = [Edge::new(0, false); 256];
+        let len = edges(0, &[bad_parity], &mut core::iter::repeat(0), &mut buf);
+        for edge in &buf[..len] {
+            assert_eq!(decoder.push(*edge), None);
+        }
+        let word = decoder.flush(25_000).unwrap().word;
+        assert_eq!(
+            (word.sync, word.error),
+            (Sync::Data, Some(WordError::Parity))
+        );
+
+        // A bit without its mid-bit transition.
+        let mut no_transition = encode(Sync::CommandStatus, 0x0F0F);
+        no_transition[21] = no_transition[20];
+        let mut decoder = EdgeDecoder::new();
+        let len = edges(0, &[no_transition], &mut core::iter::repeat(0), &mut buf);
+        buf[..len]
+            .iter()
+            .for_each(|edge| assert_eq!(decoder.push(*edge), None));
+        let word = decoder.flush(25_000).unwrap().word;
+        assert_eq!(word.error, Some(WordError::Manchester));
+
+        // Cut off after 8 bits, then a new command sync much later.
+        let mut decoder = EdgeDecoder::new();
+        let len = edges(
+            0,
+            &[encode(Sync::CommandStatus, 0x5555)],
+            &mut core::iter::repeat(0),
+            &mut buf,
+        );
+        let cut = buf[..len]
+            .iter()
+            .position(|edge| edge.time_ns >= 11_000)
+            .unwrap();
+        buf[..cut]
+            .iter()
+            .for_each(|edge| assert_eq!(decoder.push(*edge), None));
+        let late = Edge::new(50_000, !buf[cut - 1].positive);
+        let word = decoder.push(late).unwrap().word;
+        assert_eq!(word.error, Some(WordError::Truncated));
+    }
+
+    #[test]
+    fn resyncs_when_time_goes_backwards() {
+        let word = Word::Data(DataWord::from_u16(0x1553));
+        let mut buf = [Edge::new(0, false); 256];
+        let len = edges(
+            1_000_000,
+            &[encode_word(word)],
+            &mut core::iter::repeat(0),
+            &mut buf,
+        );
+        let mut decoder = EdgeDecoder::new();
+        for edge in &buf[..len / 2] {
+            assert_eq!(decoder.push(*edge), None);
+        }
+        // The counter wraps mid-word.
+        let cut = decoder
+            .push(Edge::new(100, !buf[len / 2 - 1].positive))
+            .unwrap();
+        assert_eq!(cut.word.error, Some(WordError::Truncated));
+
+        // And the next word decodes from the new time base.
+        let len = edges(
+            5_000,
+            &[encode_word(word)],
+            &mut core::iter::repeat(0),
+            &mut buf,
+        );
+        let decoded = buf[..len].iter().filter_map(|edge| decoder.push(*edge));
+        assert_eq!(decoded.count(), 0);
+        assert_eq!(decoder.flush(30_000).unwrap().word.to_word(false), Ok(word));
+    }
+}
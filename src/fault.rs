@@ -0,0 +1,371 @@
+//! Fault injection between a BusController (or RT) and any [`Bus`].
+//!
+//! Faults are applied to the words coming back from the bus, which is where
+//! a BC has to detect them. Each [`FaultRule`] picks the words it applies to
+//! and when it fires, either deterministically or from a seeded generator so
+//! random runs can be replayed.
+use crate::{
+    bus::{Bus, BusError, BusId},
+    primitives::{AlignableBitField, BitField},
+    queue::WordQueue,
+    words::*,
+};
+
+/// Longest burst of words a FaultyBus holds back (late, extra and babbled
+/// words).
+pub const FAULT_QUEUE_LEN: usize = 64;
+
+/// Longest reply: a status word and 32 data words.
+const MAX_REPLY_WORDS: usize = 33;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The word is read with a parity error.
+    Parity,
+    /// The word arrives with the other sync type: command and status words
+    /// read as data, data words read as status.
+    WrongSync,
+    /// The word never arrives.
+    Drop,
+    /// An extra data word arrives right after the word.
+    Extra(DataWord),
+    /// The word arrives after the response timeout: the read times out and
+    /// the word shows up on the next read instead.
+    Late,
+    /// A status word carries this RT address instead of its own.
+    WrongAddress(BitField<5>),
+    /// The word and the rest of the reply, up to a whole reply's worth
+    /// of words, are lost.
+    NoResponse,
+    /// A babbling transmitter puts this many junk data words on the bus
+    /// before the word.
+    Babble(u8),
+}
+
+/// Which words a rule looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Any,
+    Command,
+    Status,
+    Data,
+}
+
+impl Target {
+    fn matches(&self, word: &Word) -> bool {
+        matches!(
+            (self, word),
+            (Target::Any, _)
+                | (Target::Command, Word::Command(_))
+                | (Target::Status, Word::Status(_))
+                | (Target::Data, Word::Data(_))
+        )
+    }
+}
+
+/// When a rule fires, counting only the words its target matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// Only on the n-th matching word (0-based).
+    Nth(u32),
+    /// On every n-th matching word.
+    Every(u32),
+    /// On each matching word with a probability of `per_mille`/1000.
+    Random { per_mille: u16 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaultRule {
+    pub target: Target,
+    pub trigger: Trigger,
+    pub fault: Fault,
+}
+
+impl FaultRule {
+    pub fn new(target: Target, trigger: Trigger, fault: Fault) -> Self {
+        Self {
+            target,
+            trigger,
+            fault,
+        }
+    }
+}
+
+/// [`Bus`] decorator injecting the faults of `N` rules. When several rules
+/// fire on the same word, only the first one applies.
+pub struct FaultyBus<B: Bus, const N: usize> {
+    inner: B,
+    rules: [FaultRule; N],
+    matched: [u32; N],
+    rng: u32,
+    held: WordQueue<FAULT_QUEUE_LEN>,
+    injected: u32,
+}
+
+impl<B: Bus, const N: usize> FaultyBus<B, N> {
+    /// `seed` drives the `Random` triggers and the babbled words.
+    pub fn new(inner: B, rules: [FaultRule; N], seed: u32) -> Self {
+        Self {
+            inner,
+            rules,
+            matched: [0; N],
+            // xorshift gets stuck on 0.
+            rng: if seed == 0 { 0x9E37_79B9 } else { seed },
+            held: WordQueue::new(),
+            injected: 0,
+        }
+    }
+
+    /// Number of faults injected so far.
+    pub fn injected(&self) -> u32 {
+        self.injected
+    }
+
+    pub fn inner(&mut self) -> &mut B {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    fn next_random(&mut self) -> u32 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng = x;
+        x
+    }
+
+    fn fires(&mut self, idx: usize, word: &Word) -> bool {
+        let rule = self.rules[idx];
+        if !rule.target.matches(word) {
+            return false;
+        }
+        let count = self.matched[idx];
+        // Long soak runs wrap around rather than overflow.
+        self.matched[idx] = count.wrapping_add(1);
+        match rule.trigger {
+            Trigger::Nth(n) => count == n,
+            Trigger::Every(n) => n != 0 && count.wrapping_add(1).is_multiple_of(n),
+            Trigger::Random { per_mille } => self.next_random() % 1000 < per_mille as u32,
+        }
+    }
+
+    fn apply(&mut self, fault: Fault, word: Word) -> Result<Word, BusError> {
+        self.injected = self.injected.wrapping_add(1);
+        match fault {
+            Fault::Parity => Err(BusError::Parity),
+            Fault::WrongSync => Ok(match word {
+                Word::Command(cmd) => Word::Data(DataWord::from_u16(cmd.value())),
+                Word::Status(sw) => Word::Data(DataWord::from_u16(sw.value())),
+                Word::Data(dw) => Word::Status(StatusWord::from_u16(dw.value())),
+            }),
+            Fault::Drop => self.read_next(),
+            Fault::Extra(extra) => {
+                let _ = self.held.push(Word::Data(extra));
+                Ok(word)
+            }
+            Fault::Late => {
+                let _ = self.held.push(word);
+                Err(BusError::NoResponse)
+            }
+            Fault::WrongAddress(addr) => Ok(match word {
+                Word::Status(sw) => {
+                    Word::Status(StatusWord::from_u16(RTAddr::from(addr).set_in(sw.value())))
+                }
+                other => other,
+            }),
+            Fault::NoResponse => {
+                // Bounded, for a bus that never runs dry.
+                for _ in 1..MAX_REPLY_WORDS {
+                    if self.inner.read_next().is_err() {
+                        break;
+                    }
+                }
+                Err(BusError::NoResponse)
+            }
+            Fault::Babble(count) => {
+                for _ in 0..count {
+                    let junk = Word::Data(DataWord::from_u16(self.next_random() as u16));
+                    let _ = self.held.push(junk);
+                }
+                let _ = self.held.push(word);
+                self.held.pop().ok_or(BusError::NoResponse)
+            }
+        }
+    }
+}
+
+impl<B: Bus, const N: usize> Bus for FaultyBus<B, N> {
+    fn write_word(&mut self, value: Word) {
+        self.inner.write_word(value)
+    }
+
+    fn select_bus(&mut self, id: BusId) {
+        self.inner.select_bus(id)
+    }
+
+    fn idle(&mut self, ns: u64) {
+        self.inner.idle(ns)
+    }
+
+    fn read_next(&mut self) -> Result<Word, BusError> {
+        if let Some(word) = self.held.pop() {
+            return Ok(word);
+        }
+        let word = self.inner.read_next()?;
+        let mut fault = None;
+        for idx in 0..N {
+            // Every rule sees the word, so counts stay independent.
+            if self.fires(idx, &word) && fault.is_none() {
+                fault = Some(self.rules[idx].fault);
+            }
+        }
+        match fault {
+            Some(fault) => self.apply(fault, word),
+            None => Ok(word),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{bus::*, fault::*, rt::RemoteTerminal, sim::SimBus};
+
+    const RT: RTAddr = RTAddr::Single(BitField::new(4));
+
+    fn transmit(bus: &mut dyn Bus) -> Result<StatusWord, TransferError> {
+        let mut out = [DataWord::from_u16(0); 2];
+        BusController::new(bus).receive_transfer(RT, 1.into(), &mut out)
+    }
+
+    fn with_fault(rule: FaultRule, check: impl Fn(&mut dyn Bus)) {
+        let mut rts = [RemoteTerminal::new(4.into())];
+        let sim = SimBus::new(BusId::A, &mut rts);
+        let mut bus = FaultyBus::new(sim, [rule], 1);
+        check(&mut bus);
+        assert!(bus.injected() > 0);
+    }
+
+    #[test]
+    fn status_faults() {
+        with_fault(
+            FaultRule::new(Target::Status, Trigger::Nth(0), Fault::Parity),
+            |bus| assert_eq!(transmit(bus), Err(TransferError::Parity)),
+        );
+        with_fault(
+            FaultRule::new(Target::Status, Trigger::Nth(0), Fault::WrongSync),
+            |bus| {
+                assert!(matches!(
+                    transmit(bus),
+                    Err(TransferError::UnexpectedWord(Word::Data(_)))
+                ))
+            },
+        );
+        with_fault(
+            FaultRule::new(
+                Target::Status,
+                Trigger::Nth(0),
+                Fault::WrongAddress(5.into()),
+            ),
+            |bus| assert!(matches!(transmit(bus), Err(TransferError::WrongAddress(_)))),
+        );
+        with_fault(
+            FaultRule::new(Target::Status, Trigger::Nth(0), Fault::NoResponse),
+            |bus| {
+                assert_eq!(transmit(bus), Err(TransferError::NoResponse));
+                assert!(transmit(bus).is_ok());
+            },
+        );
+    }
+
+    #[test]
+    fn late_response_shows_up_on_the_next_read() {
+        with_fault(
+            FaultRule::new(Target::Status, Trigger::Nth(0), Fault::Late),
+            |bus| {
+                assert_eq!(transmit(bus), Err(TransferError::NoResponse));
+                assert_eq!(
+                    bus.read_next().map(|w| matches!(w, Word::Status(_))),
+                    Ok(true)
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn data_word_faults() {
+        with_fault(
+            FaultRule::new(Target::Data, Trigger::Nth(1), Fault::Drop),
+            |bus| assert_eq!(transmit(bus), Err(TransferError::NoResponse)),
+        );
+        with_fault(
+            FaultRule::new(
+                Target::Data,
+                Trigger::Nth(1),
+                Fault::Extra(DataWord::from_u16(0xEE)),
+            ),
+            |bus| {
+                assert!(transmit(bus).is_ok());
+                assert_eq!(bus.read_next(), Ok(Word::Data(DataWord::from_u16(0xEE))));
+            },
+        );
+        with_fault(
+            FaultRule::new(Target::Any, Trigger::Nth(0), Fault::Babble(3)),
+            |bus| {
+                assert!(matches!(
+                    transmit(bus),
+                    Err(TransferError::UnexpectedWord(Word::Data(_)))
+                ))
+            },
+        );
+    }
+
+    #[test]
+    fn seeded_random_faults_repeat() {
+        let run = |seed| {
+            let mut rts = [RemoteTerminal::new(4.into())];
+            let sim = SimBus::new(BusId::A, &mut rts);
+            let rule = FaultRule::new(
+                Target::Any,
+                Trigger::Random { per_mille: 300 },
+                Fault::Parity,
+            );
+            let mut bus = FaultyBus::new(sim, [rule], seed);
+            let mut outcomes = [false; 32];
+            for outcome in outcomes.iter_mut() {
+                *outcome = transmit(&mut bus).is_ok();
+            }
+            outcomes
+        };
+        assert_eq!(run(7), run(7));
+        assert!(run(7).contains(&false) && run(7).contains(&true));
+    }
+
+    /// A bus that always has another word, like a babbling RT.
+    struct Endless;
+
+    impl Bus for Endless {
+        fn write_word(&mut self, _value: Word) {}
+
+        fn read_next(&mut self) -> Result<Word, BusError> {
+            Ok(Word::Data(DataWord::from_u16(0xBABB)))
+        }
+    }
+
+    #[test]
+    fn survives_endless_streams() {
+        let rule = FaultRule::new(Target::Any, Trigger::Every(2), Fault::NoResponse);
+        let mut bus = FaultyBus::new(Endless, [rule], 1);
+        assert!(bus.read_next().is_ok());
+        assert_eq!(bus.read_next(), Err(BusError::NoResponse));
+
+        // A soak run long enough to wrap the word count.
+        bus.matched[0] = u32::MAX - 1;
+        assert!(bus.read_next().is_ok());
+        assert_eq!(bus.read_next(), Err(BusError::NoResponse));
+        assert!(bus.read_next().is_ok());
+    }
+}
@@ -0,0 +1,52 @@
+//! Byte framing of bus words, for buses carried over host streams and
+//! sockets.
+//!
+//! A frame is 3 bytes: the word type, then the 16-bit word, big endian.
+//! The type stands in for the sync pattern, which a plain `u16` loses.
+use crate::words::*;
+
+pub const FRAME_LEN: usize = 3;
+
+const COMMAND: u8 = b'C';
+const STATUS: u8 = b'S';
+const DATA: u8 = b'D';
+
+pub fn encode(word: Word) -> [u8; FRAME_LEN] {
+    let (kind, value) = match word {
+        Word::Command(cmd) => (COMMAND, cmd.value()),
+        Word::Status(sw) => (STATUS, sw.value()),
+        Word::Data(dw) => (DATA, dw.value()),
+    };
+    let [hi, lo] = value.to_be_bytes();
+    [kind, hi, lo]
+}
+
+/// The word in `frame`, or `None` when its type byte is unknown.
+pub fn decode(frame: [u8; FRAME_LEN]) -> Option<Word> {
+    let value = u16::from_be_bytes([frame[1], frame[2]]);
+    match frame[0] {
+        COMMAND => Some(Word::Command(CommandWord::from_u16(value))),
+        STATUS => Some(Word::Status(StatusWord::from_u16(value))),
+        DATA => Some(Word::Data(DataWord::from_u16(value))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frame::*;
+
+    #[test]
+    fn frames_roundtrip() {
+        let words = [
+            Word::Command(CommandWord::from_u16(0x2043)),
+            Word::Status(StatusWord::from_u16(0x2000)),
+            Word::Data(DataWord::from_u16(0xBEEF)),
+        ];
+        for word in words {
+            assert_eq!(decode(encode(word)), Some(word));
+        }
+        assert_eq!(encode(words[2]), [b'D', 0xBE, 0xEF]);
+        assert_eq!(decode([0, 0, 0]), None);
+    }
+}
@@ -0,0 +1,293 @@
+//! Interface control document: the messages of a system and the signals
+//! packed in their data words.
+//!
+//! An ICD is a text file with one entry per line; `#` starts a comment.
+//!
+//! ```text
+//! # Direction is the RT's: rx for data it receives, tx for data it sends.
+//! message nav_state rt=5 rx sa=1 wc=4 rate=50
+//! # A field of one data word, from its lowest bit, scaled to units.
+//! signal nav_state.altitude word=0 lsb=0 bits=16 signed scale=0.5 units=ft
+//! signal nav_state.valid word=1 lsb=15 bits=1
+//! ```
+//!
+//! `rate` is the lowest rate, in Hz, the message must be sent at. Signals
+//! default to a whole unsigned word with a scale of 1 and no offset.
+use core::str::FromStr;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use crate::{
+    keys::{KeyError, Tokens},
+    words::{CommandWord, DataWord, RTAction},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IcdMessage {
+    pub name: String,
+    pub rt: u8,
+    pub action: RTAction,
+    pub subaddress: u8,
+    /// Data words, 1 to 32.
+    pub word_count: u8,
+    /// Lowest rate the message must go at, in Hz.
+    pub rate_hz: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signal {
+    /// Index of its message in [`Icd::messages`].
+    pub message: usize,
+    pub name: String,
+    /// Index of its data word in the message.
+    pub word: u8,
+    pub lsb: u8,
+    pub bits: u8,
+    /// Two's complement rather than unsigned.
+    pub signed: bool,
+    pub scale: f64,
+    pub offset: f64,
+    pub units: String,
+}
+
+impl Signal {
+    /// The field as it is in `data`, `None` if the message is too short.
+    pub fn raw(&self, data: &[DataWord]) -> Option<i32> {
+        let word = data.get(self.word as usize)?.value() as u32;
+        let field = (word >> self.lsb) & ((1 << self.bits) - 1);
+        let sign = 1 << (self.bits - 1);
+        Some(match self.signed && field & sign != 0 {
+            true => field as i32 - (1 << self.bits),
+            false => field as i32,
+        })
+    }
+
+    /// The field in engineering units.
+    pub fn value(&self, data: &[DataWord]) -> Option<f64> {
+        self.raw(data)
+            .map(|raw| raw as f64 * self.scale + self.offset)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcdErrorKind {
+    /// The line starts with neither `message` nor `signal`.
+    UnknownEntry,
+    UnknownKey,
+    Missing(&'static str),
+    BadValue(&'static str),
+    /// A signal of a message not declared above it.
+    UnknownMessage,
+    /// A second message or signal of the same name.
+    Duplicate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IcdError {
+    /// 1-based line number.
+    pub line: usize,
+    pub kind: IcdErrorKind,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Icd {
+    messages: Vec<IcdMessage>,
+    signals: Vec<Signal>,
+}
+
+impl Icd {
+    pub fn messages(&self) -> &[IcdMessage] {
+        &self.messages
+    }
+
+    pub fn signals(&self) -> &[Signal] {
+        &self.signals
+    }
+
+    /// Index of the message an RT sends or receives on `subaddress`.
+    pub fn find(&self, rt: u8, action: RTAction, subaddress: u8) -> Option<usize> {
+        self.messages
+            .iter()
+            .position(|m| m.rt == rt && m.action == action && m.subaddress == subaddress)
+    }
+
+    /// Index of the message `cmd` asks for.
+    pub fn find_command(&self, cmd: &CommandWord) -> Option<usize> {
+        let value = cmd.value();
+        self.find(
+            (value >> 11) as u8,
+            cmd.get_tr_bit(),
+            ((value >> 5) & 0x1F) as u8,
+        )
+    }
+
+    pub fn signals_of(&self, message: usize) -> impl Iterator<Item = &Signal> {
+        self.signals.iter().filter(move |s| s.message == message)
+    }
+}
+
+impl From<KeyError> for IcdErrorKind {
+    fn from(err: KeyError) -> Self {
+        match err {
+            KeyError::UnknownKey => IcdErrorKind::UnknownKey,
+            KeyError::Missing(key) => IcdErrorKind::Missing(key),
+            KeyError::BadValue(key) => IcdErrorKind::BadValue(key),
+        }
+    }
+}
+
+impl Icd {
+    fn message(&mut self, name: &str, tokens: Tokens) -> Result<(), IcdErrorKind> {
+        if self.messages.iter().any(|m| m.name == name) {
+            return Err(IcdErrorKind::Duplicate);
+        }
+        let action = match (tokens.flag("rx"), tokens.flag("tx")) {
+            (true, false) => RTAction::Receive,
+            (false, true) => RTAction::Transmit,
+            (false, false) => return Err(IcdErrorKind::Missing("rx")),
+            (true, true) => return Err(IcdErrorKind::BadValue("tx")),
+        };
+        let word_count = tokens.number("wc", None, 32)?;
+        if word_count == 0 {
+            return Err(IcdErrorKind::BadValue("wc"));
+        }
+        let rate_hz = match tokens.text("rate") {
+            None => None,
+            Some(_) => Some(tokens.number("rate", None, f64::MAX)?),
+        };
+        if rate_hz.is_some_and(|rate| rate <= 0.0) {
+            return Err(IcdErrorKind::BadValue("rate"));
+        }
+        self.messages.push(IcdMessage {
+            name: name.to_string(),
+            rt: tokens.number("rt", None, 31)?,
+            action,
+            subaddress: tokens.number("sa", None, 31)?,
+            word_count,
+            rate_hz,
+        });
+        Ok(())
+    }
+
+    fn signal(&mut self, name: &str, tokens: Tokens) -> Result<(), IcdErrorKind> {
+        let (message, signal) = name.split_once('.').ok_or(IcdErrorKind::UnknownMessage)?;
+        let message = self
+            .messages
+            .iter()
+            .position(|m| m.name == message)
+            .ok_or(IcdErrorKind::UnknownMessage)?;
+        if self.signals_of(message).any(|s| s.name == signal) {
+            return Err(IcdErrorKind::Duplicate);
+        }
+        let word = tokens.number("word", None, self.messages[message].word_count - 1)?;
+        let lsb = tokens.number("lsb", Some(0), 15)?;
+        let bits = tokens.number("bits", Some(16 - lsb), 16 - lsb)?;
+        if bits == 0 {
+            return Err(IcdErrorKind::BadValue("bits"));
+        }
+        self.signals.push(Signal {
+            message,
+            name: signal.to_string(),
+            word,
+            lsb,
+            bits,
+            signed: tokens.flag("signed"),
+            scale: tokens.number("scale", Some(1.0), f64::MAX)?,
+            offset: tokens.number("offset", Some(0.0), f64::MAX)?,
+            units: tokens.text("units").unwrap_or_default().to_string(),
+        });
+        Ok(())
+    }
+}
+
+impl FromStr for Icd {
+    type Err = IcdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut icd = Icd::default();
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut tokens = line.split_whitespace();
+            let Some(entry) = tokens.next() else {
+                continue;
+            };
+            let name = tokens.next();
+            let result = match (entry, name) {
+                ("message", Some(name)) => {
+                    Tokens::new(tokens, &["rt", "rx", "tx", "sa", "wc", "rate"])
+                        .map_err(IcdErrorKind::from)
+                        .and_then(|tokens| icd.message(name, tokens))
+                }
+                ("signal", Some(name)) => Tokens::new(
+                    tokens,
+                    &["word", "lsb", "bits", "signed", "scale", "offset", "units"],
+                )
+                .map_err(IcdErrorKind::from)
+                .and_then(|tokens| icd.signal(name, tokens)),
+                ("message" | "signal", None) => Err(IcdErrorKind::Missing("name")),
+                _ => Err(IcdErrorKind::UnknownEntry),
+            };
+            result.map_err(|kind| IcdError {
+                line: idx + 1,
+                kind,
+            })?;
+        }
+        Ok(icd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{icd::*, words::*};
+
+    const ICD: &str = "
+        # Navigation computer, RT 5.
+        message nav_state rt=5 rx sa=1 wc=2 rate=50
+        signal nav_state.altitude word=0 signed scale=0.5 units=ft
+        signal nav_state.mode word=1 lsb=12 bits=4
+        message nav_status rt=5 tx sa=2 wc=1
+    ";
+
+    #[test]
+    fn parses_and_decodes_signals() {
+        let icd: Icd = ICD.parse().unwrap();
+        assert_eq!(icd.messages().len(), 2);
+        let cmd = CommandWord::from_u16(0x2822);
+        let nav = icd.find_command(&cmd).unwrap();
+        assert_eq!(icd.messages()[nav].word_count, 2);
+        assert_eq!(icd.messages()[nav].rate_hz, Some(50.0));
+        assert_eq!(icd.find(5, RTAction::Transmit, 1), None);
+
+        let data = [DataWord::from_u16(0xFFF6), DataWord::from_u16(0x3000)];
+        let mut signals = icd.signals_of(nav);
+        let altitude = signals.next().unwrap();
+        assert_eq!(altitude.value(&data), Some(-5.0));
+        assert_eq!(altitude.units, "ft");
+        assert_eq!(signals.next().unwrap().raw(&data), Some(3));
+        assert_eq!(altitude.value(&data[..0]), None);
+    }
+
+    #[test]
+    fn reports_the_bad_line() {
+        let err = |text: &str| text.parse::<Icd>().unwrap_err();
+        assert_eq!(
+            err("message a rt=5 rx sa=1 wc=2\nsignal b.x word=0"),
+            IcdError {
+                line: 2,
+                kind: IcdErrorKind::UnknownMessage
+            }
+        );
+        assert_eq!(
+            err("message a rt=32 rx sa=1 wc=2").kind,
+            IcdErrorKind::BadValue("rt")
+        );
+        assert_eq!(
+            err("message a rt=5 sa=1 wc=2 color=red").kind,
+            IcdErrorKind::UnknownKey
+        );
+        assert_eq!(
+            err("message a rt=5 rx sa=1 wc=2\nsignal a.x word=0 lsb=8 bits=9").kind,
+            IcdErrorKind::BadValue("bits")
+        );
+    }
+}
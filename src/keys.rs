@@ -0,0 +1,108 @@
+//! The `key=value` and bare tokens of the line-based text formats (ICD,
+//! scenario).
+use core::fmt;
+use core::str::FromStr;
+use std::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeyError {
+    UnknownKey,
+    Missing(&'static str),
+    BadValue(&'static str),
+}
+
+/// The tokens of a line after the entry name.
+pub(crate) struct Tokens<'a>(Vec<(&'a str, Option<&'a str>)>);
+
+impl<'a> Tokens<'a> {
+    pub(crate) fn new(
+        tokens: impl Iterator<Item = &'a str>,
+        keys: &[&str],
+    ) -> Result<Self, KeyError> {
+        let mut parsed = Vec::new();
+        for token in tokens {
+            let (key, value) = match token.split_once('=') {
+                Some((key, value)) => (key, Some(value)),
+                None => (token, None),
+            };
+            if !keys.contains(&key) {
+                return Err(KeyError::UnknownKey);
+            }
+            parsed.push((key, value));
+        }
+        Ok(Self(parsed))
+    }
+
+    pub(crate) fn flag(&self, key: &str) -> bool {
+        self.0.iter().any(|(k, v)| *k == key && v.is_none())
+    }
+
+    pub(crate) fn text(&self, key: &'static str) -> Option<&'a str> {
+        self.0.iter().find(|(k, _)| *k == key).and_then(|(_, v)| *v)
+    }
+
+    pub(crate) fn number<T: FromStr + PartialOrd>(
+        &self,
+        key: &'static str,
+        default: Option<T>,
+        max: T,
+    ) -> Result<T, KeyError> {
+        match self.text(key) {
+            None => default.ok_or(KeyError::Missing(key)),
+            Some(text) => text
+                .parse()
+                .ok()
+                .filter(|value| *value <= max)
+                .ok_or(KeyError::BadValue(key)),
+        }
+    }
+
+    /// A duration, see [`parse_ns`].
+    pub(crate) fn time_ns(&self, key: &'static str, default: Option<u64>) -> Result<u64, KeyError> {
+        match self.text(key) {
+            None => default.ok_or(KeyError::Missing(key)),
+            Some(text) => parse_ns(text).ok_or(KeyError::BadValue(key)),
+        }
+    }
+
+    /// Comma separated hex words, `None` when the key is absent.
+    pub(crate) fn hex_list(&self, key: &'static str) -> Result<Option<Vec<u16>>, KeyError> {
+        let Some(text) = self.text(key) else {
+            return Ok(None);
+        };
+        text.split(',')
+            .map(|word| u16::from_str_radix(word, 16))
+            .collect::<Result<_, _>>()
+            .map(Some)
+            .map_err(|_| KeyError::BadValue(key))
+    }
+}
+
+const UNITS: [(&str, u64); 4] = [
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("us", 1_000),
+    ("ns", 1),
+];
+
+/// A whole number of `s`, `ms`, `us` or `ns`, in ns.
+pub(crate) fn parse_ns(text: &str) -> Option<u64> {
+    let digits = text.find(|c: char| !c.is_ascii_digit())?;
+    let (value, unit) = text.split_at(digits);
+    let (_, scale) = UNITS.iter().find(|(name, _)| *name == unit)?;
+    value.parse::<u64>().ok()?.checked_mul(*scale)
+}
+
+/// Prints a duration in ns in the largest unit [`parse_ns`] reads it back
+/// exactly from.
+pub(crate) struct Nanos(pub(crate) u64);
+
+impl fmt::Display for Nanos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (unit, scale) = UNITS
+            .iter()
+            .find(|(_, scale)| self.0.is_multiple_of(*scale))
+            .unwrap_or(&UNITS[3]);
+        write!(f, "{}{}", self.0 / scale, unit)
+    }
+}
@@ -0,0 +1,263 @@
+use crate::words::*;
+
+/// Word count mask accepting every word count (1 to 32).
+pub const ANY_WORD_COUNT: u32 = u32::MAX;
+
+/// Bit of a word count mask for `count` data words (1 to 32).
+pub const fn word_count_bit(count: u8) -> u32 {
+    1 << (count % 32)
+}
+
+/// Whether a command was sent to the RT's own address or to everyone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Addressing {
+    Own,
+    Broadcast,
+}
+
+impl Addressing {
+    pub fn of(cmd: &CommandWord) -> Self {
+        match cmd.get_rt_addr() {
+            RTAddr::Broadcast => Addressing::Broadcast,
+            RTAddr::Single(_) => Addressing::Own,
+        }
+    }
+
+    const fn index(self) -> usize {
+        match self {
+            Addressing::Own => 0,
+            Addressing::Broadcast => 1,
+        }
+    }
+}
+
+const fn tr_index(tr: RTAction) -> usize {
+    match tr {
+        RTAction::Receive => 0,
+        RTAction::Transmit => 1,
+    }
+}
+
+/// The subaddress given to [`LegalityTable::set_subaddress`], which is not
+/// a data subaddress (1 to 30).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotDataSubaddress(pub u8);
+
+/// Which commands an RT accepts, per MIL-STD-1553B Notice 2 illegal command
+/// handling. Every (addressing, T/R, subaddress) entry holds a 32-bit mask
+/// over the word count field, so subaddresses 0 and 31 give the legality of
+/// each mode code.
+///
+/// An RT answers an illegal command with `MessageError` set and without
+/// transmitting (or storing) any data.
+///
+/// ```
+/// use milisse::legality::*;
+/// use milisse::words::*;
+///
+/// const ICD: LegalityTable = LegalityTable::ALL_ILLEGAL
+///     .with_subaddress(Addressing::Own, RTAction::Receive, 1, word_count_bit(4))
+///     .with_mode_code(Addressing::Own, ModeCode::TransmitStatusWord, true);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LegalityTable {
+    // [addressing][T/R][subaddress] -> legal word counts / mode codes.
+    masks: [[[u32; 32]; 2]; 2],
+}
+
+impl LegalityTable {
+    /// Rejects every command.
+    pub const ALL_ILLEGAL: Self = Self {
+        masks: [[[0; 32]; 2]; 2],
+    };
+
+    /// Every data subaddress with any word count, except broadcast
+    /// transmit commands. Every defined mode code with its own T/R bit, and
+    /// in broadcast only when the standard allows it.
+    pub const fn standard() -> Self {
+        let mut table = Self::ALL_ILLEGAL;
+        let mut sa = 1;
+        while sa < 31 {
+            table = table
+                .with_subaddress(Addressing::Own, RTAction::Receive, sa, ANY_WORD_COUNT)
+                .with_subaddress(Addressing::Own, RTAction::Transmit, sa, ANY_WORD_COUNT)
+                .with_subaddress(Addressing::Broadcast, RTAction::Receive, sa, ANY_WORD_COUNT);
+            sa += 1;
+        }
+        let mut idx = 0;
+        while idx < ModeCode::ALL.len() {
+            let code = ModeCode::ALL[idx];
+            table = table
+                .with_mode_code(Addressing::Own, code, true)
+                .with_mode_code(
+                    Addressing::Broadcast,
+                    code,
+                    code.associated_options().broadcast_allowed,
+                );
+            idx += 1;
+        }
+        table
+    }
+
+    /// Set the legal word counts of a data subaddress (1 to 30). See
+    /// [`word_count_bit`] and [`ANY_WORD_COUNT`].
+    pub const fn with_subaddress(
+        mut self,
+        addressing: Addressing,
+        tr: RTAction,
+        subaddress: u8,
+        word_counts: u32,
+    ) -> Self {
+        assert!(subaddress > 0 && subaddress < 31, "Not a data subaddress");
+        self.masks[addressing.index()][tr_index(tr)][subaddress as usize] = word_counts;
+        self
+    }
+
+    /// Mark a mode code legal or illegal, through both mode code
+    /// subaddresses. Only the T/R bit the mode code defines is affected, the
+    /// other one is always illegal. [`ModeCode::Invalid`] leaves the table
+    /// as it is.
+    pub const fn with_mode_code(
+        mut self,
+        addressing: Addressing,
+        code: ModeCode,
+        legal: bool,
+    ) -> Self {
+        if matches!(code, ModeCode::Invalid) {
+            return self;
+        }
+        let tr = tr_index(code.associated_options().tr);
+        let bit = 1 << code.code();
+        let a = addressing.index();
+        if legal {
+            self.masks[a][tr][0] |= bit;
+            self.masks[a][tr][31] |= bit;
+        } else {
+            self.masks[a][tr][0] &= !bit;
+            self.masks[a][tr][31] &= !bit;
+        }
+        self
+    }
+
+    /// Like [`with_subaddress`](Self::with_subaddress), but refuses a
+    /// subaddress outside 1 to 30 instead of panicking.
+    pub fn set_subaddress(
+        &mut self,
+        addressing: Addressing,
+        tr: RTAction,
+        subaddress: u8,
+        word_counts: u32,
+    ) -> Result<(), NotDataSubaddress> {
+        if !(1..31).contains(&subaddress) {
+            return Err(NotDataSubaddress(subaddress));
+        }
+        *self = self.with_subaddress(addressing, tr, subaddress, word_counts);
+        Ok(())
+    }
+
+    pub fn set_mode_code(&mut self, addressing: Addressing, code: ModeCode, legal: bool) {
+        *self = self.with_mode_code(addressing, code, legal);
+    }
+
+    /// Never true for [`ModeCode::Invalid`].
+    pub fn is_mode_code_legal(&self, addressing: Addressing, code: ModeCode) -> bool {
+        if code == ModeCode::Invalid {
+            return false;
+        }
+        let tr = tr_index(code.associated_options().tr);
+        self.masks[addressing.index()][tr][31] & (1 << code.code()) != 0
+    }
+
+    pub fn is_legal(&self, cmd: &CommandWord) -> bool {
+        let subaddress = ((cmd.value() >> 5) & 0b11111) as usize;
+        let field = cmd.value() & 0b11111;
+        let mask = self.masks[Addressing::of(cmd).index()][tr_index(cmd.get_tr_bit())][subaddress];
+        mask & (1 << field) != 0
+    }
+}
+
+impl Default for LegalityTable {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::legality::*;
+
+    const ICD: LegalityTable = LegalityTable::ALL_ILLEGAL
+        .with_subaddress(
+            Addressing::Own,
+            RTAction::Receive,
+            1,
+            word_count_bit(4) | word_count_bit(32),
+        )
+        .with_mode_code(Addressing::Own, ModeCode::TransmitStatusWord, true);
+
+    #[test]
+    fn const_table_word_counts() {
+        let addr = RTAddr::Single(3.into());
+        let cmd =
+            |wc: u8| CommandWord::new_data_transfer(addr, RTAction::Receive, 1.into(), wc.into());
+        assert!(ICD.is_legal(&cmd(4)));
+        assert!(ICD.is_legal(&cmd(0)));
+        assert!(!ICD.is_legal(&cmd(5)));
+        let tx = CommandWord::new_data_transfer(addr, RTAction::Transmit, 1.into(), 4.into());
+        assert!(!ICD.is_legal(&tx));
+    }
+
+    #[test]
+    fn runtime_subaddress_must_carry_data() {
+        let mut table = ICD;
+        for sa in [0, 31, 32, 255] {
+            let result = table.set_subaddress(Addressing::Own, RTAction::Receive, sa, 0);
+            assert_eq!(result, Err(NotDataSubaddress(sa)));
+        }
+        assert_eq!(table, ICD);
+        assert_eq!(
+            table.set_subaddress(Addressing::Own, RTAction::Receive, 1, 0),
+            Ok(())
+        );
+        assert_eq!(
+            table,
+            ICD.with_subaddress(Addressing::Own, RTAction::Receive, 1, 0)
+        );
+    }
+
+    #[test]
+    fn mode_codes_need_their_tr_bit() {
+        let mut cmd =
+            CommandWord::new_mode_command(RTAddr::Single(3.into()), ModeCode::TransmitStatusWord);
+        assert!(ICD.is_legal(&cmd));
+        cmd = CommandWord::from_u16(cmd.value() & !(1 << 10));
+        assert!(!ICD.is_legal(&cmd));
+        assert!(!ICD.is_mode_code_legal(Addressing::Own, ModeCode::ResetRT));
+
+        // Reserved codes come out of ModeCode::from as Invalid.
+        let invalid = ModeCode::from(9);
+        assert_eq!(invalid, ModeCode::Invalid);
+        assert!(!LegalityTable::standard().is_mode_code_legal(Addressing::Own, invalid));
+        assert_eq!(ICD.with_mode_code(Addressing::Own, invalid, true), ICD);
+    }
+
+    #[test]
+    fn standard_table_follows_broadcast_rules() {
+        let table = LegalityTable::standard();
+        for code in ModeCode::ALL {
+            assert!(table.is_mode_code_legal(Addressing::Own, code));
+            assert_eq!(
+                table.is_mode_code_legal(Addressing::Broadcast, code),
+                code.associated_options().broadcast_allowed
+            );
+        }
+        let bcast_tx = CommandWord::new_data_transfer(
+            RTAddr::Broadcast,
+            RTAction::Transmit,
+            2.into(),
+            1.into(),
+        );
+        assert!(!table.is_legal(&bcast_tx));
+        assert!(!table.is_legal(&CommandWord::from_u16(0b0001111111111111)));
+    }
+}
@@ -1,5 +1,41 @@
-//#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+pub mod buffers;
 pub mod bus;
+pub mod ch10;
+#[cfg(feature = "std")]
+pub mod channel;
+pub mod classify;
+pub mod datagram;
+pub mod disasm;
+pub mod edge;
+pub mod fault;
+pub mod frame;
+#[cfg(feature = "std")]
+pub mod icd;
+#[cfg(feature = "std")]
+mod keys;
+pub mod legality;
+pub mod manchester;
+pub mod message;
+#[cfg(feature = "std")]
+pub mod pcapng;
 pub mod primitives;
+pub mod program;
+mod queue;
+pub mod rt;
+#[cfg(feature = "std")]
+pub mod scenario;
+#[cfg(feature = "std")]
+pub mod schedule;
+pub mod sim;
+#[cfg(feature = "std")]
+pub mod tabular;
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod udp;
+#[cfg(all(feature = "std", unix))]
+pub mod unix;
+pub mod vcd;
+pub mod waveform;
 pub mod words;
@@ -0,0 +1,196 @@
+//! Manchester II bi-phase encoding of words, as they go on the wire.
+//!
+//! A word takes 20 bit times of 1 µs: a 3 µs sync, 16 data bits MSB first
+//! and an odd parity bit. The sync is 1.5 µs positive then 1.5 µs negative
+//! for command and status words, the other way around for data words. A
+//! `1` bit is positive then negative, a `0` negative then positive.
+//!
+//! Words are handled here as their 40 half-bit levels, `true` being
+//! positive.
+use crate::{bus::BusError, words::*};
+
+pub const BIT_NS: u32 = 1_000;
+pub const HALF_BIT_NS: u32 = BIT_NS / 2;
+pub const WORD_BITS: usize = 20;
+pub const WORD_NS: u32 = WORD_BITS as u32 * BIT_NS;
+pub const HALF_BITS: usize = 2 * WORD_BITS;
+/// Half-bits taken by the sync.
+pub const SYNC_HALF_BITS: usize = 6;
+
+/// The two sync waveforms. Command and status words share theirs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sync {
+    CommandStatus,
+    Data,
+}
+
+impl Sync {
+    pub fn of(word: &Word) -> Self {
+        match word {
+            Word::Command(_) | Word::Status(_) => Sync::CommandStatus,
+            Word::Data(_) => Sync::Data,
+        }
+    }
+
+    /// Level of the first half of the sync.
+    fn first_level(self) -> bool {
+        self == Sync::CommandStatus
+    }
+
+    pub(crate) fn from_first_level(level: bool) -> Self {
+        if level {
+            Sync::CommandStatus
+        } else {
+            Sync::Data
+        }
+    }
+}
+
+/// Why a word was not received cleanly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordError {
+    /// A bit without a mid-bit transition.
+    Manchester,
+    Parity,
+    /// The bus went idle before the end of the word.
+    Truncated,
+}
+
+impl From<WordError> for BusError {
+    fn from(_: WordError) -> Self {
+        BusError::Parity
+    }
+}
+
+/// A word as received: its sync and 16 bits, which can't tell a command
+/// from a status word by themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawWord {
+    pub sync: Sync,
+    pub value: u16,
+    pub error: Option<WordError>,
+}
+
+impl RawWord {
+    pub fn from_word(word: Word) -> Self {
+        let value = match word {
+            Word::Command(cmd) => cmd.value(),
+            Word::Status(sw) => sw.value(),
+            Word::Data(dw) => dw.value(),
+        };
+        Self {
+            sync: Sync::of(&word),
+            value,
+            error: None,
+        }
+    }
+
+    /// The word, reading a command/status sync as a status word when
+    /// `status` is set.
+    pub fn to_word(&self, status: bool) -> Result<Word, BusError> {
+        if let Some(err) = self.error {
+            return Err(err.into());
+        }
+        Ok(match (self.sync, status) {
+            (Sync::Data, _) => Word::Data(DataWord::from_u16(self.value)),
+            (Sync::CommandStatus, false) => Word::Command(CommandWord::from_u16(self.value)),
+            (Sync::CommandStatus, true) => Word::Status(StatusWord::from_u16(self.value)),
+        })
+    }
+}
+
+/// A word found on the bus, with the time its sync started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodedWord {
+    pub time_ns: u64,
+    pub word: RawWord,
+}
+
+/// The parity bit sent after `value`: set when `value` has an even number
+/// of ones, so the total is odd.
+pub const fn parity_bit(value: u16) -> bool {
+    value.count_ones().is_multiple_of(2)
+}
+
+/// Half-bit levels of a word with the given sync and bits.
+pub fn encode(sync: Sync, value: u16) -> [bool; HALF_BITS] {
+    let mut levels = [false; HALF_BITS];
+    let first = sync.first_level();
+    levels[..3].fill(first);
+    levels[3..SYNC_HALF_BITS].fill(!first);
+    for bit in 0..17 {
+        let one = match bit {
+            16 => parity_bit(value),
+            _ => value & (0x8000 >> bit) != 0,
+        };
+        levels[SYNC_HALF_BITS + 2 * bit] = one;
+        levels[SYNC_HALF_BITS + 2 * bit + 1] = !one;
+    }
+    levels
+}
+
+pub fn encode_word(word: Word) -> [bool; HALF_BITS] {
+    let raw = RawWord::from_word(word);
+    encode(raw.sync, raw.value)
+}
+
+/// Decode half-bit levels, or `None` when they don't start with a sync.
+/// A bit without transition is read as its first half.
+pub fn decode(levels: &[bool; HALF_BITS]) -> Option<RawWord> {
+    let sync = match levels[..SYNC_HALF_BITS] {
+        [true, true, true, false, false, false] => Sync::CommandStatus,
+        [false, false, false, true, true, true] => Sync::Data,
+        _ => return None,
+    };
+    let mut value = 0u16;
+    let mut error = None;
+    for bit in 0..17 {
+        let first = levels[SYNC_HALF_BITS + 2 * bit];
+        if first == levels[SYNC_HALF_BITS + 2 * bit + 1] {
+            error = Some(WordError::Manchester);
+        }
+        match bit {
+            16 if error.is_none() && first != parity_bit(value) => error = Some(WordError::Parity),
+            16 => {}
+            _ => value = value << 1 | first as u16,
+        }
+    }
+    Some(RawWord { sync, value, error })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manchester::*;
+
+    #[test]
+    fn encodes_sync_bits_and_parity() {
+        let levels = encode(Sync::Data, 0x8001);
+        assert_eq!(levels[..6], [false, false, false, true, true, true]);
+        // MSB first: 1 is high then low, 0 low then high.
+        assert_eq!(levels[6..10], [true, false, false, true]);
+        assert_eq!(levels[36..38], [true, false]);
+        // Two ones: the parity bit makes it odd.
+        assert_eq!(levels[38..], [true, false]);
+        assert!(!parity_bit(0x0001));
+    }
+
+    #[test]
+    fn decodes_and_classifies_errors() {
+        let word = Word::Status(StatusWord::from_u16(0x2C10));
+        let mut levels = encode_word(word);
+        let raw = decode(&levels).unwrap();
+        assert_eq!(raw.sync, Sync::CommandStatus);
+        assert_eq!(raw.to_word(true), Ok(word));
+        assert!(matches!(raw.to_word(false), Ok(Word::Command(_))));
+
+        levels[38] = !levels[38];
+        levels[39] = !levels[39];
+        assert_eq!(decode(&levels).unwrap().error, Some(WordError::Parity));
+        levels[7] = levels[6];
+        let raw = decode(&levels).unwrap();
+        assert_eq!(raw.error, Some(WordError::Manchester));
+        assert_eq!(raw.to_word(true), Err(BusError::Parity));
+        levels[0] = false;
+        assert_eq!(decode(&levels), None);
+    }
+}
@@ -0,0 +1,836 @@
+//! Whole messages: the commands, data and status words of one transfer,
+//! kept together.
+//!
+//! A [`Message`] groups the words of a transfer under its
+//! [`MessageFormat`], with where and when it went and what went wrong. The
+//! BC runs them with [`BusController::transact`](crate::bus::BusController::transact),
+//! and an [`Assembler`] rebuilds them from the words a monitor, a trace or a
+//! capture file saw.
+use crate::{
+    buffers::MAX_DATA_WORDS,
+    bus::{BusError, BusId},
+    manchester::WORD_NS,
+    words::{CommandWord, DataWord, StatusWord, Word, BROADCAST_ADDR},
+};
+
+/// The ten message formats of MIL-STD-1553B.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    BcToRt,
+    RtToBc,
+    RtToRt,
+    ModeWithoutData,
+    ModeWithDataTransmit,
+    ModeWithDataReceive,
+    BroadcastBcToRt,
+    BroadcastRtToRt,
+    BroadcastModeWithoutData,
+    BroadcastModeWithData,
+}
+
+/// A slot of a message, in bus order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Part {
+    Command(usize),
+    Data,
+    Status(usize),
+}
+
+impl MessageFormat {
+    /// Every format, in declaration order.
+    pub const ALL: [MessageFormat; 10] = [
+        MessageFormat::BcToRt,
+        MessageFormat::RtToBc,
+        MessageFormat::RtToRt,
+        MessageFormat::ModeWithoutData,
+        MessageFormat::ModeWithDataTransmit,
+        MessageFormat::ModeWithDataReceive,
+        MessageFormat::BroadcastBcToRt,
+        MessageFormat::BroadcastRtToRt,
+        MessageFormat::BroadcastModeWithoutData,
+        MessageFormat::BroadcastModeWithData,
+    ];
+
+    /// The format `command` starts. A receive command is taken for BC to
+    /// RT: only the next word tells whether a transmit command follows,
+    /// making it RT to RT.
+    pub fn of(command: &CommandWord) -> Self {
+        let fields = Fields::of(command);
+        match (fields.mode, fields.broadcast(), fields.transmit) {
+            (true, false, _) if !fields.with_data() => MessageFormat::ModeWithoutData,
+            (true, false, true) => MessageFormat::ModeWithDataTransmit,
+            (true, false, false) => MessageFormat::ModeWithDataReceive,
+            // Nobody answers a broadcast, so no data comes back either.
+            (true, true, false) if fields.with_data() => MessageFormat::BroadcastModeWithData,
+            (true, true, _) => MessageFormat::BroadcastModeWithoutData,
+            (false, _, true) => MessageFormat::RtToBc,
+            (false, false, false) => MessageFormat::BcToRt,
+            (false, true, false) => MessageFormat::BroadcastBcToRt,
+        }
+    }
+
+    /// The format once the transmit command of an RT to RT transfer
+    /// follows, for the formats that can take one.
+    pub fn with_transmit_command(self) -> Option<Self> {
+        match self {
+            MessageFormat::BcToRt => Some(MessageFormat::RtToRt),
+            MessageFormat::BroadcastBcToRt => Some(MessageFormat::BroadcastRtToRt),
+            _ => None,
+        }
+    }
+
+    pub fn is_broadcast(self) -> bool {
+        matches!(
+            self,
+            MessageFormat::BroadcastBcToRt
+                | MessageFormat::BroadcastRtToRt
+                | MessageFormat::BroadcastModeWithoutData
+                | MessageFormat::BroadcastModeWithData
+        )
+    }
+
+    pub fn is_mode_command(self) -> bool {
+        matches!(
+            self,
+            MessageFormat::ModeWithoutData
+                | MessageFormat::ModeWithDataTransmit
+                | MessageFormat::ModeWithDataReceive
+                | MessageFormat::BroadcastModeWithoutData
+                | MessageFormat::BroadcastModeWithData
+        )
+    }
+
+    pub fn is_rt_to_rt(self) -> bool {
+        matches!(self, MessageFormat::RtToRt | MessageFormat::BroadcastRtToRt)
+    }
+
+    /// Command words the BC sends: two for RT to RT, one otherwise.
+    pub fn commands(self) -> usize {
+        match self.is_rt_to_rt() {
+            true => 2,
+            false => 1,
+        }
+    }
+
+    /// Status words coming back in a message without errors.
+    pub fn status_words(self) -> usize {
+        self.parts()
+            .iter()
+            .filter(|part| matches!(part, Part::Status(_)))
+            .count()
+    }
+
+    /// Whether an RT, rather than the BC, sends the data words.
+    pub fn rt_sends_data(self) -> bool {
+        matches!(
+            self,
+            MessageFormat::RtToBc
+                | MessageFormat::RtToRt
+                | MessageFormat::ModeWithDataTransmit
+                | MessageFormat::BroadcastRtToRt
+        )
+    }
+
+    pub(crate) fn parts(self) -> &'static [Part] {
+        use Part::*;
+        match self {
+            MessageFormat::BcToRt => &[Command(0), Data, Status(0)],
+            MessageFormat::RtToBc => &[Command(0), Status(0), Data],
+            MessageFormat::RtToRt => &[Command(0), Command(1), Status(0), Data, Status(1)],
+            MessageFormat::ModeWithoutData => &[Command(0), Status(0)],
+            MessageFormat::ModeWithDataTransmit => &[Command(0), Status(0), Data],
+            MessageFormat::ModeWithDataReceive => &[Command(0), Data, Status(0)],
+            MessageFormat::BroadcastBcToRt => &[Command(0), Data],
+            MessageFormat::BroadcastRtToRt => &[Command(0), Command(1), Status(0), Data],
+            MessageFormat::BroadcastModeWithoutData => &[Command(0)],
+            MessageFormat::BroadcastModeWithData => &[Command(0), Data],
+        }
+    }
+
+    /// Which command the status word `idx` answers: in an RT to RT
+    /// transfer the transmitting RT answers first.
+    pub(crate) fn responder(self, idx: usize) -> usize {
+        match (self.is_rt_to_rt(), idx) {
+            (true, 0) => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Fields of a command, read from the raw bits.
+pub(crate) struct Fields {
+    pub(crate) addr: u8,
+    pub(crate) transmit: bool,
+    pub(crate) subaddress: u8,
+    pub(crate) mode: bool,
+    pub(crate) count: u8,
+}
+
+impl Fields {
+    pub(crate) fn of(cmd: &CommandWord) -> Self {
+        let value = cmd.value();
+        let subaddress = (value >> 5) & 0x1F;
+        Fields {
+            addr: (value >> 11) as u8,
+            transmit: value & 0x0400 != 0,
+            subaddress: subaddress as u8,
+            mode: subaddress == 0 || subaddress == 0x1F,
+            count: (value & 0x1F) as u8,
+        }
+    }
+
+    pub(crate) fn broadcast(&self) -> bool {
+        self.addr == BROADCAST_ADDR
+    }
+
+    /// Mode codes 16 to 31 carry a data word.
+    fn with_data(&self) -> bool {
+        self.count & 0x10 != 0
+    }
+
+    /// Data words the command asks for.
+    pub(crate) fn data_words(&self) -> u8 {
+        match (self.mode, self.count) {
+            (true, _) => self.with_data() as u8,
+            (false, 0) => 32,
+            (false, count) => count,
+        }
+    }
+}
+
+/// What went wrong with a message. All clear for a good one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MessageErrors {
+    /// An RT did not answer.
+    pub no_response: bool,
+    /// A word arrived with a parity or Manchester error.
+    pub word_error: bool,
+    /// A status word came with another RT's address.
+    pub wrong_address: bool,
+    /// More or fewer data words than the command asked for.
+    pub word_count: bool,
+    /// A word of the wrong kind where another was due.
+    pub sequence: bool,
+}
+
+impl MessageErrors {
+    pub fn any(&self) -> bool {
+        self.no_response
+            || self.word_error
+            || self.wrong_address
+            || self.word_count
+            || self.sequence
+    }
+}
+
+/// Why a message does not hang together. Shortfalls its
+/// [`MessageErrors`] account for are not inconsistent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsistencyError {
+    /// The commands do not make up a message of its format.
+    BadCommand,
+    DataCount {
+        expected: usize,
+        actual: usize,
+    },
+    StatusCount {
+        expected: usize,
+        actual: usize,
+    },
+    /// A status word from another RT than the one commanded.
+    StatusAddress(StatusWord),
+    EndBeforeStart,
+}
+
+/// One message on one bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Message {
+    bus: BusId,
+    format: MessageFormat,
+    commands: [CommandWord; 2],
+    data: [DataWord; MAX_DATA_WORDS],
+    data_len: usize,
+    status: [StatusWord; 2],
+    status_len: usize,
+    start_ns: u64,
+    end_ns: u64,
+    errors: MessageErrors,
+}
+
+impl Message {
+    /// A message started by `command`. See [`Message::rt_to_rt`] for RT to
+    /// RT transfers.
+    pub fn new(bus: BusId, command: CommandWord) -> Self {
+        Self {
+            bus,
+            format: MessageFormat::of(&command),
+            commands: [command; 2],
+            data: [DataWord::from_u16(0); MAX_DATA_WORDS],
+            data_len: 0,
+            status: [StatusWord::from_u16(0); 2],
+            status_len: 0,
+            start_ns: 0,
+            end_ns: 0,
+            errors: MessageErrors::default(),
+        }
+    }
+
+    pub fn rt_to_rt(bus: BusId, receive: CommandWord, transmit: CommandWord) -> Self {
+        let mut message = Self::new(bus, receive);
+        message.format = match Fields::of(&receive).broadcast() {
+            true => MessageFormat::BroadcastRtToRt,
+            false => MessageFormat::RtToRt,
+        };
+        message.commands[1] = transmit;
+        message
+    }
+
+    /// The same commands, with the data only if the BC sends it: what the
+    /// BC puts on the bus for this message.
+    pub fn request(&self) -> Self {
+        let mut request = Self::new(self.bus, self.commands[0]);
+        request.format = self.format;
+        request.commands = self.commands;
+        if !self.format.rt_sends_data() {
+            request.data = self.data;
+            request.data_len = self.data_len;
+        }
+        request
+    }
+
+    pub fn bus(&self) -> BusId {
+        self.bus
+    }
+
+    pub fn set_bus(&mut self, bus: BusId) {
+        self.bus = bus;
+    }
+
+    pub fn format(&self) -> MessageFormat {
+        self.format
+    }
+
+    /// The first command; the receive command of an RT to RT transfer.
+    pub fn command(&self) -> CommandWord {
+        self.commands[0]
+    }
+
+    /// The transmit command of an RT to RT transfer.
+    pub fn transmit_command(&self) -> Option<CommandWord> {
+        self.format.is_rt_to_rt().then_some(self.commands[1])
+    }
+
+    pub fn commands(&self) -> &[CommandWord] {
+        &self.commands[..self.format.commands()]
+    }
+
+    pub fn data(&self) -> &[DataWord] {
+        &self.data[..self.data_len]
+    }
+
+    /// Status words in the order they came: the transmitting RT's first in
+    /// an RT to RT transfer.
+    pub fn status_words(&self) -> &[StatusWord] {
+        &self.status[..self.status_len]
+    }
+
+    /// Add a data word. Gives it back when the message already has 32.
+    pub fn push_data(&mut self, word: DataWord) -> Result<(), DataWord> {
+        if self.data_len == MAX_DATA_WORDS {
+            return Err(word);
+        }
+        self.data[self.data_len] = word;
+        self.data_len += 1;
+        Ok(())
+    }
+
+    /// Add a status word. Gives it back when the message already has two.
+    pub fn push_status(&mut self, word: StatusWord) -> Result<(), StatusWord> {
+        if self.status_len == self.status.len() {
+            return Err(word);
+        }
+        self.status[self.status_len] = word;
+        self.status_len += 1;
+        Ok(())
+    }
+
+    /// When the first command started, in ns.
+    pub fn start_ns(&self) -> u64 {
+        self.start_ns
+    }
+
+    /// When the last word ended, in ns.
+    pub fn end_ns(&self) -> u64 {
+        self.end_ns
+    }
+
+    pub fn set_time(&mut self, start_ns: u64, end_ns: u64) {
+        self.start_ns = start_ns;
+        self.end_ns = end_ns;
+    }
+
+    pub fn errors(&self) -> MessageErrors {
+        self.errors
+    }
+
+    pub fn errors_mut(&mut self) -> &mut MessageErrors {
+        &mut self.errors
+    }
+
+    /// Data words the commands ask for. In an RT to RT transfer, those of
+    /// the transmit command, which the transmitting RT goes by.
+    pub fn expected_data_words(&self) -> usize {
+        let last = self.commands()[self.format.commands() - 1];
+        Fields::of(&last).data_words() as usize
+    }
+
+    /// The command the status word `idx` answers.
+    pub fn responder(&self, idx: usize) -> CommandWord {
+        self.commands[self.format.responder(idx)]
+    }
+
+    /// The RT due to send data answered with message error or busy set,
+    /// and so sends none.
+    pub fn transmitter_declined(&self) -> bool {
+        let parts = self.format.parts();
+        let Some(data) = parts.iter().position(|part| *part == Part::Data) else {
+            return false;
+        };
+        match parts[..data].last() {
+            Some(Part::Status(idx)) => self.status_words().get(*idx).is_some_and(|sw| {
+                let flags = sw.flags();
+                flags.message_error || flags.busy
+            }),
+            _ => false,
+        }
+    }
+
+    /// All the words of the message, in the order they go on the bus.
+    pub fn words(&self) -> Words<'_> {
+        Words {
+            message: self,
+            part: 0,
+            idx: 0,
+        }
+    }
+
+    /// Check that the words fit the format and each other, given the
+    /// errors recorded.
+    pub fn validate(&self) -> Result<(), ConsistencyError> {
+        let format = MessageFormat::of(&self.commands[0]);
+        let format = match self.format.is_rt_to_rt() {
+            true => format.with_transmit_command(),
+            false => Some(format),
+        };
+        if format != Some(self.format) {
+            return Err(ConsistencyError::BadCommand);
+        }
+        if let Some(transmit) = self.transmit_command() {
+            let tx = Fields::of(&transmit);
+            let counts_differ = tx.count != Fields::of(&self.commands[0]).count;
+            if !tx.transmit
+                || tx.mode
+                || tx.broadcast()
+                || (counts_differ && !self.errors.word_count)
+            {
+                return Err(ConsistencyError::BadCommand);
+            }
+        }
+        if self.end_ns < self.start_ns {
+            return Err(ConsistencyError::EndBeforeStart);
+        }
+
+        // The errors that end a BC transfer early: a missing or garbled
+        // word, or a word of the wrong kind where a status word was due.
+        let status_short = self.status_len < self.format.status_words();
+        let cut_at_status = self.errors.no_response
+            || self.errors.word_error
+            || (self.errors.sequence && status_short);
+        let expected = self.expected_data_words();
+        let actual = self.data_len;
+        let excused = cut_at_status || self.errors.word_count || self.transmitter_declined();
+        if actual != expected && !excused {
+            return Err(ConsistencyError::DataCount { expected, actual });
+        }
+
+        let expected = self.format.status_words();
+        let actual = self.status_len;
+        if actual > expected || (status_short && !cut_at_status) {
+            return Err(ConsistencyError::StatusCount { expected, actual });
+        }
+        for (idx, sw) in self.status_words().iter().enumerate() {
+            if sw.get_rt_addr() != self.responder(idx).get_rt_addr() && !self.errors.wrong_address {
+                return Err(ConsistencyError::StatusAddress(*sw));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every status and data word due has come.
+    fn is_complete(&self) -> bool {
+        self.status_len == self.format.status_words()
+            && (self.data_len >= self.expected_data_words() || self.transmitter_declined())
+    }
+
+    /// Rebuild a message from its words in bus order, without their sync
+    /// types, as recorders store them: the format tells which is which.
+    /// `rt_to_rt` says whether the second word is a transmit command.
+    /// Missing words are flagged as by [`Assembler::finish`], extra ones as
+    /// a sequence error. `None` if `words` is empty.
+    pub fn from_raw_words(bus: BusId, rt_to_rt: bool, words: &[u16]) -> Option<Self> {
+        let (&first, mut rest) = words.split_first()?;
+        let first = CommandWord::from_u16(first);
+        let mut message = match (rt_to_rt, rest.split_first()) {
+            (true, Some((&second, tail))) => {
+                rest = tail;
+                Self::rt_to_rt(bus, first, CommandWord::from_u16(second))
+            }
+            _ => Self::new(bus, first),
+        };
+        for part in message.format.parts() {
+            match *part {
+                Part::Command(_) => {}
+                Part::Data => {
+                    let count = match message.transmitter_declined() {
+                        true => 0,
+                        false => message.expected_data_words().min(rest.len()),
+                    };
+                    for value in &rest[..count] {
+                        let _ = message.push_data(DataWord::from_u16(*value));
+                    }
+                    rest = &rest[count..];
+                }
+                Part::Status(idx) => {
+                    let Some((&value, tail)) = rest.split_first() else {
+                        continue;
+                    };
+                    let sw = StatusWord::from_u16(value);
+                    if sw.get_rt_addr() != message.responder(idx).get_rt_addr() {
+                        message.errors.wrong_address = true;
+                    }
+                    let _ = message.push_status(sw);
+                    rest = tail;
+                }
+            }
+        }
+        message.errors.sequence |= !rest.is_empty();
+        message.close();
+        Some(message)
+    }
+
+    /// Flag what is missing from a message that ended early.
+    fn close(&mut self) {
+        if self.is_complete() {
+            return;
+        }
+        let short_data = self.data_len < self.expected_data_words() && !self.transmitter_declined();
+        let status_due = self.status_len < self.format.status_words();
+        // RTs do not answer a message whose data fell short.
+        if short_data && (!self.format.rt_sends_data() || !status_due) {
+            self.errors.word_count = true;
+        }
+        if status_due {
+            self.errors.no_response = true;
+        }
+    }
+}
+
+/// Iterator over the words of a [`Message`].
+pub struct Words<'a> {
+    message: &'a Message,
+    part: usize,
+    idx: usize,
+}
+
+impl Iterator for Words<'_> {
+    type Item = Word;
+
+    fn next(&mut self) -> Option<Word> {
+        let message = self.message;
+        while let Some(part) = message.format.parts().get(self.part) {
+            let word = match *part {
+                Part::Command(idx) if self.idx == 0 => Some(Word::Command(message.commands[idx])),
+                Part::Status(idx) if self.idx == 0 => {
+                    message.status_words().get(idx).map(|sw| Word::Status(*sw))
+                }
+                Part::Data => message.data().get(self.idx).map(|dw| Word::Data(*dw)),
+                _ => None,
+            };
+            match word {
+                Some(word) => {
+                    self.idx += 1;
+                    return Some(word);
+                }
+                None => {
+                    self.part += 1;
+                    self.idx = 0;
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Groups the words seen on one bus into messages.
+///
+/// Words must come classified, as [`crate::classify::Classifier`] does for
+/// raw captures, and in time order. [`crate::classify::Monitor`] chains
+/// the two, and [`RemoteTerminal`](crate::rt::RemoteTerminal) logs its
+/// messages with one.
+#[derive(Debug, Clone, Copy)]
+pub struct Assembler {
+    bus: BusId,
+    current: Option<Message>,
+    stray: u32,
+}
+
+impl Assembler {
+    pub const fn new(bus: BusId) -> Self {
+        Self {
+            bus,
+            current: None,
+            stray: 0,
+        }
+    }
+
+    pub fn bus(&self) -> BusId {
+        self.bus
+    }
+
+    /// Data and status words that belonged to no message.
+    pub fn stray_words(&self) -> u32 {
+        self.stray
+    }
+
+    /// Take the next word, or why it could not be read, starting at
+    /// `time_ns`. Returns the message it completes or ends, if any.
+    pub fn push(&mut self, time_ns: u64, word: Result<Word, BusError>) -> Option<Message> {
+        let end_ns = time_ns.saturating_add(WORD_NS as u64);
+        let word = match word {
+            Ok(word) => word,
+            Err(err) => {
+                let message = self.current.as_mut()?;
+                if err == BusError::NoResponse {
+                    message.errors.no_response = true;
+                    return self.finish();
+                }
+                message.errors.word_error = true;
+                message.end_ns = end_ns;
+                return None;
+            }
+        };
+        if let Word::Command(cmd) = word {
+            if self.second_command(cmd, end_ns) {
+                return None;
+            }
+            let ended = self.finish();
+            let mut message = Message::new(self.bus, cmd);
+            message.set_time(time_ns, end_ns);
+            self.current = Some(message);
+            // A message complete with its command waits for the next push
+            // if this one already ended another.
+            return match ended {
+                None if message.is_complete() => self.current.take(),
+                ended => ended,
+            };
+        }
+
+        let Some(message) = &mut self.current else {
+            self.stray += 1;
+            return None;
+        };
+        if message.is_complete() {
+            self.stray += 1;
+            return self.current.take();
+        }
+        match word {
+            Word::Data(dw) if message.data_len < message.expected_data_words() => {
+                let _ = message.push_data(dw);
+            }
+            Word::Status(sw) if message.status_len < message.format.status_words() => {
+                if sw.get_rt_addr() != message.responder(message.status_len).get_rt_addr() {
+                    message.errors.wrong_address = true;
+                }
+                let _ = message.push_status(sw);
+            }
+            _ => {
+                message.errors.sequence = true;
+                self.stray += 1;
+            }
+        }
+        message.end_ns = end_ns;
+        match message.is_complete() {
+            true => self.current.take(),
+            false => None,
+        }
+    }
+
+    /// End the message in progress as it is, e.g. at the end of a
+    /// capture.
+    pub fn finish(&mut self) -> Option<Message> {
+        let mut message = self.current.take()?;
+        message.close();
+        Some(message)
+    }
+
+    /// Take `cmd` as the transmit command of the RT to RT transfer in
+    /// progress, if it can be one.
+    fn second_command(&mut self, cmd: CommandWord, end_ns: u64) -> bool {
+        let Some(message) = &mut self.current else {
+            return false;
+        };
+        let Some(format) = message.format.with_transmit_command() else {
+            return false;
+        };
+        let tx = Fields::of(&cmd);
+        if message.data_len > 0 || !tx.transmit || tx.mode || tx.broadcast() {
+            return false;
+        }
+        message.format = format;
+        message.commands[1] = cmd;
+        message.end_ns = end_ns;
+        if tx.count != Fields::of(&message.commands[0]).count {
+            message.errors.word_count = true;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{bus::*, message::*, words::*};
+
+    fn cmd(value: u16) -> Word {
+        Word::Command(CommandWord::from_u16(value))
+    }
+
+    fn status(value: u16) -> Word {
+        Word::Status(StatusWord::from_u16(value))
+    }
+
+    fn data(value: u16) -> Word {
+        Word::Data(DataWord::from_u16(value))
+    }
+
+    #[test]
+    fn formats_of_commands() {
+        use MessageFormat::*;
+        let of = |value| MessageFormat::of(&CommandWord::from_u16(value));
+        // RT 5, subaddress 1.
+        assert_eq!(of(0x2822), BcToRt);
+        assert_eq!(of(0x2C22), RtToBc);
+        assert_eq!(of(0xF822), BroadcastBcToRt);
+        // Synchronize, transmit vector word, synchronize with data word.
+        assert_eq!(of(0x2C01), ModeWithoutData);
+        assert_eq!(of(0x2C10), ModeWithDataTransmit);
+        assert_eq!(of(0x2811), ModeWithDataReceive);
+        assert_eq!(of(0xFC01), BroadcastModeWithoutData);
+        assert_eq!(of(0xF811), BroadcastModeWithData);
+        assert_eq!(BcToRt.with_transmit_command(), Some(RtToRt));
+        assert_eq!(BroadcastRtToRt.status_words(), 1);
+        assert_eq!(RtToRt.status_words(), 2);
+    }
+
+    #[test]
+    fn assembles_messages_in_bus_order() {
+        let words = [
+            // RT 5 to RT 6, two words.
+            cmd(0x3022),
+            cmd(0x2C22),
+            status(0x2800),
+            data(1),
+            data(2),
+            status(0x3000),
+            // Broadcast synchronize, then RT 5 to BC with no answer.
+            cmd(0xFC01),
+            cmd(0x2C21),
+        ];
+        let mut assembler = Assembler::new(BusId::B);
+        let mut messages = words
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, word)| assembler.push(idx as u64 * 20_000, Ok(*word)));
+
+        let transfer = messages.next().unwrap();
+        assert_eq!(transfer.format(), MessageFormat::RtToRt);
+        assert_eq!(transfer.bus(), BusId::B);
+        assert_eq!((transfer.start_ns(), transfer.end_ns()), (0, 120_000));
+        assert!(transfer.words().eq(words[..6].iter().copied()));
+        assert!(!transfer.errors().any());
+        assert_eq!(transfer.validate(), Ok(()));
+
+        let sync = messages.next().unwrap();
+        assert_eq!(sync.format(), MessageFormat::BroadcastModeWithoutData);
+        assert_eq!(messages.next(), None);
+
+        let silent = assembler.finish().unwrap();
+        assert_eq!(silent.format(), MessageFormat::RtToBc);
+        assert!(silent.errors().no_response && !silent.errors().word_count);
+        assert_eq!(silent.validate(), Ok(()));
+
+        // Up to the end of time.
+        let mut late = Assembler::new(BusId::A);
+        assert_eq!(late.push(u64::MAX, Ok(cmd(0x2C21))), None);
+        assert_eq!(late.finish().unwrap().end_ns(), u64::MAX);
+    }
+
+    #[test]
+    fn validation_catches_what_errors_do_not_explain() {
+        let mut message = Message::new(BusId::A, CommandWord::from_u16(0x2822));
+        message.push_data(DataWord::from_u16(1)).unwrap();
+        assert_eq!(
+            message.validate(),
+            Err(ConsistencyError::DataCount {
+                expected: 2,
+                actual: 1
+            })
+        );
+        message.push_data(DataWord::from_u16(2)).unwrap();
+        assert_eq!(
+            message.validate(),
+            Err(ConsistencyError::StatusCount {
+                expected: 1,
+                actual: 0
+            })
+        );
+        message.errors_mut().no_response = true;
+        assert_eq!(message.validate(), Ok(()));
+        // A data word where the status word was due stops the BC just the
+        // same.
+        message.errors_mut().no_response = false;
+        message.errors_mut().sequence = true;
+        assert_eq!(message.validate(), Ok(()));
+        message.errors_mut().sequence = false;
+
+        // But a status word from another RT doesn't excuse missing data.
+        let mut short = Message::new(BusId::A, CommandWord::from_u16(0x2822));
+        short.push_data(DataWord::from_u16(1)).unwrap();
+        short.push_status(StatusWord::from_u16(0x3000)).unwrap();
+        short.errors_mut().wrong_address = true;
+        assert_eq!(
+            short.validate(),
+            Err(ConsistencyError::DataCount {
+                expected: 2,
+                actual: 1
+            })
+        );
+
+        // RT 6 answering for RT 5.
+        message.push_status(StatusWord::from_u16(0x3000)).unwrap();
+        message.errors_mut().no_response = false;
+        assert_eq!(
+            message.validate(),
+            Err(ConsistencyError::StatusAddress(StatusWord::from_u16(
+                0x3000
+            )))
+        );
+
+        // A transmit command can't be the receiving side of RT to RT.
+        let bad = Message::rt_to_rt(
+            BusId::A,
+            CommandWord::from_u16(0x2C22),
+            CommandWord::from_u16(0x3422),
+        );
+        assert_eq!(bad.validate(), Err(ConsistencyError::BadCommand));
+    }
+}
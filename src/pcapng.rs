@@ -0,0 +1,517 @@
+//! pcapng files of monitored messages, for the usual pcap tooling.
+//!
+//! [`PcapngWriter`] starts a section with one interface per bus, interface
+//! 0 for bus A and 1 for bus B, both of link type [`LINK_TYPE`] (the first
+//! user DLT) with nanosecond timestamps. Each [`Message`] becomes one
+//! enhanced packet, timestamped with its start, whose data is, big endian:
+//!
+//! | Bytes | Field                                                      |
+//! |-------|------------------------------------------------------------|
+//! | 0     | layout version, [`PACKET_VERSION`]                         |
+//! | 1     | bus: 0 for A, 1 for B                                      |
+//! | 2     | format, its index in [`MessageFormat::ALL`]                |
+//! | 3     | error flags, `ERR_*`                                       |
+//! | 4..8  | duration, from the start of the first command to the end   |
+//! |       | of the last word, in ns                                    |
+//! | 8..   | the words in bus order, 3 bytes each as [`frame::encode`]  |
+//!
+//! [`PcapngReader`] reads them back, in either byte order and at any
+//! timestamp resolution, and skips packets of other link types, so files
+//! merged with other captures still read.
+use std::io::{self, Read, Write};
+use std::vec::Vec;
+
+use crate::{
+    bus::BusId,
+    frame::{self, FRAME_LEN},
+    message::{ConsistencyError, Message, MessageErrors, MessageFormat},
+    words::Word,
+};
+
+/// LINKTYPE_USER0.
+pub const LINK_TYPE: u16 = 147;
+pub const PACKET_VERSION: u8 = 1;
+pub const PACKET_HEADER_LEN: usize = 8;
+/// An RT to RT transfer of 32 words: two commands, two status words.
+pub const MAX_PACKET_LEN: usize = PACKET_HEADER_LEN + 36 * FRAME_LEN;
+
+pub const ERR_NO_RESPONSE: u8 = 0x01;
+pub const ERR_WORD: u8 = 0x02;
+pub const ERR_WRONG_ADDRESS: u8 = 0x04;
+pub const ERR_WORD_COUNT: u8 = 0x08;
+pub const ERR_SEQUENCE: u8 = 0x10;
+
+const SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION: u32 = 1;
+const ENHANCED_PACKET: u32 = 6;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const OPT_END: u16 = 0;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_TSRESOL: u16 = 9;
+/// Larger blocks are taken for a corrupt length rather than read.
+const MAX_BLOCK_LEN: usize = 1 << 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcapngError {
+    /// The file does not start with a section header block.
+    NotPcapng,
+    /// A block with a bad length or layout.
+    BadBlock,
+    /// A packet for an interface not described before it.
+    UnknownInterface(u32),
+    UnsupportedVersion(u8),
+    /// Packet data that is not a message.
+    BadPacket,
+    /// A message whose words contradict each other or its error flags.
+    Inconsistent(ConsistencyError),
+}
+
+fn invalid(err: PcapngError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, std::format!("{:?}", err))
+}
+
+fn push_block(out: &mut Vec<u8>, kind: u32, body: &[u8]) {
+    let len = (12 + body.len().next_multiple_of(4)) as u32;
+    out.extend_from_slice(&kind.to_le_bytes());
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(body);
+    out.resize(out.len().next_multiple_of(4), 0);
+    out.extend_from_slice(&len.to_le_bytes());
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    body.extend_from_slice(value);
+    body.resize(body.len().next_multiple_of(4), 0);
+}
+
+/// Writes messages to a pcapng file, little endian.
+pub struct PcapngWriter<W: Write> {
+    out: W,
+    block: Vec<u8>,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    /// Start the file: a section header and the two interfaces.
+    pub fn new(mut out: W) -> io::Result<Self> {
+        let mut block = Vec::new();
+        let mut body = Vec::new();
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        // Section length not known.
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+        push_block(&mut block, SECTION_HEADER, &body);
+
+        for name in [&b"bus A"[..], b"bus B"] {
+            body.clear();
+            body.extend_from_slice(&LINK_TYPE.to_le_bytes());
+            body.extend_from_slice(&0u16.to_le_bytes());
+            body.extend_from_slice(&(MAX_PACKET_LEN as u32).to_le_bytes());
+            push_option(&mut body, OPT_IF_NAME, name);
+            push_option(&mut body, OPT_IF_TSRESOL, &[9]);
+            push_option(&mut body, OPT_END, &[]);
+            push_block(&mut block, INTERFACE_DESCRIPTION, &body);
+        }
+        out.write_all(&block)?;
+        Ok(Self { out, block })
+    }
+
+    pub fn write(&mut self, message: &Message) -> io::Result<()> {
+        let mut body = Vec::with_capacity(20 + MAX_PACKET_LEN);
+        body.extend_from_slice(&(message.bus().index() as u32).to_le_bytes());
+        body.extend_from_slice(&((message.start_ns() >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(message.start_ns() as u32).to_le_bytes());
+        body.extend_from_slice(&[0; 8]);
+        encode_packet(message, &mut body);
+        let len = (body.len() - 20) as u32;
+        body[12..16].copy_from_slice(&len.to_le_bytes());
+        body[16..20].copy_from_slice(&len.to_le_bytes());
+
+        self.block.clear();
+        push_block(&mut self.block, ENHANCED_PACKET, &body);
+        self.out.write_all(&self.block)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+fn encode_packet(message: &Message, out: &mut Vec<u8>) {
+    let errors = message.errors();
+    let flags = [
+        (errors.no_response, ERR_NO_RESPONSE),
+        (errors.word_error, ERR_WORD),
+        (errors.wrong_address, ERR_WRONG_ADDRESS),
+        (errors.word_count, ERR_WORD_COUNT),
+        (errors.sequence, ERR_SEQUENCE),
+    ]
+    .iter()
+    .filter(|(set, _)| *set)
+    .fold(0, |flags, (_, bit)| flags | bit);
+    let format = MessageFormat::ALL
+        .iter()
+        .position(|format| *format == message.format())
+        .unwrap_or_default();
+    let duration = message.end_ns().saturating_sub(message.start_ns());
+    out.extend_from_slice(&[
+        PACKET_VERSION,
+        message.bus().index() as u8,
+        format as u8,
+        flags,
+    ]);
+    out.extend_from_slice(&(duration.min(u32::MAX as u64) as u32).to_be_bytes());
+    for word in message.words() {
+        out.extend_from_slice(&frame::encode(word));
+    }
+}
+
+fn decode_packet(time_ns: u64, packet: &[u8]) -> Result<Message, PcapngError> {
+    if packet.len() < PACKET_HEADER_LEN
+        || !(packet.len() - PACKET_HEADER_LEN).is_multiple_of(FRAME_LEN)
+    {
+        return Err(PcapngError::BadPacket);
+    }
+    if packet[0] != PACKET_VERSION {
+        return Err(PcapngError::UnsupportedVersion(packet[0]));
+    }
+    let bus = match packet[1] {
+        0 => BusId::A,
+        1 => BusId::B,
+        _ => return Err(PcapngError::BadPacket),
+    };
+    let format = *MessageFormat::ALL
+        .get(packet[2] as usize)
+        .ok_or(PcapngError::BadPacket)?;
+    let flags = packet[3];
+    let duration = u32::from_be_bytes(packet[4..8].try_into().unwrap());
+
+    let mut words = packet[PACKET_HEADER_LEN..]
+        .chunks_exact(FRAME_LEN)
+        .map(|chunk| frame::decode(chunk.try_into().unwrap()));
+    let mut command = || match words.next() {
+        Some(Some(Word::Command(cmd))) => Ok(cmd),
+        _ => Err(PcapngError::BadPacket),
+    };
+    let mut message = match format.is_rt_to_rt() {
+        true => Message::rt_to_rt(bus, command()?, command()?),
+        false => Message::new(bus, command()?),
+    };
+    if message.format() != format {
+        return Err(PcapngError::BadPacket);
+    }
+    for word in words {
+        let pushed = match word {
+            Some(Word::Data(dw)) => message.push_data(dw).is_ok(),
+            Some(Word::Status(sw)) => message.push_status(sw).is_ok(),
+            _ => false,
+        };
+        if !pushed {
+            return Err(PcapngError::BadPacket);
+        }
+    }
+    *message.errors_mut() = MessageErrors {
+        no_response: flags & ERR_NO_RESPONSE != 0,
+        word_error: flags & ERR_WORD != 0,
+        wrong_address: flags & ERR_WRONG_ADDRESS != 0,
+        word_count: flags & ERR_WORD_COUNT != 0,
+        sequence: flags & ERR_SEQUENCE != 0,
+    };
+    let end_ns = time_ns
+        .checked_add(duration as u64)
+        .ok_or(PcapngError::BadPacket)?;
+    message.set_time(time_ns, end_ns);
+    message.validate().map_err(PcapngError::Inconsistent)?;
+    Ok(message)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Interface {
+    link_type: u16,
+    /// `if_tsresol`: 10^-n s, or 2^-n s with the top bit set.
+    resolution: u8,
+}
+
+impl Interface {
+    fn ticks_to_ns(&self, ticks: u64) -> u64 {
+        let exponent = (self.resolution & 0x7F) as u32;
+        if self.resolution & 0x80 != 0 {
+            return ((ticks as u128 * 1_000_000_000) >> exponent.min(127)) as u64;
+        }
+        match exponent {
+            0..=9 => ticks.saturating_mul(10u64.pow(9 - exponent)),
+            _ => ticks / 10u64.saturating_pow(exponent - 9),
+        }
+    }
+}
+
+/// Reads the messages of a pcapng file, in file order.
+pub struct PcapngReader<R: Read> {
+    input: R,
+    big_endian: bool,
+    interfaces: Vec<Interface>,
+    started: bool,
+}
+
+impl<R: Read> PcapngReader<R> {
+    pub fn new(input: R) -> Self {
+        Self {
+            input,
+            big_endian: false,
+            interfaces: Vec::new(),
+            started: false,
+        }
+    }
+
+    fn u16_at(&self, bytes: &[u8], at: usize) -> u16 {
+        let bytes = bytes[at..at + 2].try_into().unwrap();
+        match self.big_endian {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        }
+    }
+
+    fn u32_at(&self, bytes: &[u8], at: usize) -> u32 {
+        let bytes = bytes[at..at + 4].try_into().unwrap();
+        match self.big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        }
+    }
+
+    /// The next block, as its type and body. `None` at the end of the file.
+    fn block(&mut self) -> io::Result<Option<(u32, Vec<u8>)>> {
+        let mut head = [0; 8];
+        match self.input.read_exact(&mut head[..4]) {
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        self.input.read_exact(&mut head[4..])?;
+        let kind = self.u32_at(&head, 0);
+        if kind == SECTION_HEADER {
+            // Each section sets its own byte order, from its magic.
+            let mut magic = [0; 4];
+            self.input.read_exact(&mut magic)?;
+            self.big_endian = match u32::from_le_bytes(magic) {
+                BYTE_ORDER_MAGIC => false,
+                _ if u32::from_be_bytes(magic) == BYTE_ORDER_MAGIC => true,
+                _ => return Err(invalid(PcapngError::BadBlock)),
+            };
+            let len = self.u32_at(&head, 4) as usize;
+            let body = self.body(len, 4)?;
+            self.interfaces.clear();
+            self.started = true;
+            return Ok(Some((kind, body)));
+        }
+        if !self.started {
+            return Err(invalid(PcapngError::NotPcapng));
+        }
+        let len = self.u32_at(&head, 4) as usize;
+        Ok(Some((kind, self.body(len, 0)?)))
+    }
+
+    /// Read the rest of a block of `len` bytes, `read` of its body already
+    /// read, and check its trailing length.
+    fn body(&mut self, len: usize, read: usize) -> io::Result<Vec<u8>> {
+        if len < 12 + read || !len.is_multiple_of(4) || len > MAX_BLOCK_LEN {
+            return Err(invalid(PcapngError::BadBlock));
+        }
+        let mut body = std::vec![0; len - 12 - read];
+        self.input.read_exact(&mut body)?;
+        let mut trailer = [0; 4];
+        self.input.read_exact(&mut trailer)?;
+        if self.u32_at(&trailer, 0) as usize != len {
+            return Err(invalid(PcapngError::BadBlock));
+        }
+        Ok(body)
+    }
+
+    fn interface(&self, body: &[u8]) -> Result<Interface, PcapngError> {
+        if body.len() < 8 {
+            return Err(PcapngError::BadBlock);
+        }
+        let mut interface = Interface {
+            link_type: self.u16_at(body, 0),
+            resolution: 6,
+        };
+        let mut at = 8;
+        while at + 4 <= body.len() {
+            let code = self.u16_at(body, at);
+            let len = self.u16_at(body, at + 2) as usize;
+            if code == OPT_END || at + 4 + len > body.len() {
+                break;
+            }
+            if code == OPT_IF_TSRESOL && len >= 1 {
+                interface.resolution = body[at + 4];
+            }
+            at += 4 + len.next_multiple_of(4);
+        }
+        Ok(interface)
+    }
+
+    /// The next message, `None` at the end of the file.
+    pub fn read(&mut self) -> io::Result<Option<Message>> {
+        while let Some((kind, body)) = self.block()? {
+            match kind {
+                INTERFACE_DESCRIPTION => {
+                    let interface = self.interface(&body).map_err(invalid)?;
+                    self.interfaces.push(interface);
+                }
+                ENHANCED_PACKET => {
+                    if body.len() < 20 {
+                        return Err(invalid(PcapngError::BadBlock));
+                    }
+                    let id = self.u32_at(&body, 0);
+                    let interface = *self
+                        .interfaces
+                        .get(id as usize)
+                        .ok_or(invalid(PcapngError::UnknownInterface(id)))?;
+                    if interface.link_type != LINK_TYPE {
+                        continue;
+                    }
+                    let ticks = (self.u32_at(&body, 4) as u64) << 32 | self.u32_at(&body, 8) as u64;
+                    let captured = self.u32_at(&body, 12) as usize;
+                    let packet = body
+                        .get(20..20 + captured)
+                        .ok_or(invalid(PcapngError::BadBlock))?;
+                    return decode_packet(interface.ticks_to_ns(ticks), packet)
+                        .map(Some)
+                        .map_err(invalid);
+                }
+                // Statistics, name resolution, custom blocks...
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+}
+
+impl<R: Read> Iterator for PcapngReader<R> {
+    type Item = io::Result<Message>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::{bus::BusId, message::*, pcapng::*, words::*};
+
+    fn messages() -> [Message; 3] {
+        let mut transfer = Message::new(BusId::A, CommandWord::from_u16(0x2822));
+        transfer.push_data(DataWord::from_u16(0xCAFE)).unwrap();
+        transfer.push_data(DataWord::from_u16(0xF00D)).unwrap();
+        transfer.push_status(StatusWord::from_u16(0x2800)).unwrap();
+        transfer.set_time(1_000_000_000_123, 1_000_000_080_123);
+
+        let mut rt_to_rt = Message::rt_to_rt(
+            BusId::B,
+            CommandWord::from_u16(0x3021),
+            CommandWord::from_u16(0x2C21),
+        );
+        rt_to_rt.push_status(StatusWord::from_u16(0x2800)).unwrap();
+        rt_to_rt.push_data(DataWord::from_u16(7)).unwrap();
+        rt_to_rt.errors_mut().no_response = true;
+        rt_to_rt.set_time(1_000_000_200_000, 1_000_000_300_000);
+
+        let mut sync = Message::new(BusId::A, CommandWord::from_u16(0xFC01));
+        sync.set_time(1_000_000_400_000, 1_000_000_420_000);
+        [transfer, rt_to_rt, sync]
+    }
+
+    #[test]
+    fn messages_read_back() {
+        let mut writer = PcapngWriter::new(Vec::new()).unwrap();
+        for message in &messages() {
+            writer.write(message).unwrap();
+        }
+        let file = writer.into_inner();
+        assert_eq!(&file[..4], &[0x0A, 0x0D, 0x0D, 0x0A]);
+        assert!(file.len().is_multiple_of(4));
+
+        let read: Vec<Message> = PcapngReader::new(&file[..])
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(read, messages());
+    }
+
+    /// A big endian section, microsecond timestamps, with a foreign
+    /// interface and an unknown block thrown in.
+    #[test]
+    fn reads_other_byte_orders_and_skips_foreign_traffic() {
+        fn block(out: &mut Vec<u8>, kind: u32, body: &[u8]) {
+            let len = (12 + body.len()) as u32;
+            out.extend_from_slice(&kind.to_be_bytes());
+            out.extend_from_slice(&len.to_be_bytes());
+            out.extend_from_slice(body);
+            out.extend_from_slice(&len.to_be_bytes());
+        }
+        fn packet(out: &mut Vec<u8>, interface: u32, micros: u64, data: &[u8]) {
+            let mut body = Vec::new();
+            body.extend_from_slice(&interface.to_be_bytes());
+            body.extend_from_slice(&((micros >> 32) as u32).to_be_bytes());
+            body.extend_from_slice(&(micros as u32).to_be_bytes());
+            body.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            body.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            body.extend_from_slice(data);
+            body.resize(body.len().next_multiple_of(4), 0);
+            block(out, 6, &body);
+        }
+
+        let mut file = Vec::new();
+        block(
+            &mut file,
+            0x0A0D_0D0A,
+            &[
+                0x1A, 0x2B, 0x3C, 0x4D, 0, 1, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            ],
+        );
+        // Ethernet, then ours without options.
+        block(&mut file, 1, &[0, 1, 0, 0, 0, 0, 0xFF, 0xFF]);
+        block(&mut file, 1, &[0, 147, 0, 0, 0, 0, 0xFF, 0xFF]);
+        packet(&mut file, 0, 5, &[0xAA; 60]);
+        block(&mut file, 0x0BAD, &[1, 2, 3, 4]);
+        // RT 5 mode code synchronize, answered.
+        let data = [
+            1, 1, 3, 0, 0, 0, 0x9C, 0x40, b'C', 0x2C, 0x01, b'S', 0x28, 0x00,
+        ];
+        packet(&mut file, 1, 7, &data);
+
+        let mut reader = PcapngReader::new(&file[..]);
+        let message = reader.read().unwrap().unwrap();
+        assert_eq!(message.bus(), BusId::B);
+        assert_eq!(message.format(), MessageFormat::ModeWithoutData);
+        assert_eq!((message.start_ns(), message.end_ns()), (7_000, 47_000));
+        assert_eq!(message.status_words(), &[StatusWord::from_u16(0x2800)]);
+        assert!(reader.read().unwrap().is_none());
+
+        let err = PcapngReader::new(&b"not a capture"[..]).read().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_messages_ending_past_u64() {
+        let [_, _, sync] = messages();
+        let mut packet = Vec::new();
+        encode_packet(&sync, &mut packet);
+        let last_start = u64::MAX - 20_000;
+        let message = decode_packet(last_start, &packet).unwrap();
+        assert_eq!(
+            (message.start_ns(), message.end_ns()),
+            (last_start, u64::MAX)
+        );
+        assert_eq!(
+            decode_packet(last_start + 1, &packet),
+            Err(PcapngError::BadPacket)
+        );
+    }
+}
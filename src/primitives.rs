@@ -4,13 +4,13 @@ const WORD_SIZE: u8 = 16;
 // Primitive Types for bit sized fields.
 */
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct BitField<const SIZE: u8> {
     raw_value: u8,
 }
 
 impl<const SIZE: u8> BitField<SIZE> {
-    pub fn new(value: u8) -> Self {
+    pub const fn new(value: u8) -> Self {
         if SIZE > 8 {
             panic!("SIZE is too large for u8.");
         }
@@ -18,7 +18,7 @@ impl<const SIZE: u8> BitField<SIZE> {
         Self { raw_value: value }
     }
 
-    pub fn value(&self) -> u8 {
+    pub const fn value(&self) -> u8 {
         self.raw_value
     }
 }
@@ -77,7 +77,7 @@ impl From<bool> for BitField<1> {
 
 impl From<BitField<1>> for bool {
     fn from(bitfield: BitField<1>) -> Self {
-        bitfield.into()
+        bitfield.raw_value == 1
     }
 }
 
@@ -0,0 +1,387 @@
+//! BC instruction lists, as run by commercial BC chips: execute a message,
+//! branch on its outcome, call subroutines, wait for the frame timer and
+//! raise interrupts.
+//!
+//! An [`Interpreter`] runs a list of [`Op`]s on a [`BusController`], the
+//! messages coming from a separate table so that one message can be sent
+//! from several places. It does not keep time itself: waiting for the
+//! next minor frame, delays and interrupts are [`Event`]s for the caller.
+//!
+//! ```
+//! use milisse::{bus::*, message::Message, program::*, sim::SimBus, rt::RemoteTerminal};
+//! use milisse::words::*;
+//!
+//! // Poll RT 5, and ask for its vector word when it has a service request.
+//! let messages = [
+//!     Message::new(BusId::A, CommandWord::from_u16(0x2C22)),
+//!     Message::new(BusId::A, CommandWord::from_u16(0x2C10)),
+//! ];
+//! let service_request = StatusFlags {
+//!     service_request: true,
+//!     ..StatusFlags::default()
+//! };
+//! let ops = [
+//!     Op::Execute(0),
+//!     Op::Call { to: 3, when: Condition::when(Test::Status(service_request)) },
+//!     Op::WaitFrame,
+//!     Op::Execute(1),
+//!     Op::Return { when: Condition::ALWAYS },
+//! ];
+//!
+//! let mut rts = [RemoteTerminal::new(5.into())];
+//! rts[0].set_service_request(true);
+//! let mut bus = SimBus::new(BusId::A, &mut rts);
+//! let mut bc = BusController::new(&mut bus);
+//! let mut program = Interpreter::new(&ops, &messages);
+//! assert!(matches!(program.step(&mut bc), Event::Executed(_)));
+//! assert!(matches!(program.step(&mut bc), Event::Executed(m) if m.data().len() == 1));
+//! assert_eq!(program.step(&mut bc), Event::WaitFrame);
+//! ```
+use crate::{
+    bus::BusController,
+    message::Message,
+    words::{StatusFlags, StatusWord},
+};
+
+/// Deepest nesting of subroutine calls.
+pub const CALL_DEPTH: usize = 8;
+
+/// Jumps, calls, returns and flag changes in a row before
+/// [`Interpreter::step`] gives up on a program that does nothing else.
+pub const MAX_CONTROL_OPS: usize = 256;
+
+/// What a condition looks at: the last message executed, or a general
+/// purpose flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Test {
+    Always,
+    /// No [`MessageErrors`](crate::message::MessageErrors), and no status
+    /// word with Message Error or Busy.
+    Good,
+    /// Any [`MessageErrors`](crate::message::MessageErrors).
+    Error,
+    NoResponse,
+    /// A status word with any of these flags set.
+    Status(StatusFlags),
+    /// General purpose flag 0 to 15.
+    Flag(u8),
+}
+
+/// A [`Test`] and the result it takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub test: Test,
+    pub expected: bool,
+}
+
+impl Condition {
+    pub const ALWAYS: Self = Self::when(Test::Always);
+
+    pub const fn when(test: Test) -> Self {
+        Self {
+            test,
+            expected: true,
+        }
+    }
+
+    pub const fn unless(test: Test) -> Self {
+        Self {
+            test,
+            expected: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Run message `n` of the message table.
+    Execute(usize),
+    Jump {
+        to: usize,
+        when: Condition,
+    },
+    Call {
+        to: usize,
+        when: Condition,
+    },
+    Return {
+        when: Condition,
+    },
+    /// Wait for the start of the next minor frame.
+    WaitFrame,
+    /// Wait this many ns.
+    Delay(u64),
+    /// Interrupt the host with this vector.
+    Interrupt {
+        vector: u16,
+        when: Condition,
+    },
+    SetFlag(u8),
+    ClearFlag(u8),
+    Halt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgramError {
+    /// An op address past the end of the list.
+    BadAddress(usize),
+    /// A message missing from the table.
+    BadMessage(usize),
+    BadFlag(u8),
+    /// A call deeper than [`CALL_DEPTH`].
+    StackOverflow,
+    /// A return outside any subroutine.
+    StackUnderflow,
+    /// More than [`MAX_CONTROL_OPS`] control ops in a row.
+    Runaway,
+}
+
+/// What [`Interpreter::step`] stopped for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A message went out, as in [`BusController::transact`].
+    Executed(Message),
+    WaitFrame,
+    Delay(u64),
+    Interrupt(u16),
+    /// The program is over: a `Halt`, or the end of the list.
+    Halted,
+    /// The program is stuck at the op that failed.
+    Error(ProgramError),
+}
+
+pub struct Interpreter<'p> {
+    ops: &'p [Op],
+    messages: &'p [Message],
+    pc: usize,
+    stack: [usize; CALL_DEPTH],
+    depth: usize,
+    flags: u16,
+    last: Option<Message>,
+}
+
+impl<'p> Interpreter<'p> {
+    pub fn new(ops: &'p [Op], messages: &'p [Message]) -> Self {
+        Self {
+            ops,
+            messages,
+            pc: 0,
+            stack: [0; CALL_DEPTH],
+            depth: 0,
+            flags: 0,
+            last: None,
+        }
+    }
+
+    /// Address of the next op.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// The last message executed.
+    pub fn last(&self) -> Option<&Message> {
+        self.last.as_ref()
+    }
+
+    pub fn test(&self, test: Test) -> bool {
+        let status = |flags: StatusFlags| {
+            let mut mask = StatusWord::from_u16(0);
+            mask.set_flags(flags);
+            self.last.is_some_and(|message| {
+                message
+                    .status_words()
+                    .iter()
+                    .any(|sw| sw.value() & mask.value() != 0)
+            })
+        };
+        let declined = StatusFlags {
+            message_error: true,
+            busy: true,
+            ..StatusFlags::default()
+        };
+        match test {
+            Test::Always => true,
+            Test::Good => self.last.is_some_and(|m| !m.errors().any()) && !status(declined),
+            Test::Error => self.last.is_some_and(|m| m.errors().any()),
+            Test::NoResponse => self.last.is_some_and(|m| m.errors().no_response),
+            Test::Status(flags) => status(flags),
+            Test::Flag(n) => n < 16 && self.flags & (1 << n) != 0,
+        }
+    }
+
+    fn holds(&self, condition: Condition) -> bool {
+        self.test(condition.test) == condition.expected
+    }
+
+    /// Run the program up to the next thing the caller sees: a message
+    /// sent, a wait, an interrupt, the end, or an error. Once halted or
+    /// failed, it stays so.
+    pub fn step(&mut self, bc: &mut BusController) -> Event {
+        for _ in 0..MAX_CONTROL_OPS {
+            let Some(op) = self.ops.get(self.pc).copied() else {
+                return match self.pc == self.ops.len() {
+                    true => Event::Halted,
+                    false => Event::Error(ProgramError::BadAddress(self.pc)),
+                };
+            };
+            match self.execute(op, bc) {
+                Ok(Some(event)) => return event,
+                Ok(None) => {}
+                Err(err) => return Event::Error(err),
+            }
+        }
+        Event::Error(ProgramError::Runaway)
+    }
+
+    /// Carry out `op`, moving on unless it fails or halts.
+    fn execute(&mut self, op: Op, bc: &mut BusController) -> Result<Option<Event>, ProgramError> {
+        let next = self.pc + 1;
+        let mut event = None;
+        match op {
+            Op::Execute(n) => {
+                let request = self.messages.get(n).ok_or(ProgramError::BadMessage(n))?;
+                let message = bc.transact(request);
+                self.last = Some(message);
+                event = Some(Event::Executed(message));
+            }
+            Op::Jump { to, when } if self.holds(when) => {
+                self.pc = self.target(to)?;
+                return Ok(None);
+            }
+            Op::Call { to, when } if self.holds(when) => {
+                let to = self.target(to)?;
+                if self.depth == CALL_DEPTH {
+                    return Err(ProgramError::StackOverflow);
+                }
+                self.stack[self.depth] = next;
+                self.depth += 1;
+                self.pc = to;
+                return Ok(None);
+            }
+            Op::Return { when } if self.holds(when) => {
+                if self.depth == 0 {
+                    return Err(ProgramError::StackUnderflow);
+                }
+                self.depth -= 1;
+                self.pc = self.stack[self.depth];
+                return Ok(None);
+            }
+            Op::Interrupt { vector, when } if self.holds(when) => {
+                event = Some(Event::Interrupt(vector));
+            }
+            Op::Jump { .. } | Op::Call { .. } | Op::Return { .. } | Op::Interrupt { .. } => {}
+            Op::WaitFrame => event = Some(Event::WaitFrame),
+            Op::Delay(ns) => event = Some(Event::Delay(ns)),
+            Op::SetFlag(n) | Op::ClearFlag(n) if n >= 16 => return Err(ProgramError::BadFlag(n)),
+            Op::SetFlag(n) => self.flags |= 1 << n,
+            Op::ClearFlag(n) => self.flags &= !(1 << n),
+            Op::Halt => return Ok(Some(Event::Halted)),
+        }
+        self.pc = next;
+        Ok(event)
+    }
+
+    fn target(&self, to: usize) -> Result<usize, ProgramError> {
+        match to < self.ops.len() {
+            true => Ok(to),
+            false => Err(ProgramError::BadAddress(to)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{bus::*, message::*, program::*, rt::*, sim::*, words::*};
+
+    #[test]
+    fn retries_a_busy_rt() {
+        let messages = [Message::new(BusId::A, CommandWord::from_u16(0x2C22))];
+        let busy = StatusFlags {
+            busy: true,
+            ..StatusFlags::default()
+        };
+        // Retry a busy RT once after a delay, then tell the host.
+        let ops = [
+            Op::Execute(0),
+            Op::Jump {
+                to: 7,
+                when: Condition::unless(Test::Status(busy)),
+            },
+            Op::Jump {
+                to: 6,
+                when: Condition::when(Test::Flag(0)),
+            },
+            Op::SetFlag(0),
+            Op::Call {
+                to: 8,
+                when: Condition::ALWAYS,
+            },
+            Op::Jump {
+                to: 0,
+                when: Condition::ALWAYS,
+            },
+            Op::Interrupt {
+                vector: 0x15,
+                when: Condition::ALWAYS,
+            },
+            Op::Halt,
+            Op::Delay(1_000),
+            Op::Return {
+                when: Condition::ALWAYS,
+            },
+        ];
+        let mut rts = [RemoteTerminal::new(5.into())];
+        rts[0].set_busy(true);
+        let mut bus = SimBus::new(BusId::A, &mut rts);
+        let mut bc = BusController::new(&mut bus);
+        let mut program = Interpreter::new(&ops, &messages);
+
+        let event = program.step(&mut bc);
+        assert!(matches!(event, Event::Executed(m) if m.transmitter_declined()));
+        assert_eq!(program.step(&mut bc), Event::Delay(1_000));
+        assert!(matches!(program.step(&mut bc), Event::Executed(_)));
+        assert_eq!(program.step(&mut bc), Event::Interrupt(0x15));
+        assert_eq!(program.step(&mut bc), Event::Halted);
+        assert_eq!(program.flags(), 1);
+        assert!(program.test(Test::Status(busy)));
+        assert!(!program.test(Test::Good));
+        assert_eq!(program.step(&mut bc), Event::Halted);
+    }
+
+    #[test]
+    fn reports_bad_programs() {
+        let mut rts = [RemoteTerminal::new(5.into())];
+        let mut bus = SimBus::new(BusId::A, &mut rts);
+        let mut bc = BusController::new(&mut bus);
+        let run = |ops: &[Op], bc: &mut BusController| Interpreter::new(ops, &[]).step(bc);
+
+        let spin = [Op::Jump {
+            to: 0,
+            when: Condition::ALWAYS,
+        }];
+        assert_eq!(run(&spin, &mut bc), Event::Error(ProgramError::Runaway));
+        let ret = [Op::Return {
+            when: Condition::ALWAYS,
+        }];
+        assert_eq!(
+            run(&ret, &mut bc),
+            Event::Error(ProgramError::StackUnderflow)
+        );
+        let recurse = [Op::Call {
+            to: 0,
+            when: Condition::ALWAYS,
+        }];
+        assert_eq!(
+            run(&recurse, &mut bc),
+            Event::Error(ProgramError::StackOverflow)
+        );
+        assert_eq!(
+            run(&[Op::Execute(0)], &mut bc),
+            Event::Error(ProgramError::BadMessage(0))
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use crate::words::Word;
+
+/// Fixed-capacity FIFO of bus words, for buses that need to hold words
+/// back without allocating.
+#[derive(Debug, Clone)]
+pub struct WordQueue<const N: usize> {
+    words: [Word; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> WordQueue<N> {
+    pub const fn new() -> Self {
+        Self {
+            words: [Word::Data(crate::words::DataWord::from_u16(0)); N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Queue `word`, or hand it back when the queue is full.
+    pub fn push(&mut self, word: Word) -> Result<(), Word> {
+        if self.len == N {
+            return Err(word);
+        }
+        self.words[(self.head + self.len) % N] = word;
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<Word> {
+        if self.len == 0 {
+            return None;
+        }
+        let word = self.words[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(word)
+    }
+}
+
+impl<const N: usize> Default for WordQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
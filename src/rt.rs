@@ -0,0 +1,1032 @@
+use crate::{
+    buffers::*,
+    bus::{Bus, BusError, BusId},
+    legality::*,
+    message::{Assembler, Message},
+    primitives::BitField,
+    words::*,
+};
+
+/// Data subaddresses an RT can buffer (1 to 30, 0 and 31 are mode codes).
+pub const DATA_SUBADDRESSES: usize = 30;
+
+/// What a RemoteTerminal puts on the bus in reply to a message:
+/// its status word, followed by any data words it transmits.
+#[derive(Debug, Clone)]
+pub struct Response {
+    status: StatusWord,
+    data: [DataWord; MAX_DATA_WORDS],
+    len: u8,
+}
+
+impl Response {
+    fn new(status: StatusWord) -> Self {
+        Self {
+            status,
+            data: [DataWord::from_u16(0); MAX_DATA_WORDS],
+            len: 0,
+        }
+    }
+
+    pub fn status(&self) -> StatusWord {
+        self.status
+    }
+
+    pub fn data(&self) -> &[DataWord] {
+        &self.data[..self.len as usize]
+    }
+
+    /// Write the status word and the data words to `bus`.
+    pub fn write_to(&self, bus: &mut dyn Bus) {
+        bus.write_word(Word::Status(self.status));
+        for dw in self.data() {
+            bus.write_word(Word::Data(*dw));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Idle,
+    Receiving {
+        bus: BusId,
+        command: CommandWord,
+        accept: bool,
+        expected: u8,
+        received: u8,
+        /// The transmit command of an RT to RT transfer went by.
+        relayed: bool,
+    },
+}
+
+/// Mode commands the application has to act on, collected since the last
+/// call to [`RemoteTerminal::take_events`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ModeEvents {
+    /// Synchronize (with or without data word) was received.
+    pub synchronize: bool,
+    /// Data word of the last Synchronize With Data Word.
+    pub sync_data: Option<DataWord>,
+    /// Initiate Self Test was received.
+    pub self_test: bool,
+    /// Reset RT was received. The RT already reset its own state.
+    pub reset: bool,
+    /// Dynamic Bus Control was offered and accepted.
+    pub dynamic_bus_control: bool,
+}
+
+/// A Remote Terminal fed one bus word at a time. Data transfers are stored
+/// in per-subaddress receive and transmit buffers, see [`SubaddressBuffer`],
+/// and every defined mode code is carried out.
+#[derive(Debug, Clone)]
+pub struct RemoteTerminal {
+    addr: BitField<5>,
+    status: StatusWord,
+    state: State,
+    legality: LegalityTable,
+    last_command: Option<CommandWord>,
+    transmitter_enabled: [bool; 2],
+    terminal_flag_inhibited: bool,
+    accept_dynamic_bus_control: bool,
+    broadcast_enabled: bool,
+    vector_word: DataWord,
+    bit_word: DataWord,
+    events: ModeEvents,
+    rx: [SubaddressBuffer; DATA_SUBADDRESSES],
+    tx: [SubaddressBuffer; DATA_SUBADDRESSES],
+    // What the RT heard and said on each bus, while logging.
+    log: Option<[Assembler; 2]>,
+    logged: Option<Message>,
+}
+
+/// Number of data words a command's word count field asks for.
+fn word_count(field: BitField<5>) -> u8 {
+    match field.value() {
+        0 => MAX_DATA_WORDS as u8,
+        n => n,
+    }
+}
+
+/// Index in the buffer arrays of a data subaddress.
+fn buffer_index(subaddress: BitField<5>) -> Option<usize> {
+    match subaddress.value() {
+        0 | 31 => None,
+        sa => Some(sa as usize - 1),
+    }
+}
+
+/// Mode codes 16 to 31 carry a data word, sent by the BC when the T/R bit
+/// is Receive. Read from the raw bits since reserved codes count too.
+fn receives_mode_data(cmd: &CommandWord) -> bool {
+    cmd.value() & 0b10000 != 0 && cmd.get_tr_bit() == RTAction::Receive
+}
+
+impl RemoteTerminal {
+    /// A RemoteTerminal answering to `addr`, with every subaddress
+    /// single-buffered and the [`LegalityTable::standard`] legality.
+    pub fn new(addr: BitField<5>) -> Self {
+        Self {
+            addr,
+            status: StatusWord::builder(RTAddr::Single(addr)).build(),
+            state: State::Idle,
+            legality: LegalityTable::standard(),
+            last_command: None,
+            transmitter_enabled: [true; 2],
+            terminal_flag_inhibited: false,
+            accept_dynamic_bus_control: false,
+            broadcast_enabled: true,
+            vector_word: DataWord::from_u16(0),
+            bit_word: DataWord::from_u16(0),
+            events: ModeEvents::default(),
+            rx: [const { SubaddressBuffer::new(BufferMode::Single) }; DATA_SUBADDRESSES],
+            tx: [const { SubaddressBuffer::new(BufferMode::Single) }; DATA_SUBADDRESSES],
+            log: None,
+            logged: None,
+        }
+    }
+
+    pub fn addr(&self) -> BitField<5> {
+        self.addr
+    }
+
+    /// The status word of the last message, as Transmit Status Word would
+    /// return it.
+    pub fn status(&self) -> StatusWord {
+        let mut status = self.status;
+        if self.terminal_flag_inhibited {
+            status.set_terminal_flag(false.into());
+        }
+        status
+    }
+
+    pub fn legality(&self) -> &LegalityTable {
+        &self.legality
+    }
+
+    /// Replace the table of commands the RT accepts.
+    pub fn set_legality(&mut self, table: LegalityTable) {
+        self.legality = table;
+    }
+
+    /// The last command received, as Transmit Last Command would return it.
+    pub fn last_command(&self) -> Option<CommandWord> {
+        self.last_command
+    }
+
+    pub fn is_transmitter_enabled(&self, bus: BusId) -> bool {
+        self.transmitter_enabled[bus.index()]
+    }
+
+    pub fn is_terminal_flag_inhibited(&self) -> bool {
+        self.terminal_flag_inhibited
+    }
+
+    pub fn set_service_request(&mut self, flag: bool) {
+        self.status.set_service_request(flag.into());
+    }
+
+    /// While busy the RT answers with its status word only and neither
+    /// stores nor transmits data.
+    pub fn set_busy(&mut self, flag: bool) {
+        self.status.set_busy(flag.into());
+    }
+
+    pub fn set_subsystem_flag(&mut self, flag: bool) {
+        self.status.set_subsystem(flag.into());
+    }
+
+    /// Report a terminal fault. Masked while the BC inhibits the flag.
+    pub fn set_terminal_flag(&mut self, flag: bool) {
+        self.status.set_terminal_flag(flag.into());
+    }
+
+    /// Data word returned by Transmit Vector Word.
+    pub fn set_vector_word(&mut self, word: DataWord) {
+        self.vector_word = word;
+    }
+
+    /// Data word returned by Transmit BIT Word.
+    pub fn set_bit_word(&mut self, word: DataWord) {
+        self.bit_word = word;
+    }
+
+    /// Whether the RT accepts a Dynamic Bus Control offer.
+    pub fn set_accept_dynamic_bus_control(&mut self, accept: bool) {
+        self.accept_dynamic_bus_control = accept;
+    }
+
+    /// Whether the RT takes broadcast commands (address 31). When enabled,
+    /// broadcasts are carried out silently and flagged with the
+    /// `BroadcastCommand` status bit.
+    pub fn set_broadcast_enabled(&mut self, enabled: bool) {
+        self.broadcast_enabled = enabled;
+    }
+
+    pub fn is_broadcast_enabled(&self) -> bool {
+        self.broadcast_enabled
+    }
+
+    /// Keep the messages the RT takes part in, for [`Self::take_message`].
+    pub fn set_logging(&mut self, enabled: bool) {
+        self.log = enabled.then_some([Assembler::new(BusId::A), Assembler::new(BusId::B)]);
+        self.logged = None;
+    }
+
+    /// The last message addressed to the RT since the last call, as it
+    /// heard it and answered it, once over. Messages carry no times, the
+    /// RT keeps none. Always `None` unless logging.
+    pub fn take_message(&mut self) -> Option<Message> {
+        self.logged.take()
+    }
+
+    /// Mode commands received since the last call.
+    pub fn take_events(&mut self) -> ModeEvents {
+        core::mem::take(&mut self.events)
+    }
+
+    /// Receive buffer of a data subaddress (1 to 30).
+    pub fn rx_buffer(&mut self, subaddress: BitField<5>) -> Option<&mut SubaddressBuffer> {
+        buffer_index(subaddress).map(|idx| &mut self.rx[idx])
+    }
+
+    /// Transmit buffer of a data subaddress (1 to 30).
+    pub fn tx_buffer(&mut self, subaddress: BitField<5>) -> Option<&mut SubaddressBuffer> {
+        buffer_index(subaddress).map(|idx| &mut self.tx[idx])
+    }
+
+    /// Application side: read the next complete message received on
+    /// `subaddress`.
+    pub fn read(&mut self, subaddress: BitField<5>, out: &mut [DataWord]) -> Option<usize> {
+        self.rx_buffer(subaddress)?.read(out)
+    }
+
+    /// Application side: set the data sent on the next transmit command for
+    /// `subaddress`.
+    pub fn write(&mut self, subaddress: BitField<5>, data: &[DataWord]) -> Result<(), BufferError> {
+        match self.tx_buffer(subaddress) {
+            Some(buffer) => buffer.write(data),
+            None => Err(BufferError::BadSubaddress),
+        }
+    }
+
+    /// Whether `next`, right after the receive command `command`, is the
+    /// transmit command of an RT to RT transfer to it, sent to another RT.
+    fn is_relay(&self, command: &CommandWord, next: &CommandWord) -> bool {
+        let data_transfer = |cmd: &CommandWord| {
+            matches!(cmd.get_command_data(), CommandWordData::DataTransfer { .. })
+        };
+        data_transfer(command)
+            && command.get_tr_bit() == RTAction::Receive
+            && data_transfer(next)
+            && next.get_tr_bit() == RTAction::Transmit
+            && match next.get_rt_addr() {
+                RTAddr::Single(addr) => addr != self.addr,
+                RTAddr::Broadcast => false,
+            }
+    }
+
+    /// Whether the RT has to act on `cmd`.
+    fn is_addressed(&self, cmd: &CommandWord) -> bool {
+        match cmd.get_rt_addr() {
+            RTAddr::Broadcast => self.broadcast_enabled,
+            addr => BitField::<5>::from(addr) == self.addr,
+        }
+    }
+
+    fn is_busy(&self) -> bool {
+        self.status.get_busy().into()
+    }
+
+    /// Take the next word from `bus` and write the reply, if any, back to it.
+    pub fn poll(&mut self, id: BusId, bus: &mut dyn Bus) -> Result<(), BusError> {
+        let word = bus.read_next()?;
+        if let Some(response) = self.on_word(id, word) {
+            response.write_to(bus);
+        }
+        Ok(())
+    }
+
+    /// Feed the next word seen on `bus`. Returns the RT's reply, to be sent
+    /// on the same bus, when the word completes a message addressed to it.
+    pub fn on_word(&mut self, bus: BusId, word: Word) -> Option<Response> {
+        let response = self.handle(bus, word);
+        if self.log.is_some() {
+            self.log(bus, word);
+            if let Some(response) = &response {
+                self.log(bus, Word::Status(response.status()));
+                for dw in response.data() {
+                    self.log(bus, Word::Data(*dw));
+                }
+            }
+        }
+        response
+    }
+
+    fn log(&mut self, bus: BusId, word: Word) {
+        let Some(log) = &mut self.log else {
+            return;
+        };
+        let Some(message) = log[bus.index()].push(0, Ok(word)) else {
+            return;
+        };
+        if message.commands().iter().any(|cmd| self.is_addressed(cmd)) {
+            self.logged = Some(message);
+        }
+    }
+
+    fn handle(&mut self, bus: BusId, word: Word) -> Option<Response> {
+        match (self.state, word) {
+            (
+                State::Receiving {
+                    command,
+                    received: 0,
+                    relayed: false,
+                    ..
+                },
+                Word::Command(cmd),
+            ) if self.is_relay(&command, &cmd) => {
+                // RT to RT: the data comes after the transmitter's status.
+                if let State::Receiving { relayed, .. } = &mut self.state {
+                    *relayed = true;
+                }
+                None
+            }
+            (State::Receiving { command, .. }, Word::Command(cmd)) => {
+                // A new command before every data word arrived: the message
+                // is invalid and is not answered.
+                self.abort_receive(command);
+                self.on_command(bus, cmd)
+            }
+            (
+                State::Receiving {
+                    bus,
+                    command,
+                    accept,
+                    expected,
+                    received,
+                    relayed,
+                },
+                Word::Data(dw),
+            ) => {
+                if accept {
+                    self.receive_data(command, dw);
+                }
+                let received = received + 1;
+                if received < expected {
+                    self.state = State::Receiving {
+                        bus,
+                        command,
+                        accept,
+                        expected,
+                        received,
+                        relayed,
+                    };
+                    return None;
+                }
+                self.state = State::Idle;
+                self.complete_receive(bus, command, accept, dw)
+            }
+            (State::Idle, Word::Command(cmd)) => self.on_command(bus, cmd),
+            _ => None,
+        }
+    }
+
+    fn on_command(&mut self, bus: BusId, cmd: CommandWord) -> Option<Response> {
+        if !self.is_addressed(&cmd) {
+            return None;
+        }
+        let code = match cmd.get_command_data() {
+            CommandWordData::ModeCode(code) => Some(code),
+            CommandWordData::DataTransfer { .. } => None,
+        };
+        // Status bits describe the last message, except for the mode codes
+        // that report on the previous one.
+        if !matches!(
+            code,
+            Some(ModeCode::TransmitStatusWord | ModeCode::TransmitLastCommand)
+        ) {
+            self.status.set_message_error(false.into());
+            self.status.set_broadcast_command(false.into());
+            self.status.set_dynamic_bus_control(false.into());
+        }
+        if cmd.get_rt_addr() == RTAddr::Broadcast {
+            self.status.set_broadcast_command(true.into());
+        }
+        let legal = self.legality.is_legal(&cmd);
+        if !legal {
+            self.status.set_message_error(true.into());
+        }
+
+        let receives = match cmd.get_command_data() {
+            CommandWordData::DataTransfer {
+                subaddress,
+                word_count: wc,
+            } if cmd.get_tr_bit() == RTAction::Receive => {
+                if legal && !self.is_busy() {
+                    if let Some(buffer) = self.rx_buffer(subaddress) {
+                        buffer.begin_receive();
+                    }
+                }
+                Some(word_count(wc))
+            }
+            CommandWordData::ModeCode(_) if receives_mode_data(&cmd) => Some(1),
+            _ => None,
+        };
+        if let Some(expected) = receives {
+            self.state = State::Receiving {
+                bus,
+                command: cmd,
+                accept: legal && !(code.is_none() && self.is_busy()),
+                expected,
+                received: 0,
+                relayed: false,
+            };
+            return None;
+        }
+
+        let response = match (code, legal) {
+            (_, false) => Response::new(self.status()),
+            (Some(code), true) => {
+                let mut response = self.mode_command(bus, code, None);
+                // The mode command is carried out, but its data word held back.
+                if self.is_busy() {
+                    response.len = 0;
+                }
+                response
+            }
+            (None, true) => self.transmit(cmd),
+        };
+        if code != Some(ModeCode::TransmitLastCommand) {
+            self.last_command = Some(cmd);
+        }
+        if code == Some(ModeCode::ResetRT) && legal {
+            self.reset();
+        }
+        self.respond(bus, cmd, response)
+    }
+
+    fn transmit(&mut self, cmd: CommandWord) -> Response {
+        let mut response = Response::new(self.status());
+        if self.is_busy() {
+            return response;
+        }
+        if let CommandWordData::DataTransfer {
+            subaddress,
+            word_count: wc,
+        } = cmd.get_command_data()
+        {
+            let len = word_count(wc) as usize;
+            if let Some(buffer) = self.tx_buffer(subaddress) {
+                response.data[..len].copy_from_slice(&buffer.transmit()[..len]);
+            }
+            response.len = len as u8;
+        }
+        response
+    }
+
+    /// Carry out a legal mode command received on `bus`, with its data word
+    /// when the BC sends one.
+    fn mode_command(&mut self, bus: BusId, code: ModeCode, data: Option<DataWord>) -> Response {
+        let mut reply_word = None;
+        match code {
+            ModeCode::DynamicBusControl => {
+                if self.accept_dynamic_bus_control {
+                    self.status.set_dynamic_bus_control(true.into());
+                    self.events.dynamic_bus_control = true;
+                }
+            }
+            ModeCode::Synchronize => self.events.synchronize = true,
+            ModeCode::SynchronizeWithDataWord => {
+                self.events.synchronize = true;
+                self.events.sync_data = data;
+            }
+            ModeCode::TransmitStatusWord => {}
+            ModeCode::InitiateSelfTest => self.events.self_test = true,
+            ModeCode::TransmitterShutdown => self.transmitter_enabled[bus.other().index()] = false,
+            ModeCode::OverrideTransmitter => self.transmitter_enabled[bus.other().index()] = true,
+            ModeCode::SelectedTransmitter | ModeCode::OverrideSelectedTransmitter => {
+                // Data word holds the index of the selected bus.
+                let selected = match data.map(|dw| dw.value()) {
+                    Some(0) => Some(BusId::A),
+                    Some(1) => Some(BusId::B),
+                    _ => None,
+                };
+                if let Some(selected) = selected {
+                    self.transmitter_enabled[selected.index()] =
+                        code == ModeCode::OverrideSelectedTransmitter;
+                }
+            }
+            ModeCode::InhibitTerminalFlagBit => self.terminal_flag_inhibited = true,
+            ModeCode::OverrideInhibitTerminalFlagBit => self.terminal_flag_inhibited = false,
+            ModeCode::ResetRT => self.events.reset = true,
+            ModeCode::TransmitVectorWord => reply_word = Some(self.vector_word),
+            ModeCode::TransmitLastCommand => {
+                let last = self.last_command.map_or(0, |cmd| cmd.value());
+                reply_word = Some(DataWord::from_u16(last));
+            }
+            ModeCode::TransmitBITWord => reply_word = Some(self.bit_word),
+            // Reserved codes never reach here unless the legality table
+            // allows them, in which case they are only acknowledged.
+            ModeCode::Invalid => {}
+        }
+        let mut response = Response::new(self.status());
+        if let Some(word) = reply_word {
+            response.data[0] = word;
+            response.len = 1;
+        }
+        response
+    }
+
+    /// Back to the power-up state, keeping the application's data.
+    fn reset(&mut self) {
+        self.status.set_message_error(false.into());
+        self.status.set_broadcast_command(false.into());
+        self.status.set_dynamic_bus_control(false.into());
+        self.transmitter_enabled = [true; 2];
+        self.terminal_flag_inhibited = false;
+        self.state = State::Idle;
+    }
+
+    /// Nothing goes out on a bus whose transmitter was shut down, nor in
+    /// reply to a broadcast.
+    fn respond(&self, bus: BusId, cmd: CommandWord, response: Response) -> Option<Response> {
+        if self.is_transmitter_enabled(bus) && cmd.get_rt_addr() != RTAddr::Broadcast {
+            Some(response)
+        } else {
+            None
+        }
+    }
+
+    fn receive_data(&mut self, command: CommandWord, dw: DataWord) {
+        if let CommandWordData::DataTransfer { subaddress, .. } = command.get_command_data() {
+            if let Some(buffer) = self.rx_buffer(subaddress) {
+                buffer.push_received(dw);
+            }
+        }
+    }
+
+    fn complete_receive(
+        &mut self,
+        bus: BusId,
+        command: CommandWord,
+        accept: bool,
+        last: DataWord,
+    ) -> Option<Response> {
+        self.last_command = Some(command);
+        let response = match command.get_command_data() {
+            CommandWordData::DataTransfer { subaddress, .. } => {
+                if let Some(buffer) = self.rx_buffer(subaddress) {
+                    buffer.finish_receive(accept);
+                }
+                Response::new(self.status())
+            }
+            CommandWordData::ModeCode(code) if self.legality.is_legal(&command) => {
+                self.mode_command(bus, code, Some(last))
+            }
+            CommandWordData::ModeCode(_) => Response::new(self.status()),
+        };
+        self.respond(bus, command, response)
+    }
+
+    fn abort_receive(&mut self, command: CommandWord) {
+        if let CommandWordData::DataTransfer { subaddress, .. } = command.get_command_data() {
+            if let Some(buffer) = self.rx_buffer(subaddress) {
+                buffer.finish_receive(false);
+            }
+        }
+        self.status.set_message_error(true.into());
+        self.state = State::Idle;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{message::MessageFormat, rt::*};
+
+    fn receive_cmd(sa: u8, wc: u8) -> Word {
+        Word::Command(CommandWord::new_data_transfer(
+            RTAddr::Single(5.into()),
+            RTAction::Receive,
+            sa.into(),
+            wc.into(),
+        ))
+    }
+
+    #[test]
+    fn receive_into_subaddress_buffer() {
+        let mut rt = RemoteTerminal::new(5.into());
+        rt.rx_buffer(3.into()).unwrap().set_mode(BufferMode::Double);
+
+        assert!(rt.on_word(BusId::A, receive_cmd(3, 2)).is_none());
+        assert!(rt
+            .on_word(BusId::A, Word::Data(DataWord::from_u16(0xA)))
+            .is_none());
+        let response = rt
+            .on_word(BusId::A, Word::Data(DataWord::from_u16(0xB)))
+            .unwrap();
+        assert_eq!(response.status().get_rt_addr(), RTAddr::Single(5.into()));
+        assert!(response.data().is_empty());
+
+        let mut out = [DataWord::from_u16(0); 32];
+        assert_eq!(rt.read(3.into(), &mut out), Some(2));
+        assert_eq!(out[1].value(), 0xB);
+
+        // The next message is still arriving: the application keeps seeing
+        // the previous, complete one.
+        rt.on_word(BusId::A, receive_cmd(3, 2));
+        rt.on_word(BusId::A, Word::Data(DataWord::from_u16(0xC)));
+        assert_eq!(rt.read(3.into(), &mut out), Some(2));
+        assert_eq!(out[0].value(), 0xA);
+    }
+
+    #[test]
+    fn transmit_from_subaddress_buffer() {
+        let mut rt = RemoteTerminal::new(5.into());
+        rt.write(7.into(), &[DataWord::from_u16(1), DataWord::from_u16(2)])
+            .unwrap();
+        let cmd = CommandWord::new_data_transfer(
+            RTAddr::Single(5.into()),
+            RTAction::Transmit,
+            7.into(),
+            2.into(),
+        );
+        let response = rt.on_word(BusId::A, Word::Command(cmd)).unwrap();
+        assert_eq!(
+            response.data(),
+            &[DataWord::from_u16(1), DataWord::from_u16(2)]
+        );
+        assert_eq!(
+            rt.write(31.into(), &[DataWord::from_u16(3)]),
+            Err(BufferError::BadSubaddress)
+        );
+    }
+
+    #[test]
+    fn illegal_commands_set_message_error() {
+        let mut rt = RemoteTerminal::new(5.into());
+        rt.set_legality(LegalityTable::ALL_ILLEGAL.with_subaddress(
+            Addressing::Own,
+            RTAction::Transmit,
+            7,
+            word_count_bit(2),
+        ));
+        rt.write(7.into(), &[DataWord::from_u16(1); 4]).unwrap();
+
+        let tx = |wc: u8| {
+            Word::Command(CommandWord::new_data_transfer(
+                RTAddr::Single(5.into()),
+                RTAction::Transmit,
+                7.into(),
+                wc.into(),
+            ))
+        };
+        let response = rt.on_word(BusId::A, tx(4)).unwrap();
+        assert!(response.status().flags().message_error);
+        assert!(response.data().is_empty());
+
+        // Illegal receive: the data words are consumed but never stored.
+        rt.on_word(BusId::A, receive_cmd(3, 1));
+        let response = rt
+            .on_word(BusId::A, Word::Data(DataWord::from_u16(0xA)))
+            .unwrap();
+        assert!(response.status().flags().message_error);
+        let mut out = [DataWord::from_u16(0); 32];
+        assert_eq!(rt.read(3.into(), &mut out), None);
+
+        let response = rt.on_word(BusId::A, tx(2)).unwrap();
+        assert!(!response.status().flags().message_error);
+        assert_eq!(response.data().len(), 2);
+    }
+
+    #[test]
+    fn interrupted_message_is_not_answered() {
+        let mut rt = RemoteTerminal::new(5.into());
+        rt.on_word(BusId::A, receive_cmd(3, 2));
+        rt.on_word(BusId::A, Word::Data(DataWord::from_u16(0xA)));
+        assert!(rt.on_word(BusId::A, receive_cmd(4, 1)).is_none());
+
+        let mut out = [DataWord::from_u16(0); 32];
+        assert_eq!(rt.read(3.into(), &mut out), None);
+        let response = rt
+            .on_word(BusId::A, Word::Data(DataWord::from_u16(0xB)))
+            .unwrap();
+        assert!(!response.status().flags().message_error);
+        assert_eq!(rt.read(4.into(), &mut out), Some(1));
+
+        // Transmit Status Word reports on the message before it.
+        rt.on_word(BusId::A, receive_cmd(3, 2));
+        let tsw =
+            CommandWord::new_mode_command(RTAddr::Single(5.into()), ModeCode::TransmitStatusWord);
+        assert!(rt.on_word(BusId::A, Word::Command(tsw)).is_some());
+        assert!(rt.status().flags().message_error);
+    }
+
+    #[test]
+    fn receives_from_another_rt() {
+        let mut rt = RemoteTerminal::new(5.into());
+        let transmit = |addr: RTAddr| {
+            Word::Command(CommandWord::new_data_transfer(
+                addr,
+                RTAction::Transmit,
+                2.into(),
+                2.into(),
+            ))
+        };
+        // RT 6 sends, its status first.
+        let words = [
+            receive_cmd(3, 2),
+            transmit(RTAddr::Single(6.into())),
+            Word::Status(StatusWord::builder(RTAddr::Single(6.into())).build()),
+            Word::Data(DataWord::from_u16(0xA)),
+        ];
+        for word in words {
+            assert!(rt.on_word(BusId::A, word).is_none());
+        }
+        let response = rt
+            .on_word(BusId::A, Word::Data(DataWord::from_u16(0xB)))
+            .unwrap();
+        assert!(!response.status().flags().message_error);
+        let mut out = [DataWord::from_u16(0); 2];
+        assert_eq!(rt.read(3.into(), &mut out), Some(2));
+        assert_eq!(out[1].value(), 0xB);
+
+        // Not from itself, nor twice.
+        for second in [
+            transmit(RTAddr::Single(5.into())),
+            transmit(RTAddr::Single(6.into())),
+        ] {
+            rt.on_word(BusId::A, receive_cmd(3, 2));
+            rt.on_word(BusId::A, transmit(RTAddr::Single(6.into())));
+            rt.on_word(BusId::A, second);
+            rt.on_word(BusId::A, Word::Data(DataWord::from_u16(0xC)));
+            rt.on_word(BusId::A, Word::Data(DataWord::from_u16(0xD)));
+            assert_eq!(rt.read(3.into(), &mut out), None);
+        }
+    }
+
+    #[test]
+    fn logs_its_messages() {
+        let mut rt = RemoteTerminal::new(5.into());
+        rt.write(7.into(), &[DataWord::from_u16(0xA); 2]).unwrap();
+        rt.on_word(BusId::A, receive_cmd(3, 2));
+        rt.on_word(BusId::A, Word::Data(DataWord::from_u16(1)));
+        assert!(rt
+            .on_word(BusId::A, Word::Data(DataWord::from_u16(2)))
+            .is_some());
+        assert_eq!(rt.take_message(), None);
+
+        rt.set_logging(true);
+        let words = [
+            receive_cmd(3, 2),
+            Word::Data(DataWord::from_u16(1)),
+            Word::Data(DataWord::from_u16(2)),
+        ];
+        for word in words {
+            rt.on_word(BusId::B, word);
+        }
+        let received = rt.take_message().unwrap();
+        assert_eq!(received.format(), MessageFormat::BcToRt);
+        assert_eq!(received.bus(), BusId::B);
+        assert_eq!(received.data().len(), 2);
+        assert_eq!(received.status_words(), &[rt.status()]);
+        assert_eq!(received.validate(), Ok(()));
+        assert_eq!(rt.take_message(), None);
+
+        // Sending to RT 6 from subaddress 7.
+        let receive = CommandWord::new_data_transfer(
+            RTAddr::Single(6.into()),
+            RTAction::Receive,
+            1.into(),
+            2.into(),
+        );
+        let transmit = CommandWord::new_data_transfer(
+            RTAddr::Single(5.into()),
+            RTAction::Transmit,
+            7.into(),
+            2.into(),
+        );
+        rt.on_word(BusId::A, Word::Command(receive));
+        rt.on_word(BusId::A, Word::Command(transmit));
+        assert_eq!(rt.take_message(), None);
+        let status = StatusWord::builder(RTAddr::Single(6.into())).build();
+        rt.on_word(BusId::A, Word::Status(status));
+        let sent = rt.take_message().unwrap();
+        assert_eq!(sent.format(), MessageFormat::RtToRt);
+        assert_eq!(sent.data(), &[DataWord::from_u16(0xA); 2]);
+        assert_eq!(sent.validate(), Ok(()));
+
+        // Nothing of the messages to other RTs.
+        let other =
+            CommandWord::new_mode_command(RTAddr::Single(6.into()), ModeCode::TransmitStatusWord);
+        rt.on_word(BusId::A, Word::Command(other));
+        rt.on_word(BusId::A, Word::Status(status));
+        assert_eq!(rt.take_message(), None);
+    }
+
+    fn mode(code: ModeCode) -> Word {
+        Word::Command(CommandWord::new_mode_command(
+            RTAddr::Single(5.into()),
+            code,
+        ))
+    }
+
+    #[test]
+    fn transmit_status_and_last_command_report_previous_message() {
+        let mut rt = RemoteTerminal::new(5.into());
+        rt.on_word(BusId::A, receive_cmd(3, 1));
+        rt.on_word(BusId::A, Word::Data(DataWord::from_u16(0)));
+        rt.set_legality(LegalityTable::standard().with_subaddress(
+            Addressing::Own,
+            RTAction::Receive,
+            4,
+            0,
+        ));
+        let Word::Command(illegal) = receive_cmd(4, 1) else {
+            unreachable!()
+        };
+        rt.on_word(BusId::A, Word::Command(illegal));
+        rt.on_word(BusId::A, Word::Data(DataWord::from_u16(0)));
+
+        for _ in 0..2 {
+            let response = rt
+                .on_word(BusId::A, mode(ModeCode::TransmitLastCommand))
+                .unwrap();
+            assert!(response.status().flags().message_error);
+            assert_eq!(response.data(), &[DataWord::from_u16(illegal.value())]);
+        }
+        let response = rt
+            .on_word(BusId::A, mode(ModeCode::TransmitStatusWord))
+            .unwrap();
+        assert!(response.status().flags().message_error);
+        assert!(response.data().is_empty());
+
+        // Transmit Status Word is itself the last command now.
+        let response = rt
+            .on_word(BusId::A, mode(ModeCode::TransmitLastCommand))
+            .unwrap();
+        let Word::Command(tsw) = mode(ModeCode::TransmitStatusWord) else {
+            unreachable!()
+        };
+        assert_eq!(response.data()[0].value(), tsw.value());
+    }
+
+    #[test]
+    fn transmitter_shutdown_and_override() {
+        let mut rt = RemoteTerminal::new(5.into());
+        assert!(rt
+            .on_word(BusId::A, mode(ModeCode::TransmitterShutdown))
+            .is_some());
+        assert!(!rt.is_transmitter_enabled(BusId::B));
+        assert!(rt
+            .on_word(BusId::B, mode(ModeCode::TransmitStatusWord))
+            .is_none());
+        rt.on_word(BusId::A, mode(ModeCode::OverrideTransmitter));
+        assert!(rt
+            .on_word(BusId::B, mode(ModeCode::TransmitStatusWord))
+            .is_some());
+
+        // Selected Transmitter picks the bus from its data word.
+        rt.on_word(BusId::B, mode(ModeCode::SelectedTransmitter));
+        assert!(rt
+            .on_word(BusId::B, Word::Data(DataWord::from_u16(0)))
+            .is_some());
+        assert!(!rt.is_transmitter_enabled(BusId::A));
+        rt.on_word(BusId::B, mode(ModeCode::ResetRT));
+        assert!(rt.is_transmitter_enabled(BusId::A));
+        assert!(rt.take_events().reset);
+    }
+
+    #[test]
+    fn terminal_flag_inhibit() {
+        let mut rt = RemoteTerminal::new(5.into());
+        rt.set_terminal_flag(true);
+        let tf = |rt: &mut RemoteTerminal, code| {
+            rt.on_word(BusId::A, mode(code))
+                .unwrap()
+                .status()
+                .flags()
+                .terminal_flag
+        };
+        assert!(!tf(&mut rt, ModeCode::InhibitTerminalFlagBit));
+        assert!(!tf(&mut rt, ModeCode::TransmitStatusWord));
+        assert!(tf(&mut rt, ModeCode::OverrideInhibitTerminalFlagBit));
+    }
+
+    #[test]
+    fn mode_codes_with_data_words() {
+        let mut rt = RemoteTerminal::new(5.into());
+        rt.set_vector_word(DataWord::from_u16(0x1234));
+        rt.set_bit_word(DataWord::from_u16(0xB17));
+        let vector = rt
+            .on_word(BusId::A, mode(ModeCode::TransmitVectorWord))
+            .unwrap();
+        assert_eq!(vector.data(), &[DataWord::from_u16(0x1234)]);
+        let bit = rt
+            .on_word(BusId::A, mode(ModeCode::TransmitBITWord))
+            .unwrap();
+        assert_eq!(bit.data(), &[DataWord::from_u16(0xB17)]);
+
+        assert!(rt
+            .on_word(BusId::A, mode(ModeCode::SynchronizeWithDataWord))
+            .is_none());
+        let sync = rt
+            .on_word(BusId::A, Word::Data(DataWord::from_u16(42)))
+            .unwrap();
+        assert!(sync.data().is_empty());
+        let events = rt.take_events();
+        assert!(events.synchronize);
+        assert_eq!(events.sync_data, Some(DataWord::from_u16(42)));
+        assert_eq!(rt.take_events(), ModeEvents::default());
+    }
+
+    #[test]
+    fn dynamic_bus_control_acceptance() {
+        let mut rt = RemoteTerminal::new(5.into());
+        let dbc = rt
+            .on_word(BusId::A, mode(ModeCode::DynamicBusControl))
+            .unwrap();
+        assert!(!dbc.status().flags().dynamic_bus_control);
+        rt.set_accept_dynamic_bus_control(true);
+        let dbc = rt
+            .on_word(BusId::A, mode(ModeCode::DynamicBusControl))
+            .unwrap();
+        assert!(dbc.status().flags().dynamic_bus_control);
+        assert!(rt.take_events().dynamic_bus_control);
+    }
+
+    #[test]
+    fn broadcast_is_silent_and_reported() {
+        let mut rt = RemoteTerminal::new(5.into());
+        let bcast = Word::Command(CommandWord::new_data_transfer(
+            RTAddr::Broadcast,
+            RTAction::Receive,
+            3.into(),
+            1.into(),
+        ));
+        assert!(rt.on_word(BusId::A, bcast).is_none());
+        assert!(rt
+            .on_word(BusId::A, Word::Data(DataWord::from_u16(7)))
+            .is_none());
+        let mut out = [DataWord::from_u16(0); 32];
+        assert_eq!(rt.read(3.into(), &mut out), Some(1));
+
+        let tlc = rt
+            .on_word(BusId::A, mode(ModeCode::TransmitLastCommand))
+            .unwrap();
+        assert!(tlc.status().flags().broadcast_command);
+        let Word::Command(cmd) = bcast else {
+            unreachable!()
+        };
+        assert_eq!(tlc.data()[0].value(), cmd.value());
+        let tsw = rt
+            .on_word(BusId::A, mode(ModeCode::TransmitStatusWord))
+            .unwrap();
+        assert!(tsw.status().flags().broadcast_command);
+
+        // Any other command clears the bit.
+        let response = rt.on_word(BusId::A, receive_cmd(3, 1));
+        assert!(response.is_none());
+        let response = rt
+            .on_word(BusId::A, Word::Data(DataWord::from_u16(8)))
+            .unwrap();
+        assert!(!response.status().flags().broadcast_command);
+    }
+
+    #[test]
+    fn broadcast_mode_codes_follow_the_rules() {
+        let mut rt = RemoteTerminal::new(5.into());
+        rt.set_terminal_flag(true);
+        let bcast = |code| Word::Command(CommandWord::new_mode_command(RTAddr::Broadcast, code));
+        assert!(rt
+            .on_word(BusId::A, bcast(ModeCode::InhibitTerminalFlagBit))
+            .is_none());
+        assert!(rt.is_terminal_flag_inhibited());
+
+        // Not allowed in broadcast: illegal, and still not answered.
+        assert!(rt
+            .on_word(BusId::A, bcast(ModeCode::TransmitVectorWord))
+            .is_none());
+        let tsw = rt
+            .on_word(BusId::A, mode(ModeCode::TransmitStatusWord))
+            .unwrap();
+        assert!(tsw.status().flags().message_error);
+        assert!(tsw.status().flags().broadcast_command);
+    }
+
+    #[test]
+    fn broadcast_reception_can_be_disabled() {
+        let mut rt = RemoteTerminal::new(5.into());
+        rt.set_broadcast_enabled(false);
+        let bcast = Word::Command(CommandWord::new_data_transfer(
+            RTAddr::Broadcast,
+            RTAction::Receive,
+            3.into(),
+            1.into(),
+        ));
+        rt.on_word(BusId::A, bcast);
+        rt.on_word(BusId::A, Word::Data(DataWord::from_u16(7)));
+        let mut out = [DataWord::from_u16(0); 32];
+        assert_eq!(rt.read(3.into(), &mut out), None);
+        assert!(!rt.status().flags().broadcast_command);
+    }
+}
@@ -0,0 +1,637 @@
+//! Simulation scenarios: RTs to emulate, a BC [`Schedule`], faults to
+//! inject and how long to run, all from a text file.
+//!
+//! Besides the `frame` and `message` lines of a schedule, a scenario has:
+//!
+//! ```text
+//! # RT 5, with its busy bit up; flags are busy, service_request,
+//! # subsystem and no_broadcast.
+//! rt 5 busy
+//! # What RT 5 transmits from subaddress 2.
+//! load 5 sa=2 data=1234,5678
+//! # Commands RT 5 answers with Message Error, own or broadcast.
+//! illegal 5 rx sa=3
+//! illegal 5 mode=17 broadcast
+//! # Faults on the words the BC reads: a target (any, command, status,
+//! # data), a trigger (nth, every, random per mille) and the fault.
+//! fault status nth=3 no_response
+//! fault data random=5 parity
+//! # What the BC does about a busy RT: report (the default), repoll
+//! # with Transmit Status Word, or reschedule to a later minor frame
+//! # (`busy reschedule frames=1 times=2`), giving up after so many.
+//! busy repoll delay=50us polls=3
+//! seed 42
+//! duration 1s
+//! ```
+//!
+//! The other faults are `wrong_sync`, `drop`, `late`, `extra=<hex word>`,
+//! `wrong_address=<rt>` and `babble=<words>`. The run lasts one major frame
+//! unless a `duration` says otherwise.
+use core::str::FromStr;
+use std::vec::Vec;
+
+use crate::{
+    bus::{Bus, BusController, BusId, BusyPolicy, BusyStats},
+    fault::{Fault, FaultRule, FaultyBus, Target, Trigger},
+    keys::{parse_ns, KeyError, Tokens},
+    legality::{Addressing, LegalityTable},
+    message::Message,
+    rt::RemoteTerminal,
+    schedule::{bus_time_ns, Schedule, ScheduleErrorKind, MESSAGE_GAP_NS},
+    sim::SimBus,
+    words::{CommandWord, DataWord, ModeCode, RTAction, RTAddr},
+};
+
+/// Most `fault` lines a scenario can have.
+pub const MAX_FAULTS: usize = 8;
+
+/// Pads the fault rules: `Every(0)` never fires.
+const NO_FAULT: FaultRule = FaultRule {
+    target: Target::Any,
+    trigger: Trigger::Every(0),
+    fault: Fault::Drop,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScenarioErrorKind {
+    UnknownEntry,
+    UnknownKey,
+    Missing(&'static str),
+    BadValue(&'static str),
+    /// A second `frame`, `busy`, `seed` or `duration` line, or a second message or
+    /// RT of the same name.
+    Duplicate,
+    /// A message before the `frame` line, or no `frame` line at all.
+    NoFrame,
+    /// A `load` or `illegal` line for an RT not declared above it.
+    UnknownRt,
+    /// More than [`MAX_FAULTS`] faults.
+    TooManyFaults,
+}
+
+impl From<KeyError> for ScenarioErrorKind {
+    fn from(err: KeyError) -> Self {
+        ScheduleErrorKind::from(err).into()
+    }
+}
+
+impl From<ScheduleErrorKind> for ScenarioErrorKind {
+    fn from(kind: ScheduleErrorKind) -> Self {
+        match kind {
+            ScheduleErrorKind::UnknownEntry => ScenarioErrorKind::UnknownEntry,
+            ScheduleErrorKind::UnknownKey => ScenarioErrorKind::UnknownKey,
+            ScheduleErrorKind::Missing(key) => ScenarioErrorKind::Missing(key),
+            ScheduleErrorKind::BadValue(key) => ScenarioErrorKind::BadValue(key),
+            ScheduleErrorKind::Duplicate => ScenarioErrorKind::Duplicate,
+            ScheduleErrorKind::NoFrame => ScenarioErrorKind::NoFrame,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScenarioError {
+    /// 1-based line number, 0 for a file without a `frame` line.
+    pub line: usize,
+    pub kind: ScenarioErrorKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    rts: Vec<RemoteTerminal>,
+    schedule: Schedule,
+    faults: Vec<FaultRule>,
+    busy: Option<BusyPolicy>,
+    seed: Option<u32>,
+    duration_ns: Option<u64>,
+}
+
+/// What happened to the messages of one schedule entry.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EntryStats {
+    pub sent: u32,
+    /// Messages with [`MessageErrors`](crate::message::MessageErrors).
+    pub errors: u32,
+    pub no_response: u32,
+    /// Answered with Message Error.
+    pub message_error: u32,
+    /// Answered with Busy.
+    pub busy: u32,
+    /// Put off to a later minor frame for a busy RT.
+    pub rescheduled: u32,
+    /// Still busy once the polls or reschedules of the policy ran out.
+    pub gave_up: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    /// The messages as they went, with their bus times.
+    pub messages: Vec<Message>,
+    /// Per schedule entry.
+    pub stats: Vec<EntryStats>,
+    pub minor_frames: u32,
+    /// Minor frames whose messages ran past the start of the next one,
+    /// which then started late.
+    pub overruns: u32,
+    pub faults_injected: u32,
+    pub busy: BusyStats,
+}
+
+impl Scenario {
+    pub fn rts(&self) -> &[RemoteTerminal] {
+        &self.rts
+    }
+
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    pub fn faults(&self) -> &[FaultRule] {
+        &self.faults
+    }
+
+    pub fn busy_policy(&self) -> BusyPolicy {
+        self.busy.unwrap_or_default()
+    }
+
+    pub fn duration_ns(&self) -> u64 {
+        self.duration_ns
+            .unwrap_or_else(|| self.schedule.major_frame_ns())
+    }
+
+    /// Run the schedule against the RTs on a [`SimBus`], with the faults
+    /// injected, for the whole duration. Minor frames start on time unless
+    /// the previous one overran. Messages put off for a busy RT go after
+    /// the scheduled ones of their frame.
+    pub fn run(&self) -> Outcome {
+        let mut rts = self.rts.clone();
+        let mut rules = [NO_FAULT; MAX_FAULTS];
+        rules[..self.faults.len()].copy_from_slice(&self.faults);
+        let sim = SimBus::new(BusId::A, &mut rts);
+        let mut bus = FaultyBus::new(sim, rules, self.seed.unwrap_or(0));
+
+        let schedule = &self.schedule;
+        let mut outcome = Outcome {
+            messages: Vec::new(),
+            stats: vec![EntryStats::default(); schedule.entries().len()],
+            minor_frames: 0,
+            overruns: 0,
+            faults_injected: 0,
+            busy: BusyStats::default(),
+        };
+        let poll_ns = match self.busy_policy() {
+            BusyPolicy::Repoll { delay_ns, .. } => {
+                let poll = CommandWord::new_mode_command(
+                    RTAddr::Single(0.into()),
+                    ModeCode::TransmitStatusWord,
+                );
+                delay_ns + bus_time_ns(&Message::new(BusId::A, poll))
+            }
+            _ => 0,
+        };
+        // Minor frame count due, entry and times put off already, of the
+        // rescheduled messages.
+        let mut pending: Vec<(u32, usize, u8)> = Vec::new();
+        let mut frame_start = 0;
+        while frame_start < self.duration_ns() {
+            let frame = outcome.minor_frames % schedule.minor_frames();
+            let mut now = frame_start;
+            let entries = schedule.entries().iter().enumerate();
+            let mut due: Vec<(usize, u8)> = entries
+                .filter(|(_, entry)| entry.is_due(frame))
+                .map(|(idx, _)| (idx, 0))
+                .collect();
+            let this_frame = outcome.minor_frames;
+            due.extend(
+                pending
+                    .iter()
+                    .filter(|(at, _, _)| *at == this_frame)
+                    .map(|(_, idx, times)| (*idx, *times)),
+            );
+            pending.retain(|(at, _, _)| *at != this_frame);
+            for (idx, rescheduled) in due {
+                let request = &schedule.entries()[idx].request;
+                // Whatever is left of the last reply is over by now.
+                while bus.inner().read_next().is_ok() {}
+                let mut bc = BusController::new(&mut bus);
+                bc.set_busy_policy(self.busy_policy());
+                let handled = bc.transact_busy(request, rescheduled);
+
+                let stats = &mut outcome.stats[idx];
+                if let Some(frames) = handled.reschedule {
+                    // Past the last minor frame there is to count, it never runs.
+                    if let Some(at) = this_frame.checked_add(frames) {
+                        pending.push((at, idx, rescheduled + 1));
+                    }
+                    stats.rescheduled += 1;
+                }
+                stats.gave_up += handled.gave_up as u32;
+                let time_ns = bus_time_ns(request);
+                let mut sent = [Some((handled.message, now)), None];
+                now += time_ns + handled.polls as u64 * poll_ns;
+                if let Some(resent) = handled.resent {
+                    sent[1] = Some((resent, now));
+                    now += time_ns;
+                }
+                for (mut message, start) in sent.into_iter().flatten() {
+                    message.set_time(start, start + time_ns - MESSAGE_GAP_NS);
+                    let status = message.status_words();
+                    stats.sent += 1;
+                    stats.errors += message.errors().any() as u32;
+                    stats.no_response += message.errors().no_response as u32;
+                    stats.message_error += status.iter().any(|sw| sw.flags().message_error) as u32;
+                    stats.busy += status.iter().any(|sw| sw.flags().busy) as u32;
+                    outcome.busy.record(&message);
+                    outcome.messages.push(message);
+                }
+            }
+            outcome.minor_frames += 1;
+            let next = frame_start + schedule.minor_frame_ns();
+            if now > next {
+                outcome.overruns += 1;
+            }
+            frame_start = next.max(now);
+        }
+        outcome.faults_injected = bus.injected();
+        outcome
+    }
+
+    fn rt_mut(&mut self, addr: &str) -> Result<&mut RemoteTerminal, ScenarioErrorKind> {
+        let addr = parse_rt(addr)?;
+        self.rts
+            .iter_mut()
+            .find(|rt| rt.addr().value() == addr)
+            .ok_or(ScenarioErrorKind::UnknownRt)
+    }
+
+    fn rt_line(&mut self, addr: &str, tokens: Tokens) -> Result<(), ScenarioErrorKind> {
+        let addr = parse_rt(addr)?;
+        if self.rts.iter().any(|rt| rt.addr().value() == addr) {
+            return Err(ScenarioErrorKind::Duplicate);
+        }
+        let mut rt = RemoteTerminal::new(addr.into());
+        rt.set_busy(tokens.flag("busy"));
+        rt.set_service_request(tokens.flag("service_request"));
+        rt.set_subsystem_flag(tokens.flag("subsystem"));
+        rt.set_broadcast_enabled(!tokens.flag("no_broadcast"));
+        self.rts.push(rt);
+        Ok(())
+    }
+
+    fn load_line(&mut self, addr: &str, tokens: Tokens) -> Result<(), ScenarioErrorKind> {
+        let subaddress = data_subaddress(&tokens)?;
+        let data: Vec<DataWord> = tokens
+            .hex_list("data")?
+            .ok_or(ScenarioErrorKind::Missing("data"))?
+            .into_iter()
+            .map(DataWord::from_u16)
+            .collect();
+        self.rt_mut(addr)?
+            .write(subaddress.into(), &data)
+            .map_err(|_| ScenarioErrorKind::BadValue("data"))
+    }
+
+    fn illegal_line(&mut self, addr: &str, tokens: Tokens) -> Result<(), ScenarioErrorKind> {
+        let addressing = match tokens.flag("broadcast") {
+            true => Addressing::Broadcast,
+            false => Addressing::Own,
+        };
+        let mut table: LegalityTable = *self.rt_mut(addr)?.legality();
+        if tokens.text("mode").is_some() {
+            let code = ModeCode::from(tokens.number::<u8>("mode", None, 31)?);
+            if code == ModeCode::Invalid {
+                return Err(ScenarioErrorKind::BadValue("mode"));
+            }
+            table.set_mode_code(addressing, code, false);
+        } else {
+            let tr = match (tokens.flag("rx"), tokens.flag("tx")) {
+                (true, false) => RTAction::Receive,
+                (false, true) => RTAction::Transmit,
+                (false, false) => return Err(ScenarioErrorKind::Missing("rx")),
+                (true, true) => return Err(ScenarioErrorKind::BadValue("tx")),
+            };
+            table
+                .set_subaddress(addressing, tr, data_subaddress(&tokens)?, 0)
+                .map_err(|_| ScenarioErrorKind::BadValue("sa"))?;
+        }
+        self.rt_mut(addr)?.set_legality(table);
+        Ok(())
+    }
+
+    fn busy_line(&mut self, policy: &str, tokens: Tokens) -> Result<(), ScenarioErrorKind> {
+        if self.busy.is_some() {
+            return Err(ScenarioErrorKind::Duplicate);
+        }
+        let policy = match policy {
+            "report" => BusyPolicy::Report,
+            "repoll" => BusyPolicy::Repoll {
+                delay_ns: tokens.time_ns("delay", None)?,
+                polls: tokens.number("polls", Some(1), u8::MAX)?,
+            },
+            "reschedule" => match tokens.number("frames", Some(1), u32::MAX)? {
+                0 => return Err(ScenarioErrorKind::BadValue("frames")),
+                frames => BusyPolicy::Reschedule {
+                    frames,
+                    times: tokens.number("times", Some(1), u8::MAX)?,
+                },
+            },
+            _ => return Err(ScenarioErrorKind::BadValue("busy")),
+        };
+        self.busy = Some(policy);
+        Ok(())
+    }
+
+    fn fault_line(&mut self, tokens: Tokens) -> Result<(), ScenarioErrorKind> {
+        if self.faults.len() == MAX_FAULTS {
+            return Err(ScenarioErrorKind::TooManyFaults);
+        }
+        let targets = [
+            ("any", Target::Any),
+            ("command", Target::Command),
+            ("status", Target::Status),
+            ("data", Target::Data),
+        ];
+        let target = one_of(targets.iter().filter(|(key, _)| tokens.flag(key)), "any")?
+            .map_or(Target::Any, |(_, target)| *target);
+
+        let triggers = ["nth", "every", "random"];
+        let trigger = one_of(
+            triggers.iter().filter(|key| tokens.text(key).is_some()),
+            "nth",
+        )?;
+        let trigger = match trigger {
+            Some(&"nth") => Trigger::Nth(tokens.number("nth", None, u32::MAX)?),
+            Some(&"every") => match tokens.number("every", None, u32::MAX)? {
+                0 => return Err(ScenarioErrorKind::BadValue("every")),
+                n => Trigger::Every(n),
+            },
+            Some(_) => Trigger::Random {
+                per_mille: tokens.number("random", None, 1000)?,
+            },
+            None => return Err(ScenarioErrorKind::Missing("nth")),
+        };
+
+        let faults = [
+            "parity",
+            "wrong_sync",
+            "drop",
+            "late",
+            "no_response",
+            "extra",
+            "wrong_address",
+            "babble",
+        ];
+        let present = |key: &&'static str| tokens.flag(key) || tokens.text(key).is_some();
+        let fault = match one_of(faults.into_iter().filter(present), "parity")? {
+            Some("parity") => Fault::Parity,
+            Some("wrong_sync") => Fault::WrongSync,
+            Some("drop") => Fault::Drop,
+            Some("late") => Fault::Late,
+            Some("no_response") => Fault::NoResponse,
+            Some("extra") => {
+                let word = tokens.hex_list("extra")?.unwrap_or_default();
+                match word[..] {
+                    [word] => Fault::Extra(DataWord::from_u16(word)),
+                    _ => return Err(ScenarioErrorKind::BadValue("extra")),
+                }
+            }
+            Some("wrong_address") => {
+                Fault::WrongAddress(tokens.number::<u8>("wrong_address", None, 31)?.into())
+            }
+            Some(_) => Fault::Babble(tokens.number("babble", None, 32)?),
+            None => return Err(ScenarioErrorKind::Missing("parity")),
+        };
+        self.faults.push(FaultRule::new(target, trigger, fault));
+        Ok(())
+    }
+}
+
+/// An RT address other than broadcast.
+fn parse_rt(addr: &str) -> Result<u8, ScenarioErrorKind> {
+    addr.parse::<u8>()
+        .ok()
+        .filter(|addr| *addr < 31)
+        .ok_or(ScenarioErrorKind::BadValue("rt"))
+}
+
+fn data_subaddress(tokens: &Tokens) -> Result<u8, ScenarioErrorKind> {
+    match tokens.number::<u8>("sa", None, 30)? {
+        0 => Err(ScenarioErrorKind::BadValue("sa")),
+        sa => Ok(sa),
+    }
+}
+
+/// The only item of `items`, if any; `key` names the error for several.
+fn one_of<T>(
+    mut items: impl Iterator<Item = T>,
+    key: &'static str,
+) -> Result<Option<T>, ScenarioErrorKind> {
+    let first = items.next();
+    match items.next() {
+        Some(_) => Err(ScenarioErrorKind::BadValue(key)),
+        None => Ok(first),
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = ScenarioError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut scenario = Scenario {
+            rts: Vec::new(),
+            schedule: Schedule::UNSET,
+            faults: Vec::new(),
+            busy: None,
+            seed: None,
+            duration_ns: None,
+        };
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut tokens = line.split_whitespace();
+            let Some(entry) = tokens.next() else {
+                continue;
+            };
+            let result = match scenario.schedule.parse_line(entry, tokens.clone()) {
+                Some(result) => result.map_err(ScenarioErrorKind::from),
+                None => scenario.entry(entry, tokens),
+            };
+            result.map_err(|kind| ScenarioError {
+                line: idx + 1,
+                kind,
+            })?;
+        }
+        if !scenario.schedule.is_set() {
+            return Err(ScenarioError {
+                line: 0,
+                kind: ScenarioErrorKind::NoFrame,
+            });
+        }
+        Ok(scenario)
+    }
+}
+
+impl Scenario {
+    fn entry<'a>(
+        &mut self,
+        entry: &str,
+        mut tokens: impl Iterator<Item = &'a str>,
+    ) -> Result<(), ScenarioErrorKind> {
+        let keys: &[&str] = match entry {
+            "rt" => &["busy", "service_request", "subsystem", "no_broadcast"],
+            "load" => &["sa", "data"],
+            "illegal" => &["rx", "tx", "sa", "mode", "broadcast"],
+            "fault" => &[
+                "any",
+                "command",
+                "status",
+                "data",
+                "nth",
+                "every",
+                "random",
+                "parity",
+                "wrong_sync",
+                "drop",
+                "late",
+                "no_response",
+                "extra",
+                "wrong_address",
+                "babble",
+            ],
+            "busy" => &["delay", "polls", "frames", "times"],
+            "seed" | "duration" => &[],
+            _ => return Err(ScenarioErrorKind::UnknownEntry),
+        };
+        if entry == "fault" {
+            return self.fault_line(Tokens::new(tokens, keys)?);
+        }
+        let value = tokens.next().ok_or(ScenarioErrorKind::Missing("value"))?;
+        let tokens = Tokens::new(tokens, keys)?;
+        match entry {
+            "rt" => self.rt_line(value, tokens),
+            "load" => self.load_line(value, tokens),
+            "illegal" => self.illegal_line(value, tokens),
+            "busy" => self.busy_line(value, tokens),
+            "seed" if self.seed.is_some() => Err(ScenarioErrorKind::Duplicate),
+            "seed" => {
+                let seed = value
+                    .parse()
+                    .map_err(|_| ScenarioErrorKind::BadValue("seed"))?;
+                self.seed = Some(seed);
+                Ok(())
+            }
+            _ if self.duration_ns.is_some() => Err(ScenarioErrorKind::Duplicate),
+            _ => {
+                let duration = parse_ns(value).ok_or(ScenarioErrorKind::BadValue("duration"))?;
+                self.duration_ns = Some(duration);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{scenario::*, words::*};
+
+    const SCENARIO: &str = "
+        frame minor=1ms frames=2
+        message put rt=5 rx sa=1 wc=2 data=CAFE,F00D
+        message get rt=5 tx sa=2 wc=2 every=2 offset=1
+        message bad rt=5 rx sa=3 wc=1
+        message nobody rt=9 tx sa=1 wc=1 every=2
+        rt 5
+        load 5 sa=2 data=1234,5678
+        illegal 5 rx sa=3
+        duration 4ms
+    ";
+
+    #[test]
+    fn runs_the_schedule() {
+        let scenario: Scenario = SCENARIO.parse().unwrap();
+        let outcome = scenario.run();
+        assert_eq!(outcome.minor_frames, 4);
+        assert_eq!(outcome.overruns, 0);
+        let sent: Vec<u32> = outcome.stats.iter().map(|s| s.sent).collect();
+        assert_eq!(sent, [4, 2, 4, 2]);
+        assert_eq!(outcome.stats[2].message_error, 4);
+        assert_eq!(outcome.stats[3].no_response, 2);
+
+        let get = &outcome.messages[4];
+        // After the BC to RT message of the same minor frame.
+        assert_eq!(get.start_ns(), 1_000_000 + 4 * 20_000 + 12_000 + 4_000);
+        assert_eq!(
+            get.data(),
+            &[DataWord::from_u16(0x1234), DataWord::from_u16(0x5678)]
+        );
+    }
+
+    #[test]
+    fn relays_whichever_rt_comes_first() {
+        for rts in ["rt 6\nrt 5", "rt 5\nrt 6"] {
+            let text = std::format!(
+                "frame minor=1ms frames=1\nmessage relay rt=6 rx sa=3 wc=2 from=5.2\n\
+                 {rts}\nload 5 sa=2 data=CAFE,F00D\nduration 1ms"
+            );
+            let outcome = text.parse::<Scenario>().unwrap().run();
+            assert_eq!(outcome.stats[0].errors, 0);
+            assert_eq!(outcome.stats[0].message_error, 0);
+            let relay = &outcome.messages[0];
+            assert_eq!(relay.status_words().len(), 2);
+            assert_eq!(relay.validate(), Ok(()));
+            assert_eq!(
+                relay.data(),
+                &[DataWord::from_u16(0xCAFE), DataWord::from_u16(0xF00D)]
+            );
+        }
+    }
+
+    #[test]
+    fn injects_faults() {
+        let text = "frame minor=1ms frames=1\nmessage put rt=5 rx sa=1 wc=1\nrt 5\n\
+                    fault status every=2 no_response\nduration 4ms";
+        let outcome = text.parse::<Scenario>().unwrap().run();
+        assert_eq!(outcome.faults_injected, 2);
+        assert_eq!(outcome.stats[0].no_response, 2);
+
+        let err = |text: &str| text.parse::<Scenario>().unwrap_err();
+        assert_eq!(
+            err("frame minor=1ms frames=1\nload 5 sa=1 data=1"),
+            ScenarioError {
+                line: 2,
+                kind: ScenarioErrorKind::UnknownRt
+            }
+        );
+        assert_eq!(
+            err("frame minor=1ms frames=1\nfault nth=1 parity drop").kind,
+            ScenarioErrorKind::BadValue("parity")
+        );
+    }
+
+    #[test]
+    fn handles_busy_rts() {
+        let text = "frame minor=1ms frames=1\nmessage get rt=5 tx sa=2 wc=1\n\
+                    message put rt=5 rx sa=1 wc=1\nrt 5 busy\n";
+        let run = |busy: &str| {
+            let text = format!("{}{}\nduration 4ms", text, busy);
+            text.parse::<Scenario>().unwrap().run()
+        };
+
+        // Each frame sends its own and the busy messages of two frames ago,
+        // which are not put off again.
+        let outcome = run("busy reschedule frames=2");
+        assert_eq!(outcome.stats[0].sent, 6);
+        assert_eq!(outcome.stats[0].rescheduled, 4);
+        assert_eq!(outcome.stats[0].gave_up, 2);
+        assert_eq!(outcome.busy.count(5, 2), 6);
+        assert_eq!(outcome.busy.total(), 12);
+        let outcome = run("busy reschedule frames=1 times=3");
+        assert_eq!(outcome.stats[0].sent, 1 + 2 + 3 + 4);
+        let outcome = run("busy reschedule frames=4294967295");
+        assert_eq!(outcome.stats[0].sent, 4);
+
+        // Two polls of 100 us and a Transmit Status Word each.
+        let outcome = run("busy repoll delay=100us polls=2");
+        assert_eq!(outcome.stats[0].sent, 4);
+        assert_eq!(outcome.stats[0].gave_up, 4);
+        assert_eq!(outcome.messages[1].start_ns(), 76_000 + 2 * 156_000);
+    }
+}
@@ -0,0 +1,1010 @@
+//! BC frame schedules: the messages a BC sends in each minor frame.
+//!
+//! A major frame is a fixed number of minor frames of equal length. Each
+//! [`ScheduleEntry`] goes out once every `every` minor frames, `every`
+//! dividing the number of minor frames, starting with minor frame
+//! `offset`. Schedules are text files:
+//!
+//! ```text
+//! # Eight 10 ms minor frames.
+//! frame minor=10ms frames=8
+//! # BC to RT 5, subaddress 1, at 50 Hz in frames 1, 3, 5 and 7.
+//! message nav_state rt=5 rx sa=1 wc=2 data=0001,0002 every=2 offset=1
+//! # RT 5 to RT 6, and a broadcast mode code, once per major frame.
+//! message relay rt=6 rx sa=3 wc=4 from=5.2 every=8
+//! message sync rt=31 mode=17 data=0000 every=8 bus=B
+//! ```
+//!
+//! `rt=31` is the broadcast address, for `rx` transfers and mode codes
+//! only. RT to BC transfers are `tx`, mode codes take their T/R bit from
+//! the code, and data the BC sends defaults to zeros. Instead of `every`,
+//! `rate=<Hz>` picks the slowest harmonic period at least that fast.
+//! [`Schedule::balance`] chooses the offsets, but not of the messages
+//! marked `pin`.
+use core::fmt;
+use core::str::FromStr;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use crate::{
+    bus::BusId,
+    icd::Icd,
+    keys::{KeyError, Nanos, Tokens},
+    manchester::WORD_NS,
+    message::{Fields, Message},
+    primitives::BitField,
+    rt::RemoteTerminal,
+    words::{CommandWord, DataWord, ModeCode, RTAction, RTAddr},
+};
+
+/// Longest time an RT may take to answer, by MIL-STD-1553B.
+pub const RESPONSE_TIME_NS: u64 = 12_000;
+/// Shortest gap the BC leaves between two messages.
+pub const MESSAGE_GAP_NS: u64 = 4_000;
+/// Most minor frames in a major frame.
+pub const MAX_MINOR_FRAMES: u32 = 4096;
+
+/// Bus time of `request` when every word comes: its words, the longest
+/// response times and the gap before the next message.
+pub fn bus_time_ns(request: &Message) -> u64 {
+    let format = request.format();
+    let words = format.commands() + request.expected_data_words() + format.status_words();
+    words as u64 * WORD_NS as u64 + format.status_words() as u64 * RESPONSE_TIME_NS + MESSAGE_GAP_NS
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleEntry {
+    pub name: String,
+    /// What the BC puts on the bus: the commands, and the data if it sends
+    /// any.
+    pub request: Message,
+    /// Minor frames from one transfer to the next.
+    pub every: u32,
+    /// Minor frame of the first transfer, below `every`.
+    pub offset: u32,
+    /// Kept at its offset by [`Schedule::balance`].
+    pub pinned: bool,
+}
+
+/// Where each entry placed by [`Schedule::balance`] so far starts in each
+/// of its minor frames, behind the placed entries ahead of it.
+struct Starts {
+    minor_frames: u32,
+    /// By entry, its start in each of its frames, empty until placed.
+    starts: Vec<Vec<u64>>,
+    jitter: Vec<u64>,
+    /// Of every entry placed.
+    total_jitter: u64,
+}
+
+impl Starts {
+    fn new(minor_frames: u32, entries: usize) -> Self {
+        Self {
+            minor_frames,
+            starts: std::vec![Vec::new(); entries],
+            jitter: std::vec![0; entries],
+            total_jitter: 0,
+        }
+    }
+
+    /// Starts of entry `idx` at `offset`: the placed entries ahead of it
+    /// due in each of its frames.
+    fn own<'a>(
+        &'a self,
+        entries: &'a [ScheduleEntry],
+        times: &'a [u64],
+        idx: usize,
+        offset: u32,
+    ) -> impl Iterator<Item = u64> + 'a {
+        let frames = (offset..self.minor_frames).step_by(entries[idx].every as usize);
+        frames.map(move |frame| {
+            (0..idx)
+                .filter(|ahead| !self.starts[*ahead].is_empty() && entries[*ahead].is_due(frame))
+                .map(|ahead| times[ahead])
+                .sum()
+        })
+    }
+
+    /// The placed entries behind entry `idx` due in one of its frames at
+    /// `offset`.
+    fn behind<'a>(
+        &'a self,
+        entries: &'a [ScheduleEntry],
+        idx: usize,
+        offset: u32,
+    ) -> impl Iterator<Item = usize> + 'a {
+        let every = entries[idx].every;
+        (idx + 1..entries.len()).filter(move |behind| {
+            let entry = &entries[*behind];
+            // Frames `offset + a * every` and `entry.offset + b * entry.every`
+            // meet when the offsets agree modulo the gcd of the periods.
+            !self.starts[*behind].is_empty()
+                && offset
+                    .abs_diff(entry.offset)
+                    .is_multiple_of(gcd(every, entry.every))
+        })
+    }
+
+    /// Starts of entry `behind` with entry `idx` placed at `offset`.
+    fn pushed_back<'a>(
+        &'a self,
+        entries: &'a [ScheduleEntry],
+        times: &'a [u64],
+        (idx, offset): (usize, u32),
+        behind: usize,
+    ) -> impl Iterator<Item = u64> + 'a {
+        let every = entries[idx].every;
+        let entry = &entries[behind];
+        self.starts[behind]
+            .iter()
+            .enumerate()
+            .map(move |(n, start)| {
+                let frame = entry.offset + n as u32 * entry.every;
+                match frame % every == offset {
+                    true => start + times[idx],
+                    false => *start,
+                }
+            })
+    }
+
+    /// Jitter of every entry placed, and of entry `idx` at `offset`.
+    fn jitter_with(
+        &self,
+        entries: &[ScheduleEntry],
+        times: &[u64],
+        idx: usize,
+        offset: u32,
+    ) -> u64 {
+        let mut total = self.total_jitter + spread(self.own(entries, times, idx, offset));
+        for behind in self.behind(entries, idx, offset) {
+            let pushed = self.pushed_back(entries, times, (idx, offset), behind);
+            total = total - self.jitter[behind] + spread(pushed);
+        }
+        total
+    }
+
+    /// Place entry `idx` at its offset.
+    fn place(&mut self, entries: &[ScheduleEntry], times: &[u64], idx: usize) {
+        let offset = entries[idx].offset;
+        let behind: Vec<usize> = self.behind(entries, idx, offset).collect();
+        for behind in behind {
+            let pushed: Vec<u64> = self
+                .pushed_back(entries, times, (idx, offset), behind)
+                .collect();
+            let jitter = spread(pushed.iter().copied());
+            self.total_jitter = self.total_jitter - self.jitter[behind] + jitter;
+            self.jitter[behind] = jitter;
+            self.starts[behind] = pushed;
+        }
+        let own: Vec<u64> = self.own(entries, times, idx, offset).collect();
+        self.jitter[idx] = spread(own.iter().copied());
+        self.total_jitter += self.jitter[idx];
+        self.starts[idx] = own;
+    }
+}
+
+/// Largest minus smallest of `values`, 0 when empty.
+fn spread(values: impl Iterator<Item = u64>) -> u64 {
+    let (min, max) = values.fold((u64::MAX, 0), |(min, max), value| {
+        (min.min(value), max.max(value))
+    });
+    max.saturating_sub(min)
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+fn add_load(load: &mut [u64], entry: &ScheduleEntry, time: u64) {
+    for frame in (entry.offset as usize..load.len()).step_by(entry.every as usize) {
+        load[frame] += time;
+    }
+}
+
+impl ScheduleEntry {
+    /// Whether the entry goes out in minor frame `frame` of the major frame.
+    pub fn is_due(&self, frame: u32) -> bool {
+        frame % self.every == self.offset
+    }
+}
+
+/// A problem [`Schedule::check`] found. `entry` is an index in
+/// [`Schedule::entries`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Finding {
+    /// The messages of minor frame `frame` take longer than the frame.
+    FrameOverflow { frame: u32, load_ns: u64 },
+    /// Sent less often than the ICD asks for.
+    RateTooLow {
+        entry: usize,
+        rate_hz: f64,
+        required_hz: f64,
+    },
+    /// Broadcast of a mode code the standard does not allow in broadcast.
+    BroadcastModeCode { entry: usize, code: ModeCode },
+    /// A command asking for another word count than the ICD's.
+    WordCount {
+        entry: usize,
+        command: CommandWord,
+        icd: u8,
+    },
+    /// A command RT `rt` marks illegal.
+    IllegalCommand {
+        entry: usize,
+        command: CommandWord,
+        rt: u8,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleErrorKind {
+    UnknownEntry,
+    UnknownKey,
+    Missing(&'static str),
+    BadValue(&'static str),
+    /// A second `frame` line, or a second message of the same name.
+    Duplicate,
+    /// A message before the `frame` line, or no `frame` line at all.
+    NoFrame,
+}
+
+impl From<KeyError> for ScheduleErrorKind {
+    fn from(err: KeyError) -> Self {
+        match err {
+            KeyError::UnknownKey => ScheduleErrorKind::UnknownKey,
+            KeyError::Missing(key) => ScheduleErrorKind::Missing(key),
+            KeyError::BadValue(key) => ScheduleErrorKind::BadValue(key),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduleError {
+    /// 1-based line number, 0 for a file without a `frame` line.
+    pub line: usize,
+    pub kind: ScheduleErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    minor_frame_ns: u64,
+    minor_frames: u32,
+    entries: Vec<ScheduleEntry>,
+}
+
+impl Schedule {
+    /// An empty schedule of `minor_frames` minor frames, at least one and
+    /// at most [`MAX_MINOR_FRAMES`]. Panics if the major frame doesn't fit
+    /// a `u64` of ns.
+    pub fn new(minor_frame_ns: u64, minor_frames: u32) -> Self {
+        assert!(minor_frames > 0, "No minor frame");
+        assert!(minor_frames <= MAX_MINOR_FRAMES, "Too many minor frames");
+        assert!(
+            minor_frame_ns.checked_mul(minor_frames as u64).is_some(),
+            "Major frame too long"
+        );
+        Self {
+            minor_frame_ns,
+            minor_frames,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn minor_frame_ns(&self) -> u64 {
+        self.minor_frame_ns
+    }
+
+    pub fn minor_frames(&self) -> u32 {
+        self.minor_frames
+    }
+
+    pub fn major_frame_ns(&self) -> u64 {
+        self.minor_frame_ns * self.minor_frames as u64
+    }
+
+    pub fn entries(&self) -> &[ScheduleEntry] {
+        &self.entries
+    }
+
+    pub fn entries_mut(&mut self) -> &mut [ScheduleEntry] {
+        &mut self.entries
+    }
+
+    /// Add `entry` after the others. Panics unless `every` divides the
+    /// number of minor frames and `offset` is below it.
+    pub fn push(&mut self, entry: ScheduleEntry) {
+        assert!(
+            entry.every > 0 && self.minor_frames.is_multiple_of(entry.every),
+            "Rate not harmonic with the major frame"
+        );
+        assert!(entry.offset < entry.every, "Offset past the period");
+        self.entries.push(entry);
+    }
+
+    /// The entries sent in minor frame `frame`, in order.
+    pub fn frame(&self, frame: u32) -> impl Iterator<Item = &ScheduleEntry> {
+        self.entries.iter().filter(move |e| e.is_due(frame))
+    }
+
+    /// Bus time the messages of minor frame `frame` take, see
+    /// [`bus_time_ns`].
+    pub fn frame_load_ns(&self, frame: u32) -> u64 {
+        self.frame(frame).map(|e| bus_time_ns(&e.request)).sum()
+    }
+
+    /// Load of the busiest minor frame.
+    pub fn peak_load_ns(&self) -> u64 {
+        (0..self.minor_frames)
+            .map(|frame| self.frame_load_ns(frame))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// How much the start of entry `entry` in its minor frame varies from
+    /// one transfer to the next.
+    pub fn jitter_ns(&self, entry: usize) -> u64 {
+        let every = self.entries[entry].every;
+        let starts = (self.entries[entry].offset..self.minor_frames)
+            .step_by(every as usize)
+            .map(|frame| {
+                self.entries[..entry]
+                    .iter()
+                    .filter(|e| e.is_due(frame))
+                    .map(|e| bus_time_ns(&e.request))
+                    .sum::<u64>()
+            });
+        spread(starts)
+    }
+
+    /// Spread the entries over the minor frames: choose the offset of each
+    /// entry not pinned so that the busiest frame is as light as it can be
+    /// made, and then so that the messages jitter the least, placing the
+    /// fastest and then the longest messages first.
+    ///
+    /// This reorders the entries not pinned, fastest first, in the places
+    /// they leave between the pinned ones, which keep theirs. With harmonic
+    /// periods every message then starts at the same time in each of its
+    /// frames. Periods that don't divide one another, such as 2 and 3 minor
+    /// frames, always leave some jitter.
+    pub fn balance(&mut self) {
+        let slots: Vec<usize> = (0..self.entries.len())
+            .filter(|idx| !self.entries[*idx].pinned)
+            .collect();
+        let mut free: Vec<ScheduleEntry> =
+            slots.iter().map(|idx| self.entries[*idx].clone()).collect();
+        free.sort_by_key(|e| e.every);
+        for (idx, entry) in slots.iter().zip(free) {
+            self.entries[*idx] = entry;
+        }
+
+        let times: Vec<u64> = self
+            .entries
+            .iter()
+            .map(|e| bus_time_ns(&e.request))
+            .collect();
+        let mut load = std::vec![0; self.minor_frames as usize];
+        let mut starts = Starts::new(self.minor_frames, self.entries.len());
+        for idx in 0..self.entries.len() {
+            if self.entries[idx].pinned {
+                starts.place(&self.entries, &times, idx);
+                add_load(&mut load, &self.entries[idx], times[idx]);
+            }
+        }
+        let mut order = slots;
+        order.sort_by_key(|idx| (self.entries[*idx].every, core::cmp::Reverse(times[*idx])));
+        for idx in order {
+            let every = self.entries[idx].every;
+            // Lowest peak, then the least jitter among the entries placed so
+            // far, then the least loaded frames overall.
+            let cost = |offset: u32| {
+                let frames = (offset as usize..load.len()).step_by(every as usize);
+                let peak = frames.clone().map(|frame| load[frame] + times[idx]).max();
+                let total: u64 = frames.map(|frame| load[frame]).sum();
+                let jitter = starts.jitter_with(&self.entries, &times, idx, offset);
+                (peak, jitter, total)
+            };
+            let offset = (0..every).min_by_key(|offset| cost(*offset)).unwrap_or(0);
+            self.entries[idx].offset = offset;
+            starts.place(&self.entries, &times, idx);
+            add_load(&mut load, &self.entries[idx], times[idx]);
+        }
+    }
+
+    /// Rate of `entry`, in Hz.
+    pub fn rate_hz(&self, entry: &ScheduleEntry) -> f64 {
+        1e9 / (entry.every as u64 * self.minor_frame_ns) as f64
+    }
+
+    /// Check the schedule: minor frame loads, and each message against the
+    /// standard, the ICD if any, and the legality tables of `rts`.
+    /// Broadcast commands are checked against every RT of `rts`.
+    pub fn check(&self, icd: Option<&Icd>, rts: &[RemoteTerminal]) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for frame in 0..self.minor_frames {
+            let load_ns = self.frame_load_ns(frame);
+            if load_ns > self.minor_frame_ns {
+                findings.push(Finding::FrameOverflow { frame, load_ns });
+            }
+        }
+        for (idx, entry) in self.entries.iter().enumerate() {
+            self.check_entry(idx, entry, icd, rts, &mut findings);
+        }
+        findings
+    }
+
+    fn check_entry(
+        &self,
+        idx: usize,
+        entry: &ScheduleEntry,
+        icd: Option<&Icd>,
+        rts: &[RemoteTerminal],
+        findings: &mut Vec<Finding>,
+    ) {
+        let format = entry.request.format();
+        if format.is_mode_command() && format.is_broadcast() {
+            let code = ModeCode::from(Fields::of(&entry.request.command()).count);
+            if code != ModeCode::Invalid && !code.associated_options().broadcast_allowed {
+                findings.push(Finding::BroadcastModeCode { entry: idx, code });
+            }
+        }
+        for command in entry.request.commands() {
+            let fields = Fields::of(command);
+            let icd_message = icd
+                .and_then(|icd| icd.find_command(command).map(|m| &icd.messages()[m]))
+                .filter(|_| !fields.mode);
+            if let Some(message) = icd_message {
+                if fields.data_words() != message.word_count {
+                    findings.push(Finding::WordCount {
+                        entry: idx,
+                        command: *command,
+                        icd: message.word_count,
+                    });
+                }
+                let rate_hz = self.rate_hz(entry);
+                match message.rate_hz {
+                    Some(required_hz) if rate_hz < required_hz => {
+                        findings.push(Finding::RateTooLow {
+                            entry: idx,
+                            rate_hz,
+                            required_hz,
+                        })
+                    }
+                    _ => {}
+                }
+            }
+            let targets = rts
+                .iter()
+                .filter(|rt| fields.broadcast() || rt.addr().value() == fields.addr);
+            for rt in targets {
+                if !rt.legality().is_legal(command) {
+                    findings.push(Finding::IllegalCommand {
+                        entry: idx,
+                        command: *command,
+                        rt: rt.addr().value(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Read one line of a schedule file; `None` if `entry` is not a
+    /// schedule entry. A fresh schedule for the parser has no minor frame.
+    pub(crate) fn parse_line<'a>(
+        &mut self,
+        entry: &str,
+        mut tokens: impl Iterator<Item = &'a str>,
+    ) -> Option<Result<(), ScheduleErrorKind>> {
+        let result = match entry {
+            "frame" if self.minor_frames != 0 => Err(ScheduleErrorKind::Duplicate),
+            "frame" => Tokens::new(tokens, &["minor", "frames"])
+                .map_err(ScheduleErrorKind::from)
+                .and_then(|tokens| self.frame_line(tokens)),
+            "message" if self.minor_frames == 0 => Err(ScheduleErrorKind::NoFrame),
+            "message" => match tokens.next() {
+                None => Err(ScheduleErrorKind::Missing("name")),
+                Some(name) => Tokens::new(
+                    tokens,
+                    &[
+                        "rt", "rx", "tx", "sa", "wc", "from", "mode", "data", "bus", "every",
+                        "rate", "offset", "pin",
+                    ],
+                )
+                .map_err(ScheduleErrorKind::from)
+                .and_then(|tokens| self.message_line(name, tokens)),
+            },
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    pub(crate) const UNSET: Self = Self {
+        minor_frame_ns: 0,
+        minor_frames: 0,
+        entries: Vec::new(),
+    };
+
+    pub(crate) fn is_set(&self) -> bool {
+        self.minor_frames != 0
+    }
+
+    /// The longest period dividing the major frame that is at least
+    /// `rate_hz` fast.
+    fn every_for(&self, rate_hz: f64) -> Option<u32> {
+        (1..=self.minor_frames)
+            .rev()
+            .filter(|every| self.minor_frames.is_multiple_of(*every))
+            .find(|every| 1e9 / (*every as u64 * self.minor_frame_ns) as f64 >= rate_hz)
+    }
+
+    fn frame_line(&mut self, tokens: Tokens) -> Result<(), ScheduleErrorKind> {
+        let minor_frame_ns = tokens.time_ns("minor", None)?;
+        let minor_frames = tokens.number("frames", None, MAX_MINOR_FRAMES)?;
+        if minor_frame_ns == 0 || minor_frame_ns.checked_mul(minor_frames as u64).is_none() {
+            return Err(ScheduleErrorKind::BadValue("minor"));
+        }
+        if minor_frames == 0 {
+            return Err(ScheduleErrorKind::BadValue("frames"));
+        }
+        self.minor_frame_ns = minor_frame_ns;
+        self.minor_frames = minor_frames;
+        Ok(())
+    }
+
+    fn message_line(&mut self, name: &str, tokens: Tokens) -> Result<(), ScheduleErrorKind> {
+        if self.entries.iter().any(|e| e.name == name) {
+            return Err(ScheduleErrorKind::Duplicate);
+        }
+        let bus = match tokens.text("bus") {
+            None | Some("A") => BusId::A,
+            Some("B") => BusId::B,
+            Some(_) => return Err(ScheduleErrorKind::BadValue("bus")),
+        };
+        let addr = RTAddr::from(BitField::new(tokens.number("rt", None, 31)?));
+        let data = tokens.hex_list("data")?;
+        let (mut request, sent) = match tokens.text("mode") {
+            Some(_) => mode_request(bus, addr, &tokens)?,
+            None => transfer_request(bus, addr, &tokens)?,
+        };
+        match (data, sent) {
+            (None, _) => {}
+            (Some(data), Some(len)) if data.len() == len => {
+                for value in data {
+                    let _ = request.push_data(DataWord::from_u16(value));
+                }
+            }
+            (Some(_), _) => return Err(ScheduleErrorKind::BadValue("data")),
+        }
+        // The BC sends zeros unless told otherwise.
+        while request.data().len() < sent.unwrap_or(0) {
+            let _ = request.push_data(DataWord::from_u16(0));
+        }
+
+        let every = match tokens.text("rate") {
+            Some(_) if tokens.text("every").is_some() => {
+                return Err(ScheduleErrorKind::BadValue("rate"))
+            }
+            Some(_) => self
+                .every_for(tokens.number("rate", None, f64::MAX)?)
+                .ok_or(ScheduleErrorKind::BadValue("rate"))?,
+            None => tokens.number("every", Some(1), self.minor_frames)?,
+        };
+        if every == 0 || !self.minor_frames.is_multiple_of(every) {
+            return Err(ScheduleErrorKind::BadValue("every"));
+        }
+        self.push(ScheduleEntry {
+            name: name.to_string(),
+            request,
+            every,
+            offset: tokens.number("offset", Some(0), every - 1)?,
+            pinned: tokens.flag("pin"),
+        });
+        Ok(())
+    }
+}
+
+/// A mode command, and how many data words the BC sends with it.
+fn mode_request(
+    bus: BusId,
+    addr: RTAddr,
+    tokens: &Tokens,
+) -> Result<(Message, Option<usize>), ScheduleErrorKind> {
+    let code = ModeCode::from(tokens.number::<u8>("mode", None, 31)?);
+    if code == ModeCode::Invalid {
+        return Err(ScheduleErrorKind::BadValue("mode"));
+    }
+    if ["rx", "tx"].iter().any(|key| tokens.flag(key))
+        || ["sa", "wc", "from"]
+            .iter()
+            .any(|key| tokens.text(key).is_some())
+    {
+        return Err(ScheduleErrorKind::BadValue("mode"));
+    }
+    let options = code.associated_options();
+    let sent = options.requires_data_word && options.tr == RTAction::Receive;
+    let request = Message::new(bus, CommandWord::new_mode_command(addr, code));
+    Ok((request, sent.then_some(1)))
+}
+
+/// A data transfer, and how many data words the BC sends with it.
+fn transfer_request(
+    bus: BusId,
+    addr: RTAddr,
+    tokens: &Tokens,
+) -> Result<(Message, Option<usize>), ScheduleErrorKind> {
+    let action = match (tokens.flag("rx"), tokens.flag("tx")) {
+        (true, false) => RTAction::Receive,
+        (false, true) => RTAction::Transmit,
+        (false, false) => return Err(ScheduleErrorKind::Missing("rx")),
+        (true, true) => return Err(ScheduleErrorKind::BadValue("tx")),
+    };
+    // No RT answers a broadcast transmit command.
+    if addr == RTAddr::Broadcast && action == RTAction::Transmit {
+        return Err(ScheduleErrorKind::BadValue("tx"));
+    }
+    let subaddress = tokens.number::<u8>("sa", None, 30)?;
+    if subaddress == 0 {
+        return Err(ScheduleErrorKind::BadValue("sa"));
+    }
+    let word_count = tokens.number::<u8>("wc", None, 32)?;
+    if word_count == 0 {
+        return Err(ScheduleErrorKind::BadValue("wc"));
+    }
+    let field = BitField::new(word_count % 32);
+    let command = CommandWord::new_data_transfer(addr, action, subaddress.into(), field);
+    let Some(from) = tokens.text("from") else {
+        let request = Message::new(bus, command);
+        let sent = action == RTAction::Receive;
+        return Ok((request, sent.then_some(word_count as usize)));
+    };
+
+    // RT to RT: `from=<rt>.<subaddress>` of the transmitter.
+    let bad = ScheduleErrorKind::BadValue("from");
+    let (rt, sa) = from.split_once('.').ok_or(bad)?;
+    let rt = rt.parse::<u8>().ok().filter(|rt| *rt < 31).ok_or(bad)?;
+    let sa = sa
+        .parse::<u8>()
+        .ok()
+        .filter(|sa| (1..31).contains(sa))
+        .ok_or(bad)?;
+    if action != RTAction::Receive {
+        return Err(bad);
+    }
+    let transmit = CommandWord::new_data_transfer(
+        RTAddr::Single(rt.into()),
+        RTAction::Transmit,
+        sa.into(),
+        field,
+    );
+    Ok((Message::rt_to_rt(bus, command, transmit), None))
+}
+
+impl FromStr for Schedule {
+    type Err = ScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut schedule = Schedule::UNSET;
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut tokens = line.split_whitespace();
+            let Some(entry) = tokens.next() else {
+                continue;
+            };
+            let result = schedule
+                .parse_line(entry, tokens)
+                .unwrap_or(Err(ScheduleErrorKind::UnknownEntry));
+            result.map_err(|kind| ScheduleError {
+                line: idx + 1,
+                kind,
+            })?;
+        }
+        if !schedule.is_set() {
+            return Err(ScheduleError {
+                line: 0,
+                kind: ScheduleErrorKind::NoFrame,
+            });
+        }
+        Ok(schedule)
+    }
+}
+
+impl fmt::Display for ScheduleEntry {
+    /// The `message` line of the entry.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let request = &self.request;
+        let fields = Fields::of(&request.command());
+        write!(f, "message {} rt={}", self.name, fields.addr)?;
+        let subaddress = (request.command().value() >> 5) & 0x1F;
+        if fields.mode {
+            write!(f, " mode={}", fields.count)?;
+        } else {
+            let action = if fields.transmit { "tx" } else { "rx" };
+            let wc = fields.data_words();
+            write!(f, " {} sa={} wc={}", action, subaddress, wc)?;
+        }
+        if let Some(transmit) = request.transmit_command() {
+            let from = Fields::of(&transmit);
+            let subaddress = (transmit.value() >> 5) & 0x1F;
+            write!(f, " from={}.{}", from.addr, subaddress)?;
+        }
+        // Zeros are the default.
+        let data = match request.data().iter().all(|dw| dw.value() == 0) {
+            true => &[][..],
+            false => request.data(),
+        };
+        for (idx, dw) in data.iter().enumerate() {
+            let sep = if idx == 0 { " data=" } else { "," };
+            write!(f, "{}{:04X}", sep, dw.value())?;
+        }
+        if request.bus() == BusId::B {
+            write!(f, " bus=B")?;
+        }
+        if self.every != 1 {
+            write!(f, " every={}", self.every)?;
+        }
+        if self.offset != 0 {
+            write!(f, " offset={}", self.offset)?;
+        }
+        if self.pinned {
+            write!(f, " pin")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Schedule {
+    /// The schedule as a file [`Schedule::from_str`] reads back.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "frame minor={} frames={}",
+            Nanos(self.minor_frame_ns),
+            self.minor_frames
+        )?;
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{icd::*, legality::*, message::*, rt::*, schedule::*, words::*};
+
+    const SCHEDULE: &str = "
+        frame minor=10ms frames=8
+        message nav_state rt=5 rx sa=1 wc=2 data=0001,0002 every=2 offset=1
+        message relay rt=6 rx sa=3 wc=4 from=5.2 every=8
+        message sync rt=31 mode=17 data=0000 every=8 bus=B
+        message status rt=5 tx sa=2 wc=32
+    ";
+
+    #[test]
+    fn parses_and_prints_back() {
+        let schedule: Schedule = SCHEDULE.parse().unwrap();
+        assert_eq!(schedule.major_frame_ns(), 80_000_000);
+        let formats: Vec<_> = schedule
+            .entries()
+            .iter()
+            .map(|e| e.request.format())
+            .collect();
+        assert_eq!(
+            formats,
+            [
+                MessageFormat::BcToRt,
+                MessageFormat::RtToRt,
+                MessageFormat::BroadcastModeWithData,
+                MessageFormat::RtToBc,
+            ]
+        );
+        assert_eq!(schedule.frame(0).count(), 3);
+        assert_eq!(schedule.frame(3).count(), 2);
+        // 2 commands, 4 data words, 2 status words, 2 response times, a gap.
+        let relay = &schedule.entries()[1].request;
+        assert_eq!(bus_time_ns(relay), 8 * 20_000 + 2 * 12_000 + 4_000);
+
+        let printed = schedule.to_string();
+        assert!(printed.contains("message status rt=5 tx sa=2 wc=32\n"));
+        assert_eq!(printed.parse::<Schedule>(), Ok(schedule));
+    }
+
+    #[test]
+    fn finds_problems() {
+        let schedule: Schedule = "
+            frame minor=125us frames=4
+            message nav_state rt=5 rx sa=1 wc=3 every=2
+            message poll rt=31 mode=2 every=4
+            message fill rt=6 tx sa=2 wc=1 every=4 offset=3
+        "
+        .parse()
+        .unwrap();
+        let icd: Icd = "message nav_state rt=5 rx sa=1 wc=2 rate=10000"
+            .parse()
+            .unwrap();
+        let mut rt = RemoteTerminal::new(6.into());
+        let mut legality = *rt.legality();
+        legality
+            .set_subaddress(Addressing::Own, RTAction::Transmit, 2, 0)
+            .unwrap();
+        rt.set_legality(legality);
+
+        let nav = schedule.entries()[0].request.command();
+        assert_eq!(
+            schedule.check(Some(&icd), &[rt]),
+            [
+                // 5 words and a response for nav_state, 1 word for poll.
+                Finding::FrameOverflow {
+                    frame: 0,
+                    load_ns: 5 * 20_000 + 12_000 + 4_000 + 20_000 + 4_000,
+                },
+                Finding::WordCount {
+                    entry: 0,
+                    command: nav,
+                    icd: 2
+                },
+                Finding::RateTooLow {
+                    entry: 0,
+                    rate_hz: 4000.0,
+                    required_hz: 10000.0
+                },
+                Finding::BroadcastModeCode {
+                    entry: 1,
+                    code: ModeCode::TransmitStatusWord
+                },
+                // Which no RT accepts either.
+                Finding::IllegalCommand {
+                    entry: 1,
+                    command: schedule.entries()[1].request.command(),
+                    rt: 6
+                },
+                Finding::IllegalCommand {
+                    entry: 2,
+                    command: schedule.entries()[2].request.command(),
+                    rt: 6
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn balances_the_frames() {
+        let mut schedule: Schedule = "
+            frame minor=1ms frames=4
+            message slow_a rt=1 tx sa=1 wc=32 rate=250
+            message slow_b rt=2 tx sa=1 wc=32 every=4
+            message half_a rt=3 tx sa=1 wc=16 rate=500
+            message fast rt=4 rx sa=1 wc=1 rate=1000
+            message half_b rt=5 tx sa=1 wc=16 every=2
+            message fixed rt=6 rx sa=1 wc=8 every=4 offset=1 pin
+        "
+        .parse()
+        .unwrap();
+        let every: Vec<u32> = schedule.entries().iter().map(|e| e.every).collect();
+        assert_eq!(every, [4, 4, 2, 1, 2, 4]);
+        let before = schedule.peak_load_ns();
+
+        schedule.balance();
+        assert!(schedule.peak_load_ns() < before);
+        let names: Vec<&str> = schedule.entries().iter().map(|e| &e.name[..]).collect();
+        assert_eq!(names[0], "fast");
+        let fixed = schedule.entries().iter().find(|e| e.name == "fixed");
+        assert_eq!(fixed.unwrap().offset, 1);
+        // One half rate message in each pair of frames, and the two slow
+        // ones kept apart from each other and from the pinned one.
+        let offset = |name: &str| {
+            let entry = schedule.entries().iter().find(|e| e.name == name);
+            entry.unwrap().offset
+        };
+        assert_ne!(offset("half_a"), offset("half_b"));
+        let mut slow = [offset("slow_a"), offset("slow_b"), 1];
+        slow.sort();
+        assert!(slow.windows(2).all(|pair| pair[0] != pair[1]));
+        for entry in 0..schedule.entries().len() {
+            assert_eq!(schedule.jitter_ns(entry), 0);
+        }
+    }
+
+    #[test]
+    fn balances_a_full_size_schedule() {
+        // 220 messages over 64 minor frames, a few of them pinned.
+        let mut text = String::from("frame minor=10ms frames=64\n");
+        for idx in 0..220 {
+            let every = [64, 32, 16, 8, 4, 2, 1][idx % 7];
+            let (rt, sa, wc) = (idx % 30 + 1, idx % 29 + 1, idx % 32 + 1);
+            text += &std::format!("message m{idx} rt={rt} tx sa={sa} wc={wc} every={every}");
+            text += if idx % 50 == 0 { " pin\n" } else { "\n" };
+        }
+        let mut schedule: Schedule = text.parse().unwrap();
+        let before = schedule.peak_load_ns();
+        let pinned: Vec<usize> = (0..220).filter(|idx| idx % 50 == 0).collect();
+
+        schedule.balance();
+        assert!(schedule.peak_load_ns() < before);
+        for idx in pinned {
+            assert_eq!(schedule.entries()[idx].name, std::format!("m{idx}"));
+        }
+        // Harmonic periods: the messages not pinned jitter only behind the
+        // slower pinned ones.
+        for (idx, entry) in schedule.entries().iter().enumerate() {
+            let pinned_ahead: u64 = schedule.entries()[..idx]
+                .iter()
+                .filter(|ahead| ahead.pinned && ahead.every > entry.every)
+                .map(|ahead| bus_time_ns(&ahead.request))
+                .sum();
+            assert!(entry.pinned || schedule.jitter_ns(idx) <= pinned_ahead);
+        }
+    }
+
+    #[test]
+    fn balances_periods_that_do_not_nest() {
+        let mut schedule: Schedule = "
+            frame minor=1ms frames=6
+            message third rt=1 tx sa=1 wc=8 every=3 pin
+            message long rt=2 tx sa=1 wc=16 every=2
+            message short rt=3 tx sa=1 wc=4 every=2
+            message other rt=4 tx sa=1 wc=8 every=3
+        "
+        .parse()
+        .unwrap();
+        schedule.balance();
+        let names: Vec<&str> = schedule.entries().iter().map(|e| &e.name[..]).collect();
+        assert_eq!(names, ["third", "long", "short", "other"]);
+        assert_eq!(schedule.entries()[0].offset, 0);
+
+        // No choice of offsets does better, first on the peak load and then
+        // on the jitter.
+        let cost = |schedule: &Schedule| {
+            let jitter: u64 = (0..4).map(|entry| schedule.jitter_ns(entry)).sum();
+            (schedule.peak_load_ns(), jitter)
+        };
+        let balanced = cost(&schedule);
+        let mut other = schedule.clone();
+        for offsets in 0..12 {
+            other.entries_mut()[1].offset = offsets % 2;
+            other.entries_mut()[2].offset = offsets / 2 % 2;
+            other.entries_mut()[3].offset = offsets / 4;
+            assert!(cost(&other) >= balanced);
+        }
+        // Periods 2 and 3 always leave some.
+        assert!(balanced.1 > 0);
+    }
+
+    #[test]
+    fn reports_the_bad_line() {
+        let err = |text: &str| text.parse::<Schedule>().unwrap_err();
+        assert_eq!(
+            err("message a rt=5 rx sa=1 wc=2"),
+            ScheduleError {
+                line: 1,
+                kind: ScheduleErrorKind::NoFrame
+            }
+        );
+        assert_eq!(
+            err("frame minor=1ms frames=4\nmessage a rt=5 rx sa=1 wc=2 every=3").kind,
+            ScheduleErrorKind::BadValue("every")
+        );
+        assert_eq!(
+            err("frame minor=1ms frames=4\nmessage a rt=5 rx sa=1 wc=2 data=1").kind,
+            ScheduleErrorKind::BadValue("data")
+        );
+        assert_eq!(
+            err("frame minor=1ms frames=4\nmessage a rt=5 mode=9").kind,
+            ScheduleErrorKind::BadValue("mode")
+        );
+        assert_eq!(
+            err("frame minor=10s frames=4294967295").kind,
+            ScheduleErrorKind::BadValue("frames")
+        );
+        assert_eq!(
+            err("frame minor=18446744073709551615ns frames=2").kind,
+            ScheduleErrorKind::BadValue("minor")
+        );
+        assert_eq!(
+            err("frame minor=1ms frames=4\nmessage a rt=31 tx sa=1 wc=2").kind,
+            ScheduleErrorKind::BadValue("tx")
+        );
+    }
+}
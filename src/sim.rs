@@ -0,0 +1,319 @@
+use crate::{
+    bus::{Bus, BusError, BusId},
+    queue::WordQueue,
+    rt::RemoteTerminal,
+    words::Word,
+};
+
+/// Words a SimBus can hold back for reading: the longest reply is a status
+/// word and 32 data words, with room to spare.
+pub const SIM_QUEUE_LEN: usize = 64;
+
+/// In-process bus connecting a BusController to a set of RemoteTerminals.
+/// Every word written is seen by every RT, and their replies are queued for
+/// `read_next`, which times out (`NoResponse`) once the queue is empty.
+pub struct SimBus<'a> {
+    id: BusId,
+    rts: &'a mut [RemoteTerminal],
+    replies: WordQueue<SIM_QUEUE_LEN>,
+}
+
+impl<'a> SimBus<'a> {
+    pub fn new(id: BusId, rts: &'a mut [RemoteTerminal]) -> Self {
+        Self {
+            id,
+            rts,
+            replies: WordQueue::new(),
+        }
+    }
+
+    pub fn id(&self) -> BusId {
+        self.id
+    }
+
+    /// Switch the BC to the other bus of the pair.
+    pub fn set_id(&mut self, id: BusId) {
+        self.id = id;
+    }
+
+    pub fn rts(&mut self) -> &mut [RemoteTerminal] {
+        self.rts
+    }
+
+    /// The RT at `addr`, if one is connected.
+    pub fn rt(&mut self, addr: u8) -> Option<&mut RemoteTerminal> {
+        self.rts.iter_mut().find(|rt| rt.addr().value() == addr)
+    }
+}
+
+impl SimBus<'_> {
+    /// Put `word` in front of every RT but its sender, queueing the replies,
+    /// which the other RTs hear in turn: the receiver of an RT to RT
+    /// transfer answers the transmitter's data.
+    fn deliver(&mut self, sender: Option<usize>, word: Word) {
+        for idx in 0..self.rts.len() {
+            if sender == Some(idx) {
+                continue;
+            }
+            let Some(response) = self.rts[idx].on_word(self.id, word) else {
+                continue;
+            };
+            let reply = core::iter::once(Word::Status(response.status()))
+                .chain(response.data().iter().map(|dw| Word::Data(*dw)));
+            for word in reply {
+                let _ = self.replies.push(word);
+                self.deliver(Some(idx), word);
+            }
+        }
+    }
+}
+
+impl Bus for SimBus<'_> {
+    fn write_word(&mut self, value: Word) {
+        self.deliver(None, value);
+    }
+
+    fn read_next(&mut self) -> Result<Word, BusError> {
+        self.replies.pop().ok_or(BusError::NoResponse)
+    }
+
+    fn select_bus(&mut self, id: BusId) {
+        self.id = id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{bus::*, message::*, sim::*, words::*};
+
+    /// A SimBus on which RT 3 stops being busy after `busy_ns` idle.
+    struct Clearing<'a> {
+        sim: SimBus<'a>,
+        busy_ns: u64,
+    }
+
+    impl Bus for Clearing<'_> {
+        fn write_word(&mut self, value: Word) {
+            self.sim.write_word(value)
+        }
+
+        fn read_next(&mut self) -> Result<Word, BusError> {
+            self.sim.read_next()
+        }
+
+        fn idle(&mut self, ns: u64) {
+            self.busy_ns = self.busy_ns.saturating_sub(ns);
+            if self.busy_ns == 0 {
+                self.sim.rt(3).unwrap().set_busy(false);
+            }
+        }
+    }
+
+    #[test]
+    fn bc_talks_to_rts() {
+        let mut rts = [RemoteTerminal::new(3.into()), RemoteTerminal::new(4.into())];
+        let mut bus = SimBus::new(BusId::A, &mut rts);
+        let mut bc = BusController::new(&mut bus);
+
+        let data = [DataWord::from_u16(0xCAFE), DataWord::from_u16(0xF00D)];
+        let sw = bc
+            .send_transfer(RTAddr::Single(4.into()), 2.into(), &data)
+            .unwrap()
+            .unwrap();
+        assert_eq!(sw.get_rt_addr(), RTAddr::Single(4.into()));
+
+        let mut vector = DataWord::from_u16(0);
+        bc.send_mode_command(
+            RTAddr::Single(3.into()),
+            ModeCode::TransmitVectorWord,
+            Some(&mut vector),
+        )
+        .unwrap();
+
+        // A busy RT sends no vector word, and none is waited for.
+        bus.rt(3)
+            .unwrap()
+            .set_vector_word(DataWord::from_u16(0x0BAD));
+        bus.rt(3).unwrap().set_busy(true);
+        let mut bc = BusController::new(&mut bus);
+        let sw = bc
+            .send_mode_command(
+                RTAddr::Single(3.into()),
+                ModeCode::TransmitVectorWord,
+                Some(&mut vector),
+            )
+            .unwrap()
+            .unwrap();
+        assert!(sw.flags().busy);
+        assert_eq!(vector, DataWord::from_u16(0));
+        assert_eq!(bus.read_next(), Err(BusError::NoResponse));
+        bus.rt(3).unwrap().set_busy(false);
+        let mut bc = BusController::new(&mut bus);
+        assert_eq!(
+            bc.send_transfer(RTAddr::Broadcast, 2.into(), &data),
+            Ok(None)
+        );
+        assert_eq!(
+            bc.send_transfer(RTAddr::Single(9.into()), 2.into(), &data),
+            Err(TransferError::NoResponse)
+        );
+
+        let mut out = [DataWord::from_u16(0); 2];
+        bus.rt(3).unwrap().read(2.into(), &mut out).unwrap();
+        assert_eq!(out, data);
+        bus.rt(3).unwrap().write(5.into(), &data).unwrap();
+        let mut bc = BusController::new(&mut bus);
+        let mut out = [DataWord::from_u16(0); 2];
+        bc.receive_transfer(RTAddr::Single(3.into()), 5.into(), &mut out)
+            .unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn relays_rt_to_rt() {
+        // Whichever of the two RTs hears each word first.
+        for receiver_first in [false, true] {
+            let mut rts = [RemoteTerminal::new(3.into()), RemoteTerminal::new(4.into())];
+            let data = [DataWord::from_u16(0xCAFE), DataWord::from_u16(0xF00D)];
+            rts[0].write(2.into(), &data).unwrap();
+            if receiver_first {
+                rts.reverse();
+            }
+            let mut bus = SimBus::new(BusId::A, &mut rts);
+
+            // RT 4 receives at subaddress 3 what RT 3 transmits from 2.
+            bus.write_word(Word::Command(CommandWord::from_u16(0x2062)));
+            bus.write_word(Word::Command(CommandWord::from_u16(0x1C42)));
+            let replies = [(); 4].map(|_| bus.read_next().unwrap());
+            assert_eq!(replies[0], Word::Status(StatusWord::from_u16(0x1800)));
+            assert_eq!(replies[1..3], data.map(Word::Data));
+            assert_eq!(replies[3], Word::Status(StatusWord::from_u16(0x2000)));
+            assert_eq!(bus.read_next(), Err(BusError::NoResponse));
+
+            let mut out = [DataWord::from_u16(0); 2];
+            bus.rt(4).unwrap().read(3.into(), &mut out).unwrap();
+            assert_eq!(out, data);
+        }
+    }
+
+    #[test]
+    fn bc_runs_whole_messages() {
+        let mut rts = [RemoteTerminal::new(3.into())];
+        let mut bus = SimBus::new(BusId::A, &mut rts);
+        let mut bc = BusController::new(&mut bus);
+
+        // BC to RT 3, subaddress 2, two words.
+        let mut request = Message::new(BusId::A, CommandWord::from_u16(0x1842));
+        for value in [0xCAFE, 0xF00D] {
+            request.push_data(DataWord::from_u16(value)).unwrap();
+        }
+        let sent = bc.transact(&request);
+        assert_eq!(sent.status_words(), &[StatusWord::from_u16(0x1800)]);
+        assert_eq!(sent.validate(), Ok(()));
+
+        // And back from the same subaddress buffer, once it is written.
+        bus.rt(3).unwrap().write(2.into(), sent.data()).unwrap();
+        let mut bc = BusController::new(&mut bus);
+        let received = bc.transact(&Message::new(BusId::A, CommandWord::from_u16(0x1C42)));
+        assert_eq!(received.data(), sent.data());
+        assert_eq!(received.words().count(), 4);
+        assert_eq!(received.validate(), Ok(()));
+
+        // Nobody at RT 9.
+        let silent = bc.transact(&Message::new(BusId::A, CommandWord::from_u16(0x4C41)));
+        assert!(silent.errors().no_response);
+        assert_eq!(silent.validate(), Ok(()));
+
+        // RT 3 to RT 4, which stores the data, in either order on the bus.
+        for mut rts in [
+            [RemoteTerminal::new(3.into()), RemoteTerminal::new(4.into())],
+            [RemoteTerminal::new(4.into()), RemoteTerminal::new(3.into())],
+        ] {
+            let mut bus = SimBus::new(BusId::A, &mut rts);
+            bus.rt(3).unwrap().write(2.into(), sent.data()).unwrap();
+            let mut bc = BusController::new(&mut bus);
+            let receive = CommandWord::from_u16(0x2062);
+            let transmit = CommandWord::from_u16(0x1C42);
+            let relayed = bc.transact(&Message::rt_to_rt(BusId::A, receive, transmit));
+            assert_eq!(relayed.status_words().len(), 2);
+            assert_eq!(relayed.validate(), Ok(()));
+            let mut out = [DataWord::from_u16(0); 2];
+            bus.rt(4).unwrap().read(3.into(), &mut out).unwrap();
+            assert_eq!(out, sent.data());
+        }
+    }
+
+    #[test]
+    fn bc_retries_on_the_other_bus() {
+        let mut rts = [RemoteTerminal::new(3.into())];
+        let mut bus = SimBus::new(BusId::A, &mut rts);
+        let mut bc = BusController::new(&mut bus);
+        // Transmitter Shutdown on bus B silences RT 3 on bus A.
+        bc.transact(&Message::new(BusId::B, CommandWord::from_u16(0x1C04)));
+
+        let request = Message::new(BusId::A, CommandWord::from_u16(0x1C41));
+        let mut policy = RetryPolicy {
+            retries: 2,
+            on: RetryOn {
+                no_response: true,
+                ..RetryOn::default()
+            },
+            ..RetryPolicy::default()
+        };
+        let attempts = bc.transact_with(&request, &policy);
+        assert_eq!(attempts.retries(), 2);
+        assert!(attempts.last().errors().no_response);
+
+        policy.alternate_bus = true;
+        bc.set_retry_policy(policy);
+        let attempts = bc.transact_retrying(&request);
+        assert_eq!(attempts.messages()[0].bus(), BusId::A);
+        assert_eq!(attempts.messages()[1].bus(), BusId::B);
+        assert_eq!(attempts.last().validate(), Ok(()));
+        assert!(!attempts.last().errors().any());
+
+        // A broadcast RT to RT transfer from the silent transmitter.
+        let receive = CommandWord::from_u16(0xF841);
+        let broadcast = Message::rt_to_rt(BusId::A, receive, CommandWord::from_u16(0x1C41));
+        assert_eq!(bc.transact_retrying(&broadcast).retries(), 0);
+        policy.broadcasts = true;
+        assert_eq!(bc.transact_with(&broadcast, &policy).retries(), 1);
+    }
+
+    #[test]
+    fn bc_handles_busy_rts() {
+        let mut rts = [RemoteTerminal::new(3.into())];
+        rts[0].set_busy(true);
+        let mut bus = Clearing {
+            sim: SimBus::new(BusId::A, &mut rts),
+            busy_ns: 250_000,
+        };
+        let mut bc = BusController::new(&mut bus);
+        let request = Message::new(BusId::A, CommandWord::from_u16(0x1C42));
+
+        let reported = bc.transact_busy(&request, 0);
+        assert!(reported.message.transmitter_declined());
+        assert_eq!((reported.polls, reported.resent), (0, None));
+
+        bc.set_busy_policy(BusyPolicy::Reschedule {
+            frames: 2,
+            times: 1,
+        });
+        assert_eq!(bc.transact_busy(&request, 0).reschedule, Some(2));
+        let failed = bc.transact_busy(&request, 1);
+        assert_eq!((failed.reschedule, failed.gave_up), (None, true));
+
+        bc.set_busy_policy(BusyPolicy::Repoll {
+            delay_ns: 100_000,
+            polls: 4,
+        });
+        let repolled = bc.transact_busy(&request, 0);
+        assert_eq!((repolled.polls, repolled.gave_up), (3, false));
+        assert!(!repolled.last().transmitter_declined());
+        assert_eq!(repolled.last().data().len(), 2);
+
+        // The polls themselves are not counted.
+        assert_eq!(bc.busy_stats().count(3, 2), 4);
+        assert_eq!(bc.busy_stats().iter().count(), 1);
+    }
+}
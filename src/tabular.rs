@@ -0,0 +1,717 @@
+//! Messages as table rows, in CSV or JSON Lines, for spreadsheets and
+//! pandas, and back.
+//!
+//! Each message is one row with the columns of [`CSV_HEADER`]:
+//!
+//! - `time_ns`, `end_ns`: start of the first command, end of the last word.
+//! - `bus`: `A` or `B`.
+//! - `format`: e.g. `bc_to_rt`, `mode_with_data_transmit`.
+//! - `rt`, `tr`, `subaddress`: of the first command, `tr` being `T` or `R`.
+//! - `word_count`: data words asked for, empty for mode commands.
+//! - `mode_code`: the code, empty for data transfers.
+//! - `transmit_command`: the second command of an RT to RT transfer, as
+//!   [`disasm`](crate::disasm) prints it.
+//! - `status`: the status words as `disasm` prints them, flags included,
+//!   separated by ` / `.
+//! - `errors`: the [`MessageErrors`] set, by field name.
+//! - `data`: the data words in hex.
+//! - `values`: with an [`Icd`], its signals as `name=value`.
+//!
+//! Lists are space separated in CSV, `values` by `;`, and JSON arrays (an
+//! object for `values`) in JSON Lines. Reading back ignores `format`,
+//! which the commands decide, and `values`; columns may come in any order.
+use core::fmt::{self, Write};
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use crate::{
+    bus::BusId,
+    icd::Icd,
+    message::{ConsistencyError, Message, MessageErrors, MessageFormat},
+    words::{CommandWord, DataWord, StatusWord},
+};
+
+pub const CSV_HEADER: &str = "time_ns,end_ns,bus,format,rt,tr,subaddress,word_count,mode_code,\
+                              transmit_command,status,errors,data,values";
+
+const ERROR_NAMES: [&str; 5] = [
+    "no_response",
+    "word_error",
+    "wrong_address",
+    "word_count",
+    "sequence",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportErrorKind {
+    /// The CSV input has no header line.
+    MissingHeader,
+    Missing(&'static str),
+    BadValue(&'static str),
+    /// A line of JSON Lines input that is not a JSON object.
+    BadJson,
+    Inconsistent(ConsistencyError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportError {
+    /// 1-based line number.
+    pub line: usize,
+    pub kind: ImportErrorKind,
+}
+
+fn format_name(format: MessageFormat) -> &'static str {
+    match format {
+        MessageFormat::BcToRt => "bc_to_rt",
+        MessageFormat::RtToBc => "rt_to_bc",
+        MessageFormat::RtToRt => "rt_to_rt",
+        MessageFormat::ModeWithoutData => "mode_without_data",
+        MessageFormat::ModeWithDataTransmit => "mode_with_data_transmit",
+        MessageFormat::ModeWithDataReceive => "mode_with_data_receive",
+        MessageFormat::BroadcastBcToRt => "broadcast_bc_to_rt",
+        MessageFormat::BroadcastRtToRt => "broadcast_rt_to_rt",
+        MessageFormat::BroadcastModeWithoutData => "broadcast_mode_without_data",
+        MessageFormat::BroadcastModeWithData => "broadcast_mode_with_data",
+    }
+}
+
+fn error_flags(errors: &MessageErrors) -> [bool; 5] {
+    [
+        errors.no_response,
+        errors.word_error,
+        errors.wrong_address,
+        errors.word_count,
+        errors.sequence,
+    ]
+}
+
+/// The columns of a row.
+struct Fields {
+    time_ns: u64,
+    end_ns: u64,
+    bus: char,
+    format: &'static str,
+    rt: u16,
+    tr: char,
+    subaddress: u16,
+    word_count: Option<usize>,
+    mode_code: Option<u16>,
+    transmit_command: Option<String>,
+    status: Vec<String>,
+    errors: Vec<&'static str>,
+    data: Vec<String>,
+    /// Signal names and values.
+    values: Vec<(String, f64)>,
+}
+
+impl Fields {
+    fn of(message: &Message, icd: Option<&Icd>) -> Self {
+        let command = message.command();
+        let value = command.value();
+        let mode = message.format().is_mode_command();
+        let values = icd
+            .and_then(|icd| {
+                let idx = icd.find_command(&command).or_else(|| {
+                    message
+                        .transmit_command()
+                        .and_then(|cmd| icd.find_command(&cmd))
+                })?;
+                let signals = icd.signals_of(idx).filter_map(|signal| {
+                    Some((signal.name.clone(), signal.value(message.data())?))
+                });
+                Some(signals.collect())
+            })
+            .unwrap_or_default();
+        Fields {
+            time_ns: message.start_ns(),
+            end_ns: message.end_ns(),
+            bus: match message.bus() {
+                BusId::A => 'A',
+                BusId::B => 'B',
+            },
+            format: format_name(message.format()),
+            rt: value >> 11,
+            tr: if value & 0x0400 != 0 { 'T' } else { 'R' },
+            subaddress: (value >> 5) & 0x1F,
+            word_count: (!mode).then(|| message.expected_data_words()),
+            mode_code: mode.then_some(value & 0x1F),
+            transmit_command: message.transmit_command().map(|cmd| cmd.to_string()),
+            status: message
+                .status_words()
+                .iter()
+                .map(|sw| sw.to_string())
+                .collect(),
+            errors: ERROR_NAMES
+                .iter()
+                .zip(error_flags(&message.errors()))
+                .filter(|(_, set)| *set)
+                .map(|(name, _)| *name)
+                .collect(),
+            data: message.data().iter().map(|dw| dw.to_string()).collect(),
+            values,
+        }
+    }
+}
+
+/// Write `field`, quoted if it needs to be.
+fn write_csv_field(out: &mut impl Write, field: &str) -> fmt::Result {
+    if !field.contains([',', '"', '\n', '\r']) {
+        return out.write_str(field);
+    }
+    out.write_char('"')?;
+    for c in field.chars() {
+        if c == '"' {
+            out.write_char('"')?;
+        }
+        out.write_char(c)?;
+    }
+    out.write_char('"')
+}
+
+pub fn write_csv_header(out: &mut impl Write) -> fmt::Result {
+    writeln!(out, "{}", CSV_HEADER)
+}
+
+/// Write `message` as a CSV row, its signal values decoded with `icd`.
+pub fn write_csv(out: &mut impl Write, message: &Message, icd: Option<&Icd>) -> fmt::Result {
+    let fields = Fields::of(message, icd);
+    let opt = |value: Option<String>| value.unwrap_or_default();
+    let values: Vec<String> = fields
+        .values
+        .iter()
+        .map(|(name, value)| std::format!("{}={}", name, value))
+        .collect();
+    let columns = [
+        fields.time_ns.to_string(),
+        fields.end_ns.to_string(),
+        fields.bus.to_string(),
+        fields.format.to_string(),
+        fields.rt.to_string(),
+        fields.tr.to_string(),
+        fields.subaddress.to_string(),
+        opt(fields.word_count.map(|n| n.to_string())),
+        opt(fields.mode_code.map(|n| n.to_string())),
+        opt(fields.transmit_command),
+        fields.status.join(" / "),
+        fields.errors.join(" "),
+        fields.data.join(" "),
+        values.join(";"),
+    ];
+    for (idx, column) in columns.iter().enumerate() {
+        if idx > 0 {
+            out.write_char(',')?;
+        }
+        write_csv_field(out, column)?;
+    }
+    out.write_char('\n')
+}
+
+fn write_json_string(out: &mut impl Write, text: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in text.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+fn write_json_list<T: AsRef<str>>(out: &mut impl Write, items: &[T]) -> fmt::Result {
+    out.write_char('[')?;
+    for (idx, item) in items.iter().enumerate() {
+        if idx > 0 {
+            out.write_char(',')?;
+        }
+        write_json_string(out, item.as_ref())?;
+    }
+    out.write_char(']')
+}
+
+/// Write `message` as one line of JSON, its signal values decoded with
+/// `icd`.
+pub fn write_jsonl(out: &mut impl Write, message: &Message, icd: Option<&Icd>) -> fmt::Result {
+    let fields = Fields::of(message, icd);
+    write!(
+        out,
+        "{{\"time_ns\":{},\"end_ns\":{},\"bus\":\"{}\",\"format\":\"{}\",\"rt\":{},\"tr\":\"{}\",\
+         \"subaddress\":{},\"word_count\":",
+        fields.time_ns,
+        fields.end_ns,
+        fields.bus,
+        fields.format,
+        fields.rt,
+        fields.tr,
+        fields.subaddress
+    )?;
+    match fields.word_count {
+        Some(count) => write!(out, "{}", count)?,
+        None => out.write_str("null")?,
+    }
+    out.write_str(",\"mode_code\":")?;
+    match fields.mode_code {
+        Some(code) => write!(out, "{}", code)?,
+        None => out.write_str("null")?,
+    }
+    out.write_str(",\"transmit_command\":")?;
+    match &fields.transmit_command {
+        Some(cmd) => write_json_string(out, cmd)?,
+        None => out.write_str("null")?,
+    }
+    out.write_str(",\"status\":")?;
+    write_json_list(out, &fields.status)?;
+    out.write_str(",\"errors\":")?;
+    write_json_list(out, &fields.errors)?;
+    out.write_str(",\"data\":")?;
+    write_json_list(out, &fields.data)?;
+    out.write_str(",\"values\":{")?;
+    for (idx, (name, value)) in fields.values.iter().enumerate() {
+        if idx > 0 {
+            out.write_char(',')?;
+        }
+        write_json_string(out, name)?;
+        // JSON has no infinities nor NaN.
+        match value.is_finite() {
+            true => write!(out, ":{}", value)?,
+            false => out.write_str(":null")?,
+        }
+    }
+    out.write_str("}}\n")
+}
+
+/// A row read back, before it is checked.
+#[derive(Debug, Default)]
+struct Row {
+    time_ns: Option<String>,
+    end_ns: Option<String>,
+    bus: Option<String>,
+    rt: Option<String>,
+    tr: Option<String>,
+    subaddress: Option<String>,
+    word_count: Option<String>,
+    mode_code: Option<String>,
+    transmit_command: Option<String>,
+    status: Vec<String>,
+    errors: Vec<String>,
+    data: Vec<String>,
+}
+
+impl Row {
+    /// Set column `name` from `value`; `list` splits it for list columns.
+    fn set(&mut self, name: &str, value: Option<String>, list: impl Fn(String) -> Vec<String>) {
+        let value = value.filter(|value| !value.trim().is_empty());
+        let slot = match name {
+            "time_ns" => &mut self.time_ns,
+            "end_ns" => &mut self.end_ns,
+            "bus" => &mut self.bus,
+            "rt" => &mut self.rt,
+            "tr" => &mut self.tr,
+            "subaddress" => &mut self.subaddress,
+            "word_count" => &mut self.word_count,
+            "mode_code" => &mut self.mode_code,
+            "transmit_command" => &mut self.transmit_command,
+            "status" => {
+                self.status = value.map(&list).unwrap_or_default();
+                return;
+            }
+            "errors" => {
+                self.errors = value.map(&list).unwrap_or_default();
+                return;
+            }
+            "data" => {
+                self.data = value.map(&list).unwrap_or_default();
+                return;
+            }
+            _ => return,
+        };
+        *slot = value;
+    }
+
+    fn message(&self) -> Result<Message, ImportErrorKind> {
+        fn number<T: core::str::FromStr + PartialOrd>(
+            value: &Option<String>,
+            name: &'static str,
+            max: T,
+        ) -> Result<T, ImportErrorKind> {
+            let text = value.as_deref().ok_or(ImportErrorKind::Missing(name))?;
+            text.trim()
+                .parse()
+                .ok()
+                .filter(|value| *value <= max)
+                .ok_or(ImportErrorKind::BadValue(name))
+        }
+
+        let bus = match self.bus.as_deref().map(str::trim) {
+            Some("A") => BusId::A,
+            Some("B") => BusId::B,
+            Some(_) => return Err(ImportErrorKind::BadValue("bus")),
+            None => return Err(ImportErrorKind::Missing("bus")),
+        };
+        let rt: u16 = number(&self.rt, "rt", 31)?;
+        let tr: u16 = match self.tr.as_deref().map(str::trim) {
+            Some("T") => 1,
+            Some("R") => 0,
+            Some(_) => return Err(ImportErrorKind::BadValue("tr")),
+            None => return Err(ImportErrorKind::Missing("tr")),
+        };
+        let subaddress: u16 = number(&self.subaddress, "subaddress", 31)?;
+        let field = match self.mode_code {
+            Some(_) if subaddress != 0 && subaddress != 31 => {
+                return Err(ImportErrorKind::BadValue("mode_code"))
+            }
+            Some(_) => number(&self.mode_code, "mode_code", 31)?,
+            None => match number(&self.word_count, "word_count", 32)? {
+                0 => return Err(ImportErrorKind::BadValue("word_count")),
+                count => count % 32,
+            },
+        };
+        let command = CommandWord::from_u16(rt << 11 | tr << 10 | subaddress << 5 | field);
+
+        let mut message = match &self.transmit_command {
+            Some(text) => {
+                let transmit = text
+                    .parse()
+                    .map_err(|_| ImportErrorKind::BadValue("transmit_command"))?;
+                Message::rt_to_rt(bus, command, transmit)
+            }
+            None => Message::new(bus, command),
+        };
+        for text in &self.status {
+            let sw: StatusWord = text
+                .parse()
+                .map_err(|_| ImportErrorKind::BadValue("status"))?;
+            message
+                .push_status(sw)
+                .map_err(|_| ImportErrorKind::BadValue("status"))?;
+        }
+        for text in &self.data {
+            let dw: DataWord = text
+                .parse()
+                .map_err(|_| ImportErrorKind::BadValue("data"))?;
+            message
+                .push_data(dw)
+                .map_err(|_| ImportErrorKind::BadValue("data"))?;
+        }
+        let errors = message.errors_mut();
+        for name in &self.errors {
+            match name.as_str() {
+                "no_response" => errors.no_response = true,
+                "word_error" => errors.word_error = true,
+                "wrong_address" => errors.wrong_address = true,
+                "word_count" => errors.word_count = true,
+                "sequence" => errors.sequence = true,
+                _ => return Err(ImportErrorKind::BadValue("errors")),
+            }
+        }
+        let time_ns = number(&self.time_ns, "time_ns", u64::MAX)?;
+        let end_ns = match self.end_ns {
+            Some(_) => number(&self.end_ns, "end_ns", u64::MAX)?,
+            None => time_ns,
+        };
+        message.set_time(time_ns, end_ns);
+        message.validate().map_err(ImportErrorKind::Inconsistent)?;
+        Ok(message)
+    }
+}
+
+/// Split a CSV line into its fields. Quoted fields may hold commas and
+/// doubled quotes, not line breaks.
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => fields.push(core::mem::take(&mut field)),
+            (c, _) => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Read the messages of CSV text, as [`write_csv`] writes them after
+/// [`write_csv_header`].
+pub fn read_csv(text: &str) -> Result<Vec<Message>, ImportError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let Some((_, header)) = lines.next() else {
+        return Err(ImportError {
+            line: 1,
+            kind: ImportErrorKind::MissingHeader,
+        });
+    };
+    let header = split_csv(header.trim_start_matches('\u{feff}'));
+    if !header.iter().any(|name| name.trim() == "rt") {
+        return Err(ImportError {
+            line: 1,
+            kind: ImportErrorKind::MissingHeader,
+        });
+    }
+    let list =
+        |value: String| -> Vec<String> { value.split_whitespace().map(String::from).collect() };
+    let status_list = |value: String| -> Vec<String> {
+        value.split('/').map(|sw| sw.trim().to_string()).collect()
+    };
+    let mut messages = Vec::new();
+    for (idx, line) in lines {
+        let mut row = Row::default();
+        for (name, value) in header.iter().zip(split_csv(line)) {
+            match name.trim() {
+                "status" => row.set("status", Some(value), status_list),
+                name => row.set(name, Some(value), list),
+            }
+        }
+        messages.push(row.message().map_err(|kind| ImportError {
+            line: idx + 1,
+            kind,
+        })?);
+    }
+    Ok(messages)
+}
+
+/// The JSON values a row holds.
+#[derive(Debug)]
+enum Json {
+    Null,
+    /// A string, a number or a boolean, as its text.
+    Scalar(String),
+    List(Vec<String>),
+    Object,
+}
+
+/// Just enough of JSON to read rows back: one flat object whose values are
+/// scalars, lists of strings, or objects, which are skipped.
+struct JsonParser<'a> {
+    chars: core::iter::Peekable<core::str::Chars<'a>>,
+}
+
+impl JsonParser<'_> {
+    fn skip_space(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, want: char) -> Option<()> {
+        self.skip_space();
+        (self.chars.next()? == want).then_some(())
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            match self.chars.next()? {
+                '"' => return Some(text),
+                '\\' => match self.chars.next()? {
+                    'n' => text.push('\n'),
+                    't' => text.push('\t'),
+                    'r' => text.push('\r'),
+                    'u' => {
+                        let hex: String = (0..4).filter_map(|_| self.chars.next()).collect();
+                        text.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                    }
+                    c => text.push(c),
+                },
+                c => text.push(c),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Option<Json> {
+        self.skip_space();
+        match *self.chars.peek()? {
+            '"' => self.string().map(Json::Scalar),
+            '[' => {
+                self.chars.next();
+                let mut items = Vec::new();
+                self.skip_space();
+                if self.chars.next_if_eq(&']').is_some() {
+                    return Some(Json::List(items));
+                }
+                loop {
+                    items.push(self.string()?);
+                    self.skip_space();
+                    match self.chars.next()? {
+                        ',' => continue,
+                        ']' => return Some(Json::List(items)),
+                        _ => return None,
+                    }
+                }
+            }
+            '{' => {
+                self.object(|_, _| {})?;
+                Some(Json::Object)
+            }
+            _ => {
+                let mut text = String::new();
+                while let Some(c) = self
+                    .chars
+                    .next_if(|c| c.is_ascii_alphanumeric() || "+-.".contains(*c))
+                {
+                    text.push(c);
+                }
+                match text.as_str() {
+                    "" => None,
+                    "null" => Some(Json::Null),
+                    _ => Some(Json::Scalar(text)),
+                }
+            }
+        }
+    }
+
+    fn object(&mut self, mut field: impl FnMut(String, Json)) -> Option<()> {
+        self.expect('{')?;
+        self.skip_space();
+        if self.chars.next_if_eq(&'}').is_some() {
+            return Some(());
+        }
+        loop {
+            let name = self.string()?;
+            self.expect(':')?;
+            let value = self.value()?;
+            field(name, value);
+            self.skip_space();
+            match self.chars.next()? {
+                ',' => continue,
+                '}' => return Some(()),
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Read the messages of JSON Lines text, as [`write_jsonl`] writes them.
+pub fn read_jsonl(text: &str) -> Result<Vec<Message>, ImportError> {
+    let mut messages = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let error = |kind| ImportError {
+            line: idx + 1,
+            kind,
+        };
+        let mut row = Row::default();
+        let mut parser = JsonParser {
+            chars: line.chars().peekable(),
+        };
+        let parsed = parser.object(|name, value| match value {
+            Json::Scalar(text) => row.set(&name, Some(text), |text| std::vec![text]),
+            Json::List(items) => row.set(&name, Some(items.join("\n")), |text| {
+                text.split('\n').map(String::from).collect()
+            }),
+            Json::Null | Json::Object => {}
+        });
+        parser.skip_space();
+        if parsed.is_none() || parser.chars.next().is_some() {
+            return Err(error(ImportErrorKind::BadJson));
+        }
+        messages.push(row.message().map_err(error)?);
+    }
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::String;
+
+    use crate::{bus::BusId, icd::Icd, message::*, tabular::*, words::*};
+
+    fn messages() -> [Message; 3] {
+        let mut transfer = Message::new(BusId::A, CommandWord::from_u16(0x2822));
+        transfer.push_data(DataWord::from_u16(0xFFF6)).unwrap();
+        transfer.push_data(DataWord::from_u16(0x3000)).unwrap();
+        transfer.push_status(StatusWord::from_u16(0x2808)).unwrap();
+        transfer.set_time(1_000, 81_000);
+
+        let mut rt_to_rt = Message::rt_to_rt(
+            BusId::B,
+            CommandWord::from_u16(0x3021),
+            CommandWord::from_u16(0x2C21),
+        );
+        rt_to_rt.push_status(StatusWord::from_u16(0x2800)).unwrap();
+        rt_to_rt.push_data(DataWord::from_u16(7)).unwrap();
+        rt_to_rt.errors_mut().no_response = true;
+        rt_to_rt.set_time(200_000, 300_000);
+
+        let mut vector = Message::new(BusId::A, CommandWord::from_u16(0x2C10));
+        vector.push_status(StatusWord::from_u16(0x2800)).unwrap();
+        vector.push_data(DataWord::from_u16(0xBEEF)).unwrap();
+        vector.set_time(400_000, 460_000);
+        [transfer, rt_to_rt, vector]
+    }
+
+    const ICD: &str = "
+        message nav_state rt=5 rx sa=1 wc=2
+        signal nav_state.altitude word=0 signed scale=0.5
+        signal nav_state.mode word=1 lsb=12 bits=4
+    ";
+
+    #[test]
+    fn csv_round_trip() {
+        let icd: Icd = ICD.parse().unwrap();
+        let mut csv = String::new();
+        write_csv_header(&mut csv).unwrap();
+        for message in &messages() {
+            write_csv(&mut csv, message, Some(&icd)).unwrap();
+        }
+        let mut lines = csv.lines().skip(1);
+        assert_eq!(
+            lines.next().unwrap(),
+            "1000,81000,A,bc_to_rt,5,R,1,2,,,RT05 BUSY,,FFF6 3000,altitude=-5;mode=3"
+        );
+        assert_eq!(
+            lines.next().unwrap(),
+            "200000,300000,B,rt_to_rt,6,R,1,1,,RT05 T SA01 WC01,RT05,no_response,0007,"
+        );
+        assert_eq!(read_csv(&csv).unwrap(), messages());
+
+        // Hand-edited: columns dropped and moved, a word count changed.
+        let edited = "rt,subaddress,tr,bus,word_count,data,time_ns\n3,4,R,B,2,1 2,0\n";
+        let message = read_csv(edited).unwrap_err();
+        assert_eq!(message.line, 2);
+        assert_eq!(
+            message.kind,
+            ImportErrorKind::Inconsistent(ConsistencyError::StatusCount {
+                expected: 1,
+                actual: 0
+            })
+        );
+        let edited = "rt,subaddress,tr,bus,word_count,data,time_ns,errors\n\
+                      3,4,R,B,2,1 2,0,no_response\n";
+        let message = read_csv(edited).unwrap()[0];
+        assert_eq!(message.command(), CommandWord::from_u16(0x1882));
+        assert_eq!(message.data().len(), 2);
+    }
+
+    #[test]
+    fn jsonl_round_trip() {
+        let icd: Icd = ICD.parse().unwrap();
+        let mut jsonl = String::new();
+        for message in &messages() {
+            write_jsonl(&mut jsonl, message, Some(&icd)).unwrap();
+        }
+        assert_eq!(
+            jsonl.lines().next().unwrap(),
+            "{\"time_ns\":1000,\"end_ns\":81000,\"bus\":\"A\",\"format\":\"bc_to_rt\",\"rt\":5,\
+             \"tr\":\"R\",\"subaddress\":1,\"word_count\":2,\"mode_code\":null,\
+             \"transmit_command\":null,\"status\":[\"RT05 BUSY\"],\"errors\":[],\
+             \"data\":[\"FFF6\",\"3000\"],\"values\":{\"altitude\":-5,\"mode\":3}}"
+        );
+        assert_eq!(read_jsonl(&jsonl).unwrap(), messages());
+        assert_eq!(
+            read_jsonl("{\"rt\": 5,").unwrap_err().kind,
+            ImportErrorKind::BadJson
+        );
+    }
+}
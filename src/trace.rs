@@ -0,0 +1,364 @@
+//! Recording bus traffic and replaying it.
+//!
+//! [`RecordingBus`] sits in front of any bus and logs every word written
+//! and read, with a timestamp, into a [`TraceSink`]. [`ReplayBus`] plays such
+//! a trace back and flags every place where the code under test writes
+//! something else than what was recorded.
+use crate::{
+    bus::{Bus, BusError, BusId},
+    words::*,
+};
+
+/// Source of timestamps, in nanoseconds. Any `FnMut() -> u64` is a clock.
+pub trait Clock {
+    fn now_ns(&mut self) -> u64;
+}
+
+impl<F: FnMut() -> u64> Clock for F {
+    fn now_ns(&mut self) -> u64 {
+        self()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Put on the bus by the recorded participant.
+    Written,
+    /// Received from the bus by the recorded participant.
+    Read,
+}
+
+impl Direction {
+    /// The same traffic seen from the other end of the bus.
+    pub fn mirrored(self) -> Self {
+        match self {
+            Direction::Written => Direction::Read,
+            Direction::Read => Direction::Written,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub time_ns: u64,
+    pub direction: Direction,
+    /// The word, or why reading it failed.
+    pub word: Result<Word, BusError>,
+}
+
+impl TraceEntry {
+    const EMPTY: Self = Self {
+        time_ns: 0,
+        direction: Direction::Written,
+        word: Err(BusError::NoResponse),
+    };
+}
+
+/// Where a RecordingBus puts its entries.
+pub trait TraceSink {
+    fn record(&mut self, entry: TraceEntry);
+}
+
+/// Fixed-capacity trace. Entries past `N` are counted but not kept.
+#[derive(Debug, Clone)]
+pub struct Trace<const N: usize> {
+    entries: [TraceEntry; N],
+    len: usize,
+    dropped: u32,
+}
+
+impl<const N: usize> Trace<N> {
+    pub const fn new() -> Self {
+        Self {
+            entries: [TraceEntry::EMPTY; N],
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries[..self.len]
+    }
+
+    /// Entries that did not fit.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+}
+
+impl<const N: usize> Default for Trace<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> TraceSink for Trace<N> {
+    fn record(&mut self, entry: TraceEntry) {
+        if self.len == N {
+            self.dropped += 1;
+            return;
+        }
+        self.entries[self.len] = entry;
+        self.len += 1;
+    }
+}
+
+impl<S: TraceSink + ?Sized> TraceSink for &mut S {
+    fn record(&mut self, entry: TraceEntry) {
+        (**self).record(entry)
+    }
+}
+
+/// [`Bus`] wrapper logging all traffic going through it.
+pub struct RecordingBus<B: Bus, S: TraceSink, C: Clock> {
+    inner: B,
+    sink: S,
+    clock: C,
+}
+
+impl<B: Bus, S: TraceSink, C: Clock> RecordingBus<B, S, C> {
+    pub fn new(inner: B, sink: S, clock: C) -> Self {
+        Self { inner, sink, clock }
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn inner(&mut self) -> &mut B {
+        &mut self.inner
+    }
+
+    pub fn into_parts(self) -> (B, S) {
+        (self.inner, self.sink)
+    }
+}
+
+impl<B: Bus, S: TraceSink, C: Clock> Bus for RecordingBus<B, S, C> {
+    fn write_word(&mut self, value: Word) {
+        let time_ns = self.clock.now_ns();
+        self.sink.record(TraceEntry {
+            time_ns,
+            direction: Direction::Written,
+            word: Ok(value),
+        });
+        self.inner.write_word(value)
+    }
+
+    fn select_bus(&mut self, id: BusId) {
+        self.inner.select_bus(id)
+    }
+
+    fn idle(&mut self, ns: u64) {
+        self.inner.idle(ns)
+    }
+
+    fn read_next(&mut self) -> Result<Word, BusError> {
+        let word = self.inner.read_next();
+        let time_ns = self.clock.now_ns();
+        self.sink.record(TraceEntry {
+            time_ns,
+            direction: Direction::Read,
+            word,
+        });
+        word
+    }
+}
+
+/// A place where replayed code wrote something else than the recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index in the trace of the entry that was expected.
+    pub index: usize,
+    /// What the recording wrote there, `None` past its end or where it read.
+    pub expected: Option<Word>,
+    /// What was written, `None` where the recording wrote but the code read
+    /// instead.
+    pub actual: Option<Word>,
+}
+
+/// [`Bus`] playing a recorded trace back. Reads return the recorded words
+/// (and errors), writes are compared with the recorded ones.
+///
+/// Replay a BC recording to a BC, or use [`ReplayBus::mirrored`] to feed a
+/// BC recording to an RT (and the other way around).
+pub struct ReplayBus<'a> {
+    trace: &'a [TraceEntry],
+    mirrored: bool,
+    pos: usize,
+    divergences: u32,
+    first_divergence: Option<Divergence>,
+}
+
+impl<'a> ReplayBus<'a> {
+    pub fn new(trace: &'a [TraceEntry]) -> Self {
+        Self {
+            trace,
+            mirrored: false,
+            pos: 0,
+            divergences: 0,
+            first_divergence: None,
+        }
+    }
+
+    /// Replay the trace as seen from the other end of the bus: recorded
+    /// writes are read, recorded reads are expected to be written.
+    pub fn mirrored(trace: &'a [TraceEntry]) -> Self {
+        Self {
+            mirrored: true,
+            ..Self::new(trace)
+        }
+    }
+
+    pub fn divergences(&self) -> u32 {
+        self.divergences
+    }
+
+    pub fn first_divergence(&self) -> Option<Divergence> {
+        self.first_divergence
+    }
+
+    /// True once every recorded entry was replayed.
+    pub fn is_finished(&self) -> bool {
+        self.pos >= self.trace.len()
+    }
+
+    /// Timestamp of the next entry to replay.
+    pub fn next_time_ns(&self) -> Option<u64> {
+        self.trace.get(self.pos).map(|entry| entry.time_ns)
+    }
+
+    fn direction(&self, entry: &TraceEntry) -> Direction {
+        if self.mirrored {
+            entry.direction.mirrored()
+        } else {
+            entry.direction
+        }
+    }
+
+    fn diverge(&mut self, divergence: Divergence) {
+        self.divergences += 1;
+        if self.first_divergence.is_none() {
+            self.first_divergence = Some(divergence);
+        }
+    }
+}
+
+impl Bus for ReplayBus<'_> {
+    fn write_word(&mut self, value: Word) {
+        let Some(entry) = self.trace.get(self.pos).copied() else {
+            self.diverge(Divergence {
+                index: self.pos,
+                expected: None,
+                actual: Some(value),
+            });
+            return;
+        };
+        if self.direction(&entry) != Direction::Written {
+            // The recording reads here: don't move, so the read still
+            // lines up.
+            self.diverge(Divergence {
+                index: self.pos,
+                expected: None,
+                actual: Some(value),
+            });
+            return;
+        }
+        self.pos += 1;
+        if entry.word != Ok(value) {
+            self.diverge(Divergence {
+                index: self.pos - 1,
+                expected: entry.word.ok(),
+                actual: Some(value),
+            });
+        }
+    }
+
+    fn read_next(&mut self) -> Result<Word, BusError> {
+        // Writes the code skipped are divergences too.
+        while let Some(entry) = self.trace.get(self.pos).copied() {
+            self.pos += 1;
+            if self.direction(&entry) == Direction::Read {
+                return entry.word;
+            }
+            if entry.word.is_err() {
+                // A failed read seen from the other end: nothing to write.
+                continue;
+            }
+            self.diverge(Divergence {
+                index: self.pos - 1,
+                expected: entry.word.ok(),
+                actual: None,
+            });
+        }
+        Err(BusError::NoResponse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{bus::*, rt::RemoteTerminal, sim::SimBus, trace::*};
+
+    fn record_exchange(trace: &mut Trace<64>) {
+        let mut rts = [RemoteTerminal::new(4.into())];
+        let sim = SimBus::new(BusId::A, &mut rts);
+        let mut now = 0;
+        let clock = || {
+            now += 20_000;
+            now
+        };
+        let mut bus = RecordingBus::new(sim, trace, clock);
+        let mut bc = BusController::new(&mut bus);
+        let data = [DataWord::from_u16(1), DataWord::from_u16(2)];
+        bc.send_transfer(RTAddr::Single(4.into()), 1.into(), &data)
+            .unwrap();
+        bc.send_mode_command(RTAddr::Single(4.into()), ModeCode::TransmitStatusWord, None)
+            .unwrap();
+    }
+
+    #[test]
+    fn records_both_directions_with_timestamps() {
+        let mut trace = Trace::<64>::new();
+        record_exchange(&mut trace);
+        let entries = trace.entries();
+        assert_eq!(entries.len(), 6);
+        assert_eq!(entries[0].direction, Direction::Written);
+        assert_eq!(entries[3].direction, Direction::Read);
+        assert!(matches!(entries[3].word, Ok(Word::Status(_))));
+        assert!(entries.windows(2).all(|w| w[0].time_ns < w[1].time_ns));
+    }
+
+    #[test]
+    fn replay_to_bc_flags_divergence() {
+        let mut trace = Trace::<64>::new();
+        record_exchange(&mut trace);
+
+        let mut replay = ReplayBus::new(trace.entries());
+        let mut bc = BusController::new(&mut replay);
+        let data = [DataWord::from_u16(1), DataWord::from_u16(3)];
+        bc.send_transfer(RTAddr::Single(4.into()), 1.into(), &data)
+            .unwrap();
+        bc.send_mode_command(RTAddr::Single(4.into()), ModeCode::TransmitStatusWord, None)
+            .unwrap();
+        assert!(replay.is_finished());
+        assert_eq!(replay.divergences(), 1);
+        let divergence = replay.first_divergence().unwrap();
+        assert_eq!(divergence.index, 2);
+        assert_eq!(divergence.actual, Some(Word::Data(DataWord::from_u16(3))));
+    }
+
+    #[test]
+    fn mirrored_replay_drives_an_rt() {
+        let mut trace = Trace::<64>::new();
+        record_exchange(&mut trace);
+
+        let mut replay = ReplayBus::mirrored(trace.entries());
+        let mut rt = RemoteTerminal::new(4.into());
+        while !replay.is_finished() {
+            rt.poll(BusId::A, &mut replay).unwrap();
+        }
+        assert_eq!(replay.divergences(), 0);
+        let mut out = [DataWord::from_u16(0); 2];
+        assert_eq!(rt.read(1.into(), &mut out), Some(2));
+    }
+}
@@ -0,0 +1,349 @@
+//! [`Bus`] bridged over UDP, in the [`datagram`](crate::datagram) format.
+//!
+//! Each [`UdpBus`] sends what is written to it to all its peers, and reads
+//! what its peers send for the same bus. Point two of them at each other to
+//! bridge a simulated bus across machines, or add a remote display as one
+//! more peer. Datagrams lost, repeated or reordered on the way show in the
+//! sequence numbers of each sender: see [`UdpBus::lost`] and
+//! [`UdpBus::out_of_order`].
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+use crate::{
+    bus::{Bus, BusError, BusId},
+    datagram::{self, DatagramError, Header, Record, Records, MAX_DATAGRAM_LEN},
+    words::Word,
+};
+
+/// How long `read_next` waits before reporting `NoResponse`.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Time one word takes on the wire, used to timestamp the words of a
+/// message sent at once.
+pub const WORD_TIME_NS: u32 = 20_000;
+
+/// Received words kept for `read_next`; past that the oldest are dropped.
+pub const MAX_QUEUED_WORDS: usize = 1024;
+
+pub struct UdpBus {
+    socket: UdpSocket,
+    id: BusId,
+    peers: Vec<SocketAddr>,
+    start: Instant,
+    sequence: u16,
+    timeout: Duration,
+    // Next sequence number expected from each sender.
+    expected: Vec<(SocketAddr, u16)>,
+    // Received words with their sender's timestamp.
+    received: VecDeque<(u64, Result<Word, BusError>)>,
+    last_time_ns: Option<u64>,
+    rejected: u32,
+    lost: u32,
+    out_of_order: u32,
+    dropped: u32,
+}
+
+impl UdpBus {
+    /// Listen on `addr` for traffic of bus `id`.
+    pub fn bind(addr: impl ToSocketAddrs, id: BusId) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_read_timeout(Some(DEFAULT_TIMEOUT))?;
+        Ok(Self {
+            socket,
+            id,
+            peers: Vec::new(),
+            start: Instant::now(),
+            sequence: 0,
+            timeout: DEFAULT_TIMEOUT,
+            expected: Vec::new(),
+            received: VecDeque::new(),
+            last_time_ns: None,
+            rejected: 0,
+            lost: 0,
+            out_of_order: 0,
+            dropped: 0,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn id(&self) -> BusId {
+        self.id
+    }
+
+    /// Send everything written from now on to `peer` too.
+    pub fn add_peer(&mut self, peer: SocketAddr) {
+        self.peers.push(peer);
+    }
+
+    /// Fails on a zero `timeout`.
+    pub fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.socket.set_read_timeout(Some(timeout))?;
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    /// Sender's timestamp of the last word read.
+    pub fn last_time_ns(&self) -> Option<u64> {
+        self.last_time_ns
+    }
+
+    /// Datagrams dropped because they were malformed or for the other bus.
+    pub fn rejected(&self) -> u32 {
+        self.rejected
+    }
+
+    /// Datagrams missing from the sequence numbers of a sender.
+    pub fn lost(&self) -> u32 {
+        self.lost
+    }
+
+    /// Datagrams dropped because they came again, or after a later one
+    /// from the same sender.
+    pub fn out_of_order(&self) -> u32 {
+        self.out_of_order
+    }
+
+    /// Words dropped because more than [`MAX_QUEUED_WORDS`] were waiting.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Send a whole message in one datagram, with its words
+    /// [`WORD_TIME_NS`] apart.
+    pub fn send_message(&mut self, words: &[Word]) -> io::Result<()> {
+        if words.len() > datagram::MAX_RECORDS {
+            return Err(invalid(DatagramError::TooManyRecords));
+        }
+        let mut records = [Record::new(0, Err(BusError::NoResponse)); datagram::MAX_RECORDS];
+        for (idx, (record, word)) in records.iter_mut().zip(words).enumerate() {
+            *record = Record::new(idx as u32 * WORD_TIME_NS, Ok(*word));
+        }
+        self.send_records(&records[..words.len()])
+    }
+
+    pub fn send_records(&mut self, records: &[Record]) -> io::Result<()> {
+        let header = Header {
+            bus: self.id,
+            sequence: self.sequence,
+            time_ns: self.start.elapsed().as_nanos() as u64,
+        };
+        let mut buf = [0; MAX_DATAGRAM_LEN];
+        let len = datagram::encode(&header, records, &mut buf).map_err(invalid)?;
+        self.sequence = self.sequence.wrapping_add(1);
+        for peer in &self.peers {
+            self.socket.send_to(&buf[..len], peer)?;
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<(), BusError> {
+        let mut buf = [0; MAX_DATAGRAM_LEN];
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(BusError::NoResponse);
+            }
+            let (len, from) = self
+                .socket
+                .set_read_timeout(Some(left))
+                .and_then(|()| self.socket.recv_from(&mut buf))
+                .map_err(|_| BusError::NoResponse)?;
+            match datagram::decode(&buf[..len]) {
+                Ok((header, records)) if header.bus == self.id && times_fit(&header, &records) => {
+                    if !self.in_sequence(from, header.sequence) {
+                        self.out_of_order = self.out_of_order.wrapping_add(1);
+                        continue;
+                    }
+                    for record in records {
+                        if self.received.len() == MAX_QUEUED_WORDS {
+                            self.received.pop_front();
+                            self.dropped = self.dropped.wrapping_add(1);
+                        }
+                        let time_ns = header.time_ns + record.offset_ns as u64;
+                        self.received.push_back((time_ns, record.word));
+                    }
+                    return Ok(());
+                }
+                _ => self.rejected = self.rejected.wrapping_add(1),
+            }
+        }
+    }
+
+    /// Whether `sequence` from `from` comes after the last one, counting
+    /// the datagrams skipped in between as lost.
+    fn in_sequence(&mut self, from: SocketAddr, sequence: u16) -> bool {
+        let Some(idx) = self.expected.iter().position(|(peer, _)| *peer == from) else {
+            self.expected.push((from, sequence.wrapping_add(1)));
+            return true;
+        };
+        let expected = &mut self.expected[idx].1;
+        let skipped = sequence.wrapping_sub(*expected);
+        // Half the sequence space ahead, or behind.
+        if skipped >= 0x8000 {
+            return false;
+        }
+        self.lost = self.lost.wrapping_add(skipped as u32);
+        *expected = sequence.wrapping_add(1);
+        true
+    }
+}
+
+/// Whether the time of every record, its offset after the header time,
+/// fits in a u64. Datagrams where it does not are malformed.
+fn times_fit(header: &Header, records: &Records) -> bool {
+    records.clone().all(|record| {
+        header
+            .time_ns
+            .checked_add(record.offset_ns as u64)
+            .is_some()
+    })
+}
+
+fn invalid(err: DatagramError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, std::format!("{:?}", err))
+}
+
+impl Bus for UdpBus {
+    fn write_word(&mut self, value: Word) {
+        // A datagram lost on the way is a word lost on the bus.
+        let _ = self.send_records(&[Record::new(0, Ok(value))]);
+    }
+
+    fn read_next(&mut self) -> Result<Word, BusError> {
+        while self.received.is_empty() {
+            self.receive()?;
+        }
+        let (time_ns, word) = self.received.pop_front().unwrap();
+        self.last_time_ns = Some(time_ns);
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::{bus::*, rt::RemoteTerminal, udp::*, words::*};
+
+    fn pair(id: BusId) -> (UdpBus, UdpBus) {
+        let mut a = UdpBus::bind("127.0.0.1:0", id).unwrap();
+        let mut b = UdpBus::bind("127.0.0.1:0", id).unwrap();
+        a.add_peer(b.local_addr().unwrap());
+        b.add_peer(a.local_addr().unwrap());
+        (a, b)
+    }
+
+    #[test]
+    fn bc_and_rt_over_loopback() {
+        let (mut bus, mut rt_bus) = pair(BusId::A);
+        bus.set_timeout(Duration::from_secs(2)).unwrap();
+        rt_bus.set_timeout(Duration::from_millis(5)).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let rt_stop = stop.clone();
+        let rt = thread::spawn(move || {
+            let mut rt = RemoteTerminal::new(9.into());
+            rt.write(3.into(), &[DataWord::from_u16(0x0042)]).unwrap();
+            while !rt_stop.load(Ordering::Relaxed) {
+                let _ = rt.poll(BusId::A, &mut rt_bus);
+            }
+        });
+
+        let mut out = [DataWord::from_u16(0)];
+        BusController::new(&mut bus)
+            .receive_transfer(RTAddr::Single(9.into()), 3.into(), &mut out)
+            .unwrap();
+        assert_eq!(out[0].value(), 0x0042);
+        assert!(bus.last_time_ns().is_some());
+        stop.store(true, Ordering::Relaxed);
+        rt.join().unwrap();
+    }
+
+    #[test]
+    fn messages_keep_word_timing_and_bus() {
+        let (mut sender, mut display) = pair(BusId::B);
+        let message = [
+            Word::Command(CommandWord::from_u16(0x4821)),
+            Word::Data(DataWord::from_u16(7)),
+        ];
+        sender.send_message(&message).unwrap();
+        assert_eq!(display.read_next(), Ok(message[0]));
+        let first = display.last_time_ns().unwrap();
+        assert_eq!(display.read_next(), Ok(message[1]));
+        assert_eq!(display.last_time_ns(), Some(first + WORD_TIME_NS as u64));
+
+        // Traffic of the other bus is not ours.
+        let mut other = UdpBus::bind("127.0.0.1:0", BusId::A).unwrap();
+        other.add_peer(display.local_addr().unwrap());
+        other.write_word(message[0]);
+        display.set_timeout(Duration::from_millis(20)).unwrap();
+        assert_eq!(display.read_next(), Err(BusError::NoResponse));
+        assert_eq!(display.rejected(), 1);
+    }
+
+    #[test]
+    fn counts_lost_and_reordered_datagrams() {
+        let mut display = UdpBus::bind("127.0.0.1:0", BusId::A).unwrap();
+        display.set_timeout(Duration::from_millis(20)).unwrap();
+        let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let send = |sequence: u16, value: u16| {
+            let header = Header {
+                bus: BusId::A,
+                sequence,
+                time_ns: 0,
+            };
+            let record = Record::new(0, Ok(Word::Data(DataWord::from_u16(value))));
+            let mut buf = [0; MAX_DATAGRAM_LEN];
+            let len = datagram::encode(&header, &[record], &mut buf).unwrap();
+            sender
+                .send_to(&buf[..len], display.local_addr().unwrap())
+                .unwrap();
+        };
+        // 1 is lost, then comes late, and 3 comes twice.
+        for sequence in [0xFFFF, 0, 2, 1, 3, 3, 4] {
+            send(sequence, sequence);
+        }
+        let mut values = Vec::new();
+        while let Ok(Word::Data(word)) = display.read_next() {
+            values.push(word.value());
+        }
+        assert_eq!(values, [0xFFFF, 0, 2, 3, 4]);
+        assert_eq!(display.lost(), 1);
+        assert_eq!(display.out_of_order(), 2);
+        assert_eq!(display.rejected(), 0);
+    }
+
+    #[test]
+    fn rejects_record_times_past_u64() {
+        let mut display = UdpBus::bind("127.0.0.1:0", BusId::A).unwrap();
+        display.set_timeout(Duration::from_millis(20)).unwrap();
+        let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let send = |sequence: u16, offset_ns: u32| {
+            let header = Header {
+                bus: BusId::A,
+                sequence,
+                time_ns: u64::MAX,
+            };
+            let word = Ok(Word::Data(DataWord::from_u16(sequence)));
+            let records = [Record::new(0, word), Record::new(offset_ns, word)];
+            let mut buf = [0; MAX_DATAGRAM_LEN];
+            let len = datagram::encode(&header, &records, &mut buf).unwrap();
+            sender
+                .send_to(&buf[..len], display.local_addr().unwrap())
+                .unwrap();
+        };
+        send(0, 1);
+        send(1, 0);
+        assert_eq!(display.read_next(), Ok(Word::Data(DataWord::from_u16(1))));
+        assert_eq!(display.last_time_ns(), Some(u64::MAX));
+        assert_eq!(display.rejected(), 1);
+    }
+}
@@ -0,0 +1,220 @@
+//! Virtual bus between processes on one host, over Unix domain sockets.
+//!
+//! A [`UnixHub`] listens on a socket path and stands for the bus. Every
+//! process (BC, RT or monitor) connects a [`UnixBus`] to it. Words are sent
+//! as [`frame`](crate::frame)s, and the hub copies every frame it gets to
+//! all the other connections, like on the wire. The `milisse-hub` binary
+//! runs a hub.
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use std::vec::Vec;
+
+use crate::{
+    bus::{Bus, BusError},
+    frame::{self, FRAME_LEN},
+    words::Word,
+};
+
+/// How long `read_next` waits before reporting `NoResponse`.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Frames the hub holds for a client that reads slower than the others
+/// write. A client that falls further behind is dropped.
+pub const CLIENT_QUEUE_LEN: usize = 1024;
+
+/// How long the hub waits on a client that doesn't take its frames before
+/// it gives up on it.
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Pause after a failed `accept`, so that running out of file
+/// descriptors doesn't turn into a busy loop.
+const ACCEPT_RETRY: Duration = Duration::from_millis(10);
+
+type Frame = [u8; FRAME_LEN];
+type Clients = Arc<Mutex<Vec<(usize, SyncSender<Frame>)>>>;
+
+pub struct UnixHub {
+    listener: UnixListener,
+    clients: Clients,
+}
+
+impl UnixHub {
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            listener: UnixListener::bind(path)?,
+            clients: Clients::default(),
+        })
+    }
+
+    /// Accept connections and relay their frames, with a reading and a
+    /// writing thread per connection. A failed `accept` only loses that
+    /// connection, so this never returns.
+    pub fn run(self) -> io::Result<()> {
+        for (id, stream) in self.listener.incoming().enumerate() {
+            let Ok(stream) = stream else {
+                thread::sleep(ACCEPT_RETRY);
+                continue;
+            };
+            let Ok(writer) = stream.try_clone() else {
+                continue;
+            };
+            let _ = writer.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT));
+            let (queue, frames) = mpsc::sync_channel(CLIENT_QUEUE_LEN);
+            self.clients.lock().unwrap().push((id, queue));
+            let clients = self.clients.clone();
+            thread::spawn(move || relay(id, stream, clients));
+            thread::spawn(move || forward(frames, writer));
+        }
+        Ok(())
+    }
+}
+
+/// Copy the frames of client `id` to the queues of all the others.
+fn relay(id: usize, mut stream: UnixStream, clients: Clients) {
+    let mut frame = [0; FRAME_LEN];
+    while stream.read_exact(&mut frame).is_ok() {
+        // A client whose queue is full has stopped reading, and one whose
+        // queue is closed is gone.
+        clients
+            .lock()
+            .unwrap()
+            .retain(|(other, queue)| *other == id || queue.try_send(frame).is_ok());
+    }
+    clients.lock().unwrap().retain(|(other, _)| *other != id);
+}
+
+/// Write the frames queued for a client, until it is dropped or its
+/// connection fails.
+fn forward(frames: Receiver<Frame>, mut writer: UnixStream) {
+    while let Ok(frame) = frames.recv() {
+        if writer.write_all(&frame).is_err() {
+            break;
+        }
+    }
+    // Ends the reading side too.
+    let _ = writer.shutdown(Shutdown::Both);
+}
+
+/// One process's connection to a [`UnixHub`].
+pub struct UnixBus {
+    stream: UnixStream,
+    // Start of a frame cut by a timeout.
+    partial: [u8; FRAME_LEN],
+    filled: usize,
+}
+
+impl UnixBus {
+    pub fn connect(path: impl AsRef<Path>) -> io::Result<Self> {
+        let stream = UnixStream::connect(path)?;
+        stream.set_read_timeout(Some(DEFAULT_TIMEOUT))?;
+        Ok(Self {
+            stream,
+            partial: [0; FRAME_LEN],
+            filled: 0,
+        })
+    }
+
+    /// Fails on a zero `timeout`.
+    pub fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.stream.set_read_timeout(Some(timeout))
+    }
+}
+
+impl Bus for UnixBus {
+    fn write_word(&mut self, value: Word) {
+        // Like a transmitter on a dead bus: the word is just lost.
+        let _ = self.stream.write_all(&frame::encode(value));
+    }
+
+    fn read_next(&mut self) -> Result<Word, BusError> {
+        while self.filled < FRAME_LEN {
+            match self.stream.read(&mut self.partial[self.filled..]) {
+                Ok(0) => return Err(BusError::NoResponse),
+                Ok(len) => self.filled += len,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return Err(BusError::NoResponse),
+            }
+        }
+        self.filled = 0;
+        frame::decode(self.partial).ok_or(BusError::Parity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use crate::{bus::*, rt::RemoteTerminal, unix::*, words::*};
+
+    #[test]
+    fn bc_and_rt_through_a_hub() {
+        let path = std::env::temp_dir().join(std::format!("milisse-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let hub = UnixHub::bind(&path).unwrap();
+        let clients = hub.clients.clone();
+        thread::spawn(move || hub.run());
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut rt_bus = UnixBus::connect(&path).unwrap();
+        rt_bus.set_timeout(Duration::from_millis(5)).unwrap();
+        let rt_stop = stop.clone();
+        let rt = thread::spawn(move || {
+            let mut rt = RemoteTerminal::new(6.into());
+            while !rt_stop.load(Ordering::Relaxed) {
+                let _ = rt.poll(BusId::A, &mut rt_bus);
+            }
+            rt
+        });
+        let mut bus = UnixBus::connect(&path).unwrap();
+        bus.set_timeout(Duration::from_secs(2)).unwrap();
+        while clients.lock().unwrap().len() < 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let data = [DataWord::from_u16(0x1553)];
+        let sw = BusController::new(&mut bus)
+            .send_transfer(RTAddr::Single(6.into()), 7.into(), &data)
+            .unwrap()
+            .unwrap();
+        assert_eq!(sw.get_rt_addr(), RTAddr::Single(6.into()));
+
+        stop.store(true, Ordering::Relaxed);
+        let mut rt = rt.join().unwrap();
+        let mut out = [DataWord::from_u16(0)];
+        assert_eq!(rt.read(7.into(), &mut out), Some(1));
+        assert_eq!(out, data);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn drops_clients_that_stop_reading() {
+        let path =
+            std::env::temp_dir().join(std::format!("milisse-{}-slow.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let hub = UnixHub::bind(&path).unwrap();
+        let clients = hub.clients.clone();
+        thread::spawn(move || hub.run());
+
+        let _silent = UnixStream::connect(&path).unwrap();
+        let mut bus = UnixBus::connect(&path).unwrap();
+        while clients.lock().unwrap().len() < 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        // Far more than the socket buffer and the queue together.
+        for _ in 0..400_000 {
+            bus.write_word(Word::Data(DataWord::from_u16(0x1553)));
+        }
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while clients.lock().unwrap().len() > 1 {
+            assert!(std::time::Instant::now() < deadline, "silent client kept");
+            thread::sleep(Duration::from_millis(1));
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+}
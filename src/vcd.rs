@@ -0,0 +1,338 @@
+//! Value Change Dump export of bus traffic, for GTKWave and the like.
+//!
+//! Each bus gets four signals:
+//!
+//! - `bus_a`: the Manchester encoded line, `z` while idle.
+//! - `type_a`: what is on the line, 0 idle, 1 command, 2 status, 3 data.
+//! - `word_a`: the 16 bits of the word on the line, `x` while idle.
+//! - `msg_a`: high from the first command of a message to its last word.
+//!
+//! and the same for bus B.
+use core::fmt::{self, Write};
+
+use crate::{
+    bus::BusId,
+    manchester::{self, HALF_BITS, HALF_BIT_NS, WORD_NS},
+    message::{Fields, MessageFormat},
+    words::Word,
+};
+
+/// A word put on a bus at `time_ns`, the start of its sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedWord {
+    pub time_ns: u64,
+    pub bus: BusId,
+    pub word: Word,
+}
+
+impl TimedWord {
+    pub fn new(time_ns: u64, bus: BusId, word: Word) -> Self {
+        Self { time_ns, bus, word }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Signal {
+    Line,
+    Type,
+    Value,
+    Message,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    Level(Option<bool>),
+    Type(u8),
+    Word(Option<u16>),
+    Message(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Event {
+    time_ns: u64,
+    signal: Signal,
+    value: Value,
+}
+
+const NO_EVENT: Event = Event {
+    time_ns: 0,
+    signal: Signal::Line,
+    value: Value::Level(None),
+};
+
+/// Most events one word makes: message, type and value at the start, one
+/// per half-bit, and four at the end.
+const WORD_EVENTS: usize = 3 + HALF_BITS + 4;
+
+/// The value changes of one bus, a word at a time.
+struct Lane<'a> {
+    words: &'a [TimedWord],
+    bus: BusId,
+    next: usize,
+    previous: Option<Word>,
+    events: [Event; WORD_EVENTS],
+    len: usize,
+    pos: usize,
+}
+
+impl<'a> Lane<'a> {
+    fn new(words: &'a [TimedWord], bus: BusId) -> Self {
+        Self {
+            words,
+            bus,
+            next: 0,
+            previous: None,
+            events: [NO_EVENT; WORD_EVENTS],
+            len: 0,
+            pos: 0,
+        }
+    }
+
+    fn find(&self, from: usize) -> Option<usize> {
+        (from..self.words.len()).find(|idx| self.words[*idx].bus == self.bus)
+    }
+
+    fn peek(&mut self) -> Option<Event> {
+        if self.pos == self.len {
+            self.refill();
+        }
+        (self.pos < self.len).then(|| self.events[self.pos])
+    }
+
+    fn refill(&mut self) {
+        self.len = 0;
+        self.pos = 0;
+        let Some(idx) = self.find(self.next) else {
+            return;
+        };
+        self.next = idx + 1;
+        let TimedWord { time_ns, word, .. } = self.words[idx];
+        let following = self.find(self.next).map(|next| self.words[next]);
+        let mut push = |time_ns, signal, value| {
+            self.events[self.len] = Event {
+                time_ns,
+                signal,
+                value,
+            };
+            self.len += 1;
+        };
+
+        if starts_message(self.previous, word) {
+            push(time_ns, Signal::Message, Value::Message(true));
+        }
+        let (kind, value) = match word {
+            Word::Command(cmd) => (1, cmd.value()),
+            Word::Status(sw) => (2, sw.value()),
+            Word::Data(dw) => (3, dw.value()),
+        };
+        push(time_ns, Signal::Type, Value::Type(kind));
+        push(time_ns, Signal::Value, Value::Word(Some(value)));
+        let mut level = None;
+        for (half, high) in manchester::encode_word(word).into_iter().enumerate() {
+            if level != Some(high) {
+                let at = time_ns + (half as u32 * HALF_BIT_NS) as u64;
+                push(at, Signal::Line, Value::Level(Some(high)));
+                level = Some(high);
+            }
+        }
+        let end = time_ns + WORD_NS as u64;
+        if following.is_none_or(|next| next.time_ns > end) {
+            push(end, Signal::Line, Value::Level(None));
+            push(end, Signal::Type, Value::Type(0));
+            push(end, Signal::Value, Value::Word(None));
+        }
+        if following.is_none_or(|next| starts_message(Some(word), next.word)) {
+            push(end, Signal::Message, Value::Message(false));
+        }
+        self.previous = Some(word);
+    }
+}
+
+/// A command starts a message, but for the transmit command right after
+/// the receive command of an RT to RT transfer.
+fn starts_message(previous: Option<Word>, word: Word) -> bool {
+    let Word::Command(cmd) = word else {
+        return false;
+    };
+    let Some(Word::Command(first)) = previous else {
+        return true;
+    };
+    let tx = Fields::of(&cmd);
+    let second = MessageFormat::of(&first).with_transmit_command().is_some()
+        && tx.transmit
+        && !tx.mode
+        && !tx.broadcast();
+    !second
+}
+
+fn id(bus: BusId, signal: Signal) -> char {
+    let ids = match bus {
+        // Not `#` nor `$`, which readers may take for a time or a keyword.
+        BusId::A => ['!', '"', '%', '&'],
+        BusId::B => ['\'', '(', ')', '*'],
+    };
+    ids[signal as usize]
+}
+
+fn write_value(out: &mut impl Write, id: char, value: Value) -> fmt::Result {
+    match value {
+        Value::Level(Some(high)) => writeln!(out, "{}{}", high as u8, id),
+        Value::Level(None) => writeln!(out, "z{}", id),
+        Value::Type(kind) => writeln!(out, "b{:b} {}", kind, id),
+        Value::Word(Some(value)) => writeln!(out, "b{:b} {}", value, id),
+        Value::Word(None) => writeln!(out, "bx {}", id),
+        Value::Message(on) => writeln!(out, "{}{}", on as u8, id),
+    }
+}
+
+/// Write `words` as a VCD file, 1 ns per tick. Words must be in time order
+/// and not overlap on the same bus.
+pub fn write_vcd(out: &mut impl Write, words: &[TimedWord]) -> fmt::Result {
+    writeln!(out, "$version milisse $end")?;
+    writeln!(out, "$timescale 1ns $end")?;
+    writeln!(out, "$scope module milstd1553 $end")?;
+    for (bus, name) in [(BusId::A, 'a'), (BusId::B, 'b')] {
+        writeln!(
+            out,
+            "$var wire 1 {} bus_{} $end",
+            id(bus, Signal::Line),
+            name
+        )?;
+        writeln!(
+            out,
+            "$var reg 2 {} type_{} $end",
+            id(bus, Signal::Type),
+            name
+        )?;
+        writeln!(
+            out,
+            "$var reg 16 {} word_{} $end",
+            id(bus, Signal::Value),
+            name
+        )?;
+        writeln!(
+            out,
+            "$var wire 1 {} msg_{} $end",
+            id(bus, Signal::Message),
+            name
+        )?;
+    }
+    writeln!(out, "$upscope $end")?;
+    writeln!(out, "$enddefinitions $end")?;
+    writeln!(out, "#0")?;
+    writeln!(out, "$dumpvars")?;
+    for bus in [BusId::A, BusId::B] {
+        write_value(out, id(bus, Signal::Line), Value::Level(None))?;
+        write_value(out, id(bus, Signal::Type), Value::Type(0))?;
+        write_value(out, id(bus, Signal::Value), Value::Word(None))?;
+        write_value(out, id(bus, Signal::Message), Value::Message(false))?;
+    }
+    writeln!(out, "$end")?;
+
+    let mut lanes = [Lane::new(words, BusId::A), Lane::new(words, BusId::B)];
+    let mut now = 0;
+    loop {
+        let lane = match (lanes[0].peek(), lanes[1].peek()) {
+            (None, None) => return Ok(()),
+            (Some(_), None) => 0,
+            (None, Some(_)) => 1,
+            (Some(a), Some(b)) => (b.time_ns < a.time_ns) as usize,
+        };
+        let event = lanes[lane].peek().unwrap();
+        lanes[lane].pos += 1;
+        if event.time_ns != now {
+            writeln!(out, "#{}", event.time_ns)?;
+            now = event.time_ns;
+        }
+        write_value(out, id(lanes[lane].bus, event.signal), event.value)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::string::String;
+
+    use crate::{vcd::*, words::*};
+
+    #[test]
+    fn exports_both_buses_in_time_order() {
+        let words = [
+            TimedWord::new(0, BusId::A, Word::Command(CommandWord::from_u16(0x0821))),
+            TimedWord::new(20_000, BusId::A, Word::Data(DataWord::from_u16(0xFFFF))),
+            TimedWord::new(
+                30_000,
+                BusId::B,
+                Word::Command(CommandWord::from_u16(0x0C21)),
+            ),
+            TimedWord::new(48_000, BusId::A, Word::Status(StatusWord::from_u16(0x0800))),
+        ];
+        let mut vcd = String::new();
+        write_vcd(&mut vcd, &words).unwrap();
+
+        assert!(vcd.contains("$var wire 1 ! bus_a $end"));
+        assert!(vcd.contains("$var reg 16 ) word_b $end"));
+        let body = vcd.split("$dumpvars").nth(1).unwrap();
+        let times: std::vec::Vec<u64> = body
+            .lines()
+            .filter_map(|line| line.strip_prefix('#'))
+            .map(|time| time.parse().unwrap())
+            .collect();
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // Command then data back to back: the line stays busy between them.
+        let at = |time: u64| {
+            // Time 0 changes follow the initial values.
+            let marker = match time {
+                0 => String::from("$end\n"),
+                _ => std::format!("\n#{}\n", time),
+            };
+            let rest = &body[body.find(&marker).unwrap() + marker.len()..];
+            &rest[..rest.find("\n#").map_or(rest.len(), |end| end + 1)]
+        };
+        assert_eq!(at(0), "1&\nb1 \"\nb100000100001 %\n1!\n");
+        assert!(at(20_000).contains("b11 \"") && !at(20_000).contains("z!"));
+        assert!(at(30_000).contains("1*") && at(30_000).contains("1'"));
+        assert!(at(68_000).contains("z!") && at(68_000).contains("0&"));
+        assert!(at(50_000).contains("z'") && at(50_000).contains("0*"));
+        // The status word goes on with the message started by the command.
+        assert!(!at(40_000).contains('&'));
+    }
+
+    #[test]
+    fn commands_without_replies_end_their_message() {
+        // A broadcast synchronize, then a BC to RT message right after it,
+        // and an RT to RT transfer.
+        let command = |time_ns, value| {
+            TimedWord::new(
+                time_ns,
+                BusId::A,
+                Word::Command(CommandWord::from_u16(value)),
+            )
+        };
+        let words = [
+            command(0, 0xFC01),
+            command(24_000, 0x2821),
+            TimedWord::new(44_000, BusId::A, Word::Data(DataWord::from_u16(1))),
+            TimedWord::new(70_000, BusId::A, Word::Status(StatusWord::from_u16(0x2800))),
+            command(100_000, 0x2821),
+            command(120_000, 0x1C21),
+        ];
+        let mut vcd = String::new();
+        write_vcd(&mut vcd, &words).unwrap();
+        let body = vcd.split("$dumpvars").nth(1).unwrap();
+        let msg = |time: u64| {
+            let marker = std::format!("\n#{}\n", time);
+            let rest = &body[body.find(&marker).unwrap() + marker.len()..];
+            let changes = &rest[..rest.find("\n#").map_or(rest.len(), |end| end + 1)];
+            (changes.contains("0&"), changes.contains("1&"))
+        };
+        // The sync ends where it started, and the next message starts.
+        assert_eq!(msg(20_000), (true, false));
+        assert_eq!(msg(24_000), (false, true));
+        assert_eq!(msg(90_000), (true, false));
+        // Both commands of the RT to RT transfer make one message.
+        assert_eq!(msg(100_000), (false, true));
+        assert_eq!(msg(120_000), (false, false));
+    }
+}
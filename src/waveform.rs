@@ -0,0 +1,320 @@
+//! Sampled analog waveform of bus traffic, as a scope or ADC sees the
+//! differential bus signal, and decoding it back into words.
+use crate::{
+    manchester::{self, DecodedWord, Sync, WordError, HALF_BITS, HALF_BIT_NS, WORD_NS},
+    words::Word,
+};
+
+/// Shortest level run taken for half a sync. Data bits never hold a level
+/// longer than 1 µs.
+const SYNC_MIN_NS: f64 = 1_250.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WaveformConfig {
+    pub sample_rate_hz: u32,
+    /// Peak differential voltage: the signal swings between plus and minus
+    /// this.
+    pub amplitude: f32,
+    /// 10 % to 90 % rise (and fall) time. 0 for square edges.
+    pub rise_time_ns: u32,
+    /// RMS of the noise added to every sample.
+    pub noise_rms: f32,
+    /// Drives the noise, so a waveform can be generated again.
+    pub seed: u32,
+}
+
+impl Default for WaveformConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate_hz: 50_000_000,
+            amplitude: 3.5,
+            rise_time_ns: 200,
+            noise_rms: 0.0,
+            seed: 1,
+        }
+    }
+}
+
+impl WaveformConfig {
+    fn sample_ns(&self) -> f64 {
+        1e9 / self.sample_rate_hz as f64
+    }
+
+    /// Samples it takes to cover `duration_ns`.
+    pub fn samples_for(&self, duration_ns: u64) -> usize {
+        (duration_ns as u128 * self.sample_rate_hz as u128).div_ceil(1_000_000_000) as usize
+    }
+}
+
+/// Render words, each starting at its time in ns, into `out`. Times must
+/// be increasing; the bus is idle (0 V) between words. Returns the number
+/// of samples written: up to 1 µs of idle after the last word, or all of
+/// `out` if it is too short.
+pub fn synthesize(config: &WaveformConfig, words: &[(u64, Word)], out: &mut [f32]) -> usize {
+    render(
+        config,
+        words.len(),
+        |idx| (words[idx].0, manchester::encode_word(words[idx].1)),
+        out,
+    )
+}
+
+/// Like [`synthesize`], from raw half-bit levels, e.g. to put words with
+/// Manchester or parity errors on the bus.
+pub fn synthesize_levels(
+    config: &WaveformConfig,
+    words: &[(u64, [bool; HALF_BITS])],
+    out: &mut [f32],
+) -> usize {
+    render(config, words.len(), |idx| words[idx], out)
+}
+
+fn render(
+    config: &WaveformConfig,
+    count: usize,
+    word: impl Fn(usize) -> (u64, [bool; HALF_BITS]),
+    out: &mut [f32],
+) -> usize {
+    let end = match count {
+        0 => 0,
+        _ => word(count - 1).0 + (WORD_NS + manchester::BIT_NS) as u64,
+    };
+    let len = config.samples_for(end).min(out.len());
+    let sample_ns = config.sample_ns();
+    // 10 % to 90 % of a full swing in the rise time.
+    let max_step = match config.rise_time_ns {
+        0 => f32::INFINITY,
+        rise => (1.6 * config.amplitude) * (sample_ns / rise as f64) as f32,
+    };
+    let mut rng = if config.seed == 0 {
+        0x9E37_79B9
+    } else {
+        config.seed
+    };
+    // Start each swing half its length early, so the zero crossings fall
+    // on the bit boundaries.
+    let lead = config.rise_time_ns as f64 / 1.6;
+    let mut current = 0;
+    let mut level = 0.0f32;
+    for (idx, sample) in out[..len].iter_mut().enumerate() {
+        let time = idx as f64 * sample_ns + lead;
+        while current < count && time >= (word(current).0 + WORD_NS as u64) as f64 {
+            current += 1;
+        }
+        let target = match (current < count).then(|| word(current)) {
+            Some((start, levels)) if time >= start as f64 => {
+                let half = ((time - start as f64) / HALF_BIT_NS as f64) as usize;
+                if levels[half.min(HALF_BITS - 1)] {
+                    config.amplitude
+                } else {
+                    -config.amplitude
+                }
+            }
+            _ => 0.0,
+        };
+        level += (target - level).clamp(-max_step, max_step);
+        *sample = level + config.noise_rms * gaussian(&mut rng);
+    }
+    len
+}
+
+/// Roughly normal, unit variance: the sum of 12 uniform draws.
+fn gaussian(rng: &mut u32) -> f32 {
+    let mut sum = 0.0;
+    for _ in 0..12 {
+        *rng ^= *rng << 13;
+        *rng ^= *rng >> 17;
+        *rng ^= *rng << 5;
+        sum += *rng as f32 / u32::MAX as f32;
+    }
+    sum - 6.0
+}
+
+/// Decoder for sampled captures. The sample rate must give at least two
+/// samples per half-bit (4 MHz); scopes usually run far above that.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledDecoder {
+    sample_rate_hz: u32,
+    threshold: f32,
+}
+
+impl SampledDecoder {
+    /// Samples above `threshold` volts read positive, below `-threshold`
+    /// negative, and the bus is idle in between.
+    pub fn new(sample_rate_hz: u32, threshold: f32) -> Self {
+        Self {
+            sample_rate_hz,
+            threshold,
+        }
+    }
+
+    fn level(&self, sample: f32) -> i8 {
+        if sample > self.threshold {
+            1
+        } else if sample < -self.threshold {
+            -1
+        } else {
+            0
+        }
+    }
+
+    /// Decode the words of `samples` into `out`, returning how many were
+    /// found. Words that don't fit in `out` are dropped.
+    pub fn decode(&self, samples: &[f32], out: &mut [DecodedWord]) -> usize {
+        let sample_ns = 1e9 / self.sample_rate_hz as f64;
+        let mut found = 0;
+        // The level being held, since when, and its last sample.
+        let mut run = 0;
+        let mut run_start = 0.0;
+        let mut last = 0;
+        let mut idx = 0;
+        while idx < samples.len() && found < out.len() {
+            let level = self.level(samples[idx]);
+            if level == 0 || level == run {
+                if level != 0 {
+                    last = idx;
+                } else if (idx - last) as f64 * sample_ns > manchester::BIT_NS as f64 {
+                    run = 0;
+                }
+                idx += 1;
+                continue;
+            }
+            let crossing = match run {
+                0 => idx as f64 * sample_ns,
+                _ => (last + idx) as f64 / 2.0 * sample_ns,
+            };
+            if run != 0 && crossing - run_start >= SYNC_MIN_NS {
+                if let Some(word) = self.word_at(samples, sample_ns, crossing, run > 0) {
+                    out[found] = word;
+                    found += 1;
+                    // Carry on from the middle of the parity bit's second
+                    // half, which may run into the next sync.
+                    let half_ns = HALF_BIT_NS as f64;
+                    let word_start = crossing - 3.0 * half_ns;
+                    idx = ((word_start + (HALF_BITS as f64 - 0.5) * half_ns) / sample_ns) as usize;
+                    run = samples.get(idx).map_or(0, |s| self.level(*s));
+                    run_start = word_start + (HALF_BITS - 1) as f64 * half_ns;
+                    last = idx;
+                    idx += 1;
+                    continue;
+                }
+            }
+            run = level;
+            run_start = crossing;
+            last = idx;
+            idx += 1;
+        }
+        found
+    }
+
+    /// The word whose mid-sync transition is at `crossing`, if a sync
+    /// really follows.
+    fn word_at(
+        &self,
+        samples: &[f32],
+        sample_ns: f64,
+        crossing: f64,
+        positive_first: bool,
+    ) -> Option<DecodedWord> {
+        let half_ns = HALF_BIT_NS as f64;
+        let start = crossing - 3.0 * half_ns;
+        let level_at = |time: f64| {
+            samples
+                .get((time / sample_ns + 0.5) as usize)
+                .map_or(0, |s| self.level(*s))
+        };
+        let second = if positive_first { -1 } else { 1 };
+        if level_at(crossing + 1.5 * half_ns) != second
+            || level_at(crossing + 2.5 * half_ns) != second
+        {
+            return None;
+        }
+        let mut levels = [false; HALF_BITS];
+        let mut truncated = false;
+        for (half, level) in levels.iter_mut().enumerate() {
+            *level = match half {
+                0..3 => positive_first,
+                3..6 => !positive_first,
+                _ => match level_at(start + (half as f64 + 0.5) * half_ns) {
+                    0 => {
+                        truncated = true;
+                        false
+                    }
+                    sign => sign > 0,
+                },
+            };
+        }
+        let mut word = manchester::decode(&levels)?;
+        if truncated {
+            word.error = Some(WordError::Truncated);
+        }
+        debug_assert_eq!(word.sync == Sync::CommandStatus, positive_first);
+        Some(DecodedWord {
+            time_ns: (start.max(0.0) + 0.5) as u64,
+            word,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{manchester::*, waveform::*, words::*};
+
+    const WORDS: [(u64, Word); 3] = [
+        (2_000, Word::Command(CommandWord::from_u16(0x2422))),
+        (22_000, Word::Data(DataWord::from_u16(0xA5F0))),
+        (50_000, Word::Status(StatusWord::from_u16(0x2000))),
+    ];
+
+    #[test]
+    fn noisy_waveform_decodes_back() {
+        let config = WaveformConfig {
+            noise_rms: 0.3,
+            seed: 42,
+            ..WaveformConfig::default()
+        };
+        let mut samples = [0.0; 4096];
+        let len = synthesize(&config, &WORDS, &mut samples);
+        assert_eq!(len, config.samples_for(71_000));
+        assert!(samples[..len].iter().any(|s| *s > 3.5));
+
+        let decoder = SampledDecoder::new(config.sample_rate_hz, 1.0);
+        let mut out = [DecodedWord {
+            time_ns: 0,
+            word: RawWord::from_word(WORDS[0].1),
+        }; 4];
+        assert_eq!(decoder.decode(&samples[..len], &mut out), 3);
+        for ((time, word), decoded) in WORDS.iter().zip(out) {
+            assert!(decoded.time_ns.abs_diff(*time) <= 40, "{:?}", decoded);
+            let status = matches!(word, Word::Status(_));
+            assert_eq!(decoded.word.to_word(status), Ok(*word));
+        }
+    }
+
+    #[test]
+    fn errors_are_classified() {
+        let config = WaveformConfig::default();
+        let mut bad_parity = encode(Sync::Data, 0x1234);
+        bad_parity[38] = !bad_parity[38];
+        bad_parity[39] = !bad_parity[39];
+        let mut no_transition = encode(Sync::CommandStatus, 0x1234);
+        no_transition[11] = no_transition[10];
+        let words = [(0, bad_parity), (20_000, no_transition)];
+        let mut samples = [0.0; 2048];
+        let len = synthesize_levels(&config, &words, &mut samples);
+
+        let decoder = SampledDecoder::new(config.sample_rate_hz, 1.0);
+        let mut out = [DecodedWord {
+            time_ns: 0,
+            word: RawWord::from_word(WORDS[0].1),
+        }; 2];
+        assert_eq!(decoder.decode(&samples[..len], &mut out), 2);
+        assert_eq!(out[0].word.error, Some(WordError::Parity));
+        assert_eq!(out[1].word.error, Some(WordError::Manchester));
+
+        // Capture cut in the middle of the second word.
+        let cut = config.samples_for(30_000);
+        assert_eq!(decoder.decode(&samples[..cut], &mut out), 2);
+        assert_eq!(out[1].word.error, Some(WordError::Truncated));
+    }
+}
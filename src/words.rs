@@ -4,7 +4,7 @@ const SUBADDRESS_MODE_CODE_0: u8 = 0b00000; // Subaddress for mode code
 const SUBADDRESS_MODE_CODE_1: u8 = 0b11111; // Subaddress for mode code
 pub const BROADCAST_ADDR: u8 = 0b11111; // Address for Brodcast mode.
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Word {
     Command(CommandWord),
     Data(DataWord),
@@ -32,7 +32,7 @@ impl From<BitField<5>> for RTAddr {
         if BROADCAST_ADDR == bitfield.into() {
             RTAddr::Broadcast
         } else {
-            RTAddr::Single(bitfield.into())
+            RTAddr::Single(bitfield)
         }
     }
 }
@@ -151,7 +151,26 @@ pub struct ModeCodeOptions {
     pub broadcast_allowed: bool,
 }
 impl ModeCode {
-    pub fn associated_options(&self) -> ModeCodeOptions {
+    /// Every defined (non reserved) mode code.
+    pub const ALL: [ModeCode; 15] = [
+        ModeCode::DynamicBusControl,
+        ModeCode::Synchronize,
+        ModeCode::TransmitStatusWord,
+        ModeCode::InitiateSelfTest,
+        ModeCode::TransmitterShutdown,
+        ModeCode::OverrideTransmitter,
+        ModeCode::InhibitTerminalFlagBit,
+        ModeCode::OverrideInhibitTerminalFlagBit,
+        ModeCode::ResetRT,
+        ModeCode::TransmitVectorWord,
+        ModeCode::SynchronizeWithDataWord,
+        ModeCode::TransmitLastCommand,
+        ModeCode::TransmitBITWord,
+        ModeCode::SelectedTransmitter,
+        ModeCode::OverrideSelectedTransmitter,
+    ];
+
+    pub const fn associated_options(&self) -> ModeCodeOptions {
         match *self {
             ModeCode::DynamicBusControl => ModeCodeOptions {
                 tr: RTAction::Transmit,
@@ -231,11 +250,10 @@ impl ModeCode {
             ModeCode::Invalid => unreachable!(),
         }
     }
-}
 
-impl From<ModeCode> for u8 {
-    fn from(code: ModeCode) -> Self {
-        match code {
+    /// The 5-bit value of the mode code.
+    pub const fn code(&self) -> u8 {
+        match *self {
             ModeCode::DynamicBusControl => 0b00000,
             ModeCode::Synchronize => 0b00001,
             ModeCode::TransmitStatusWord => 0b00010,
@@ -256,6 +274,12 @@ impl From<ModeCode> for u8 {
     }
 }
 
+impl From<ModeCode> for u8 {
+    fn from(code: ModeCode) -> Self {
+        code.code()
+    }
+}
+
 impl From<u8> for ModeCode {
     fn from(value: u8) -> Self {
         match value {
@@ -291,7 +315,7 @@ impl From<ModeCode> for BitField<5> {
  *  Bits [9:5]: Subaddress Mode.
  *  Bits [4:0]: Data Word Count / Mode Code.
 **/
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct CommandWord {
     raw_value: u16,
 }
@@ -307,7 +331,7 @@ impl CommandWord {
     }
 
     /// Initialize a CommandWord from a u16.
-    pub fn from_u16(value: u16) -> Self {
+    pub const fn from_u16(value: u16) -> Self {
         Self { raw_value: value }
     }
 
@@ -334,7 +358,7 @@ impl CommandWord {
     }
 
     pub fn get_rt_addr(&self) -> RTAddr {
-        return RTAddr::read(self.raw_value);
+        RTAddr::read(self.raw_value)
     }
 
     pub fn set_rt_addr(&mut self, addr: RTAddr) {
@@ -342,7 +366,7 @@ impl CommandWord {
     }
 
     pub fn get_tr_bit(&self) -> RTAction {
-        return RTAction::read(self.raw_value);
+        RTAction::read(self.raw_value)
     }
 
     /// Set the T/R bit. If the CommandWord is a Mode Code Command,
@@ -355,7 +379,7 @@ impl CommandWord {
     }
 
     pub fn get_command_data(&self) -> CommandWordData {
-        return CommandWordData::read(self.raw_value);
+        CommandWordData::read(self.raw_value)
     }
 
     /// Sets the Subaddress field to the Mode Code value
@@ -484,12 +508,15 @@ impl AlignableBitField<1, 0> for TerminalFlag {}
  *  Bits [2:1]: Dynamic Bus Control bit.
  *  Bits [1:0]: Terminal flag bit.
 **/
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct StatusWord {
     raw_value: u16,
 }
 
 impl StatusWord {
+    /// Positional constructor kept for compatibility. Prefer
+    /// [`StatusWord::builder`], which names every flag.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         addr: RTAddr,
         msg_err: MessageError,
@@ -513,24 +540,56 @@ impl StatusWord {
         Self { raw_value: value }
     }
 
+    /// Start building a StatusWord for `addr` with every flag cleared.
+    pub fn builder(addr: RTAddr) -> StatusWordBuilder {
+        StatusWordBuilder::new(addr)
+    }
+
     pub fn value(&self) -> u16 {
         self.raw_value
     }
 
-    pub fn from_u16(value: u16) -> Self {
+    pub const fn from_u16(value: u16) -> Self {
         Self { raw_value: value }
     }
 
     pub fn get_rt_addr(&self) -> RTAddr {
-        return RTAddr::read(self.raw_value);
+        RTAddr::read(self.raw_value)
     }
 
     pub fn set_rt_addr(&mut self, addr: RTAddr) {
         self.raw_value = addr.set_in(self.raw_value)
     }
 
+    /// Read every flag at once.
+    pub fn flags(&self) -> StatusFlags {
+        StatusFlags {
+            message_error: self.get_message_error().into(),
+            instrumentation: self.get_instrumentation().into(),
+            service_request: self.get_service_request().into(),
+            broadcast_command: self.get_broadcast_command().into(),
+            busy: self.get_busy().into(),
+            subsystem: self.get_subsystem().into(),
+            dynamic_bus_control: self.get_dynamic_bus_control().into(),
+            terminal_flag: self.get_terminal_flag().into(),
+        }
+    }
+
+    /// Overwrite every flag at once. The RT address and the
+    /// reserved bits are left untouched.
+    pub fn set_flags(&mut self, flags: StatusFlags) {
+        self.set_message_error(flags.message_error.into());
+        self.set_instrumentation(flags.instrumentation.into());
+        self.set_service_request(flags.service_request.into());
+        self.set_broadcast_command(flags.broadcast_command.into());
+        self.set_busy(flags.busy.into());
+        self.set_subsystem(flags.subsystem.into());
+        self.set_dynamic_bus_control(flags.dynamic_bus_control.into());
+        self.set_terminal_flag(flags.terminal_flag.into());
+    }
+
     pub fn get_message_error(&self) -> MessageError {
-        return MessageError::read(self.raw_value);
+        MessageError::read(self.raw_value)
     }
 
     pub fn set_message_error(&mut self, flag: MessageError) {
@@ -538,7 +597,7 @@ impl StatusWord {
     }
 
     pub fn get_instrumentation(&self) -> Instrumentation {
-        return Instrumentation::read(self.raw_value);
+        Instrumentation::read(self.raw_value)
     }
 
     pub fn set_instrumentation(&mut self, flag: Instrumentation) {
@@ -546,15 +605,15 @@ impl StatusWord {
     }
 
     pub fn get_service_request(&self) -> ServiceRequest {
-        return ServiceRequest::read(self.raw_value);
+        ServiceRequest::read(self.raw_value)
     }
 
-    pub fn set_service_request(&mut self, flag: Instrumentation) {
+    pub fn set_service_request(&mut self, flag: ServiceRequest) {
         self.raw_value = flag.set_in(self.raw_value)
     }
 
     pub fn get_broadcast_command(&self) -> BroadcastCommand {
-        return BroadcastCommand::read(self.raw_value);
+        BroadcastCommand::read(self.raw_value)
     }
 
     pub fn set_broadcast_command(&mut self, flag: BroadcastCommand) {
@@ -562,7 +621,7 @@ impl StatusWord {
     }
 
     pub fn get_busy(&self) -> Busy {
-        return Busy::read(self.raw_value);
+        Busy::read(self.raw_value)
     }
 
     pub fn set_busy(&mut self, flag: Busy) {
@@ -570,7 +629,7 @@ impl StatusWord {
     }
 
     pub fn get_subsystem(&self) -> Subsystem {
-        return Subsystem::read(self.raw_value);
+        Subsystem::read(self.raw_value)
     }
 
     pub fn set_subsystem(&mut self, flag: Subsystem) {
@@ -578,7 +637,7 @@ impl StatusWord {
     }
 
     pub fn get_dynamic_bus_control(&self) -> DynamicBusControl {
-        return DynamicBusControl::read(self.raw_value);
+        DynamicBusControl::read(self.raw_value)
     }
 
     pub fn set_dynamic_bus_control(&mut self, flag: DynamicBusControl) {
@@ -586,7 +645,7 @@ impl StatusWord {
     }
 
     pub fn get_terminal_flag(&self) -> TerminalFlag {
-        return TerminalFlag::read(self.raw_value);
+        TerminalFlag::read(self.raw_value)
     }
 
     pub fn set_terminal_flag(&mut self, flag: TerminalFlag) {
@@ -594,17 +653,125 @@ impl StatusWord {
     }
 }
 
+/// Snapshot of every flag in a StatusWord, so monitors and BC health
+/// logic can look at (and compare) all of them in one go.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StatusFlags {
+    pub message_error: bool,
+    pub instrumentation: bool,
+    pub service_request: bool,
+    pub broadcast_command: bool,
+    pub busy: bool,
+    pub subsystem: bool,
+    pub dynamic_bus_control: bool,
+    pub terminal_flag: bool,
+}
+
+impl StatusFlags {
+    /// Flags whose value differs between `self` and `other`.
+    pub fn diff(&self, other: &StatusFlags) -> StatusFlags {
+        StatusFlags {
+            message_error: self.message_error != other.message_error,
+            instrumentation: self.instrumentation != other.instrumentation,
+            service_request: self.service_request != other.service_request,
+            broadcast_command: self.broadcast_command != other.broadcast_command,
+            busy: self.busy != other.busy,
+            subsystem: self.subsystem != other.subsystem,
+            dynamic_bus_control: self.dynamic_bus_control != other.dynamic_bus_control,
+            terminal_flag: self.terminal_flag != other.terminal_flag,
+        }
+    }
+
+    /// True if at least one flag is set.
+    pub fn any(&self) -> bool {
+        *self != StatusFlags::default()
+    }
+}
+
+/// Builder for [`StatusWord`]. Every flag starts cleared.
+///
+/// ```
+/// use milisse::words::*;
+/// let sw = StatusWord::builder(RTAddr::Single(5.into()))
+///     .message_error(true)
+///     .busy(true)
+///     .build();
+/// assert!(sw.flags().busy);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct StatusWordBuilder {
+    word: StatusWord,
+}
+
+impl StatusWordBuilder {
+    pub fn new(addr: RTAddr) -> Self {
+        Self {
+            word: StatusWord::from_u16(addr.align_to_word()),
+        }
+    }
+
+    pub fn message_error(mut self, flag: impl Into<MessageError>) -> Self {
+        self.word.set_message_error(flag.into());
+        self
+    }
+
+    pub fn instrumentation(mut self, flag: impl Into<Instrumentation>) -> Self {
+        self.word.set_instrumentation(flag.into());
+        self
+    }
+
+    pub fn service_request(mut self, flag: impl Into<ServiceRequest>) -> Self {
+        self.word.set_service_request(flag.into());
+        self
+    }
+
+    pub fn broadcast_command(mut self, flag: impl Into<BroadcastCommand>) -> Self {
+        self.word.set_broadcast_command(flag.into());
+        self
+    }
+
+    pub fn busy(mut self, flag: impl Into<Busy>) -> Self {
+        self.word.set_busy(flag.into());
+        self
+    }
+
+    pub fn subsystem(mut self, flag: impl Into<Subsystem>) -> Self {
+        self.word.set_subsystem(flag.into());
+        self
+    }
+
+    pub fn dynamic_bus_control(mut self, flag: impl Into<DynamicBusControl>) -> Self {
+        self.word.set_dynamic_bus_control(flag.into());
+        self
+    }
+
+    pub fn terminal_flag(mut self, flag: impl Into<TerminalFlag>) -> Self {
+        self.word.set_terminal_flag(flag.into());
+        self
+    }
+
+    /// Set every flag from a snapshot.
+    pub fn flags(mut self, flags: StatusFlags) -> Self {
+        self.word.set_flags(flags);
+        self
+    }
+
+    pub fn build(self) -> StatusWord {
+        self.word
+    }
+}
+
 /*
 // Data Words.
 */
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct DataWord {
     raw_value: u16, // Data 16 bit field.
 }
 
 impl DataWord {
-    pub fn from_u16(value: u16) -> Self {
+    pub const fn from_u16(value: u16) -> Self {
         Self { raw_value: value }
     }
 
@@ -700,4 +867,38 @@ mod tests {
         );
         assert_eq!(word.value(), 0b1010101000010101);
     }
+
+    #[test]
+    fn status_word_builder() {
+        let word = StatusWord::builder(RTAddr::Single(21.into()))
+            .instrumentation(true)
+            .broadcast_command(true)
+            .subsystem(true)
+            .terminal_flag(true)
+            .build();
+        assert_eq!(word.value(), 0b1010101000010101);
+        assert_eq!(word.get_rt_addr(), RTAddr::Single(21.into()));
+    }
+
+    #[test]
+    fn status_flags_roundtrip_and_diff() {
+        let mut word = StatusWord::builder(RTAddr::Single(3.into()))
+            .service_request(true)
+            .build();
+        let before = word.flags();
+        assert!(before.service_request);
+        assert!(!before.busy);
+
+        let mut after = before;
+        after.busy = true;
+        after.service_request = false;
+        word.set_flags(after);
+        assert_eq!(word.flags(), after);
+        assert_eq!(word.get_rt_addr(), RTAddr::Single(3.into()));
+
+        let changed = before.diff(&word.flags());
+        assert!(changed.busy && changed.service_request);
+        assert!(!changed.message_error);
+        assert!(!before.diff(&before).any());
+    }
 }